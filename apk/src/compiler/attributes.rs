@@ -1,4 +1,4 @@
-use crate::compiler::table::{Ref, Table};
+use crate::compiler::table::{Entry, Ref, Table};
 use crate::res::{ResAttributeType, ResValue, ResValueType};
 use anyhow::{Context, Result};
 use roxmltree::Attribute;
@@ -6,36 +6,22 @@ use std::collections::{BTreeMap, BTreeSet};
 
 pub fn compile_attr(table: &Table, name: &str, value: &str, strings: &Strings) -> Result<ResValue> {
     let entry = table.entry_by_ref(Ref::attr(name))?;
-    let attr_type = entry.attribute_type().unwrap();
-    let (data, data_type) = match attr_type {
-        ResAttributeType::Reference => {
-            let id = table.entry_by_ref(Ref::parse(value)?)?.id();
-            (u32::from(id), ResValueType::Reference)
-        }
-        ResAttributeType::String => (strings.id(value) as u32, ResValueType::String),
-        ResAttributeType::Integer => (value.parse()?, ResValueType::IntDec),
-        ResAttributeType::Boolean => match value {
-            "true" => (0xffff_ffff, ResValueType::IntBoolean),
-            "false" => (0x0000_0000, ResValueType::IntBoolean),
-            _ => anyhow::bail!("expected boolean"),
-        },
-        ResAttributeType::Enum => {
-            let id = table.entry_by_ref(Ref::id(value))?.id();
-            let value = entry.lookup_value(id).unwrap();
-            (value.data, ResValueType::from_u8(value.data_type).unwrap())
-        }
-        ResAttributeType::Flags => {
-            let mut data = 0;
-            let mut data_type = ResValueType::Null;
-            for flag in value.split('|') {
-                let id = table.entry_by_ref(Ref::id(flag))?.id();
-                let value = entry.lookup_value(id).unwrap();
-                data |= value.data;
-                data_type = ResValueType::from_u8(value.data_type).unwrap();
-            }
-            (data, data_type)
-        }
-        _ => anyhow::bail!("unsupported attribute type"),
+    let (data, data_type) = if value == "@null" {
+        (0, ResValueType::Reference)
+    } else if entry.accepts(ResAttributeType::Reference) && value.starts_with('@') {
+        let id = table.entry_by_ref(Ref::parse(value)?)?.id();
+        (u32::from(id), ResValueType::Reference)
+    } else if entry.accepts(ResAttributeType::Reference) && value.starts_with('?') {
+        let id = table.entry_by_ref(Ref::parse(value)?)?.id();
+        (u32::from(id), ResValueType::Attribute)
+    } else if let Some(value) = compile_flags(table, entry, value) {
+        value
+    } else if let Some(value) = compile_value(entry, value) {
+        value
+    } else if entry.accepts(ResAttributeType::String) {
+        (strings.id(value) as u32, ResValueType::String)
+    } else {
+        anyhow::bail!("invalid value `{}` of attribute {}", value, name);
     };
     Ok(ResValue {
         size: 8,
@@ -45,6 +31,120 @@ pub fn compile_attr(table: &Table, name: &str, value: &str, strings: &Strings) -
     })
 }
 
+/// Looks up the values of enum and flags attributes.
+fn compile_flags(table: &Table, entry: Entry, value: &str) -> Option<(u32, ResValueType)> {
+    if !entry.accepts(ResAttributeType::Enum) && !entry.accepts(ResAttributeType::Flags) {
+        return None;
+    }
+    let mut data = 0;
+    let mut data_type = None;
+    for flag in value.split('|') {
+        let id = table.entry_by_ref(Ref::id(flag.trim())).ok()?.id();
+        let value = entry.lookup_value(id)?;
+        data |= value.data;
+        data_type = ResValueType::from_u8(value.data_type);
+    }
+    Some((data, data_type?))
+}
+
+/// Parses the literal value types the attribute accepts.
+fn compile_value(entry: Entry, value: &str) -> Option<(u32, ResValueType)> {
+    if entry.accepts(ResAttributeType::Color) {
+        if let Some(color) = parse_color(value) {
+            return Some(color);
+        }
+    }
+    if entry.accepts(ResAttributeType::Boolean) {
+        match value {
+            "true" => return Some((0xffff_ffff, ResValueType::IntBoolean)),
+            "false" => return Some((0x0000_0000, ResValueType::IntBoolean)),
+            _ => {}
+        }
+    }
+    if entry.accepts(ResAttributeType::Integer) {
+        if let Some(int) = parse_integer(value) {
+            return Some(int);
+        }
+    }
+    if entry.accepts(ResAttributeType::Float) {
+        if let Ok(float) = value.parse::<f32>() {
+            return Some((float.to_bits(), ResValueType::Float));
+        }
+    }
+    if entry.accepts(ResAttributeType::Dimension) {
+        if let Some(dimension) = parse_dimension(value) {
+            return Some((dimension, ResValueType::Dimension));
+        }
+    }
+    None
+}
+
+/// Parses `#rgb`, `#argb`, `#rrggbb` and `#aarrggbb` into an argb color.
+pub fn parse_color(value: &str) -> Option<(u32, ResValueType)> {
+    let hex = value.strip_prefix('#')?;
+    let data = u32::from_str_radix(hex, 16).ok()?;
+    let nibbles = |data: u32| {
+        (0..4).rev().fold(0, |argb, i| {
+            let nibble = data >> (i * 4) & 0xf;
+            argb << 8 | nibble << 4 | nibble
+        })
+    };
+    Some(match hex.len() {
+        3 => (nibbles(data) | 0xff00_0000, ResValueType::IntColorRgb4),
+        4 => (nibbles(data), ResValueType::IntColorArgb4),
+        6 => (data | 0xff00_0000, ResValueType::IntColorRgb8),
+        8 => (data, ResValueType::IntColorArgb8),
+        _ => return None,
+    })
+}
+
+/// Parses decimal and `0x` prefixed hex integers.
+pub fn parse_integer(value: &str) -> Option<(u32, ResValueType)> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        let data = u32::from_str_radix(hex, 16).ok()?;
+        Some((data, ResValueType::IntHex))
+    } else {
+        let data = value.parse::<i32>().ok()?;
+        Some((data as u32, ResValueType::IntDec))
+    }
+}
+
+/// Encodes dimensions like `16dp` as a fixed point mantissa with the radix
+/// and unit in the low byte.
+pub fn parse_dimension(value: &str) -> Option<u32> {
+    const UNITS: [(&str, u32); 7] = [
+        ("px", 0),
+        ("dip", 1),
+        ("dp", 1),
+        ("sp", 2),
+        ("pt", 3),
+        ("in", 4),
+        ("mm", 5),
+    ];
+    let (number, unit) = UNITS
+        .iter()
+        .find_map(|(suffix, unit)| Some((value.strip_suffix(suffix)?, *unit)))?;
+    let number = number.trim().parse::<f32>().ok()?;
+    let bits = (number.abs() as f64 * (1 << 23) as f64 + 0.5) as u64;
+    // radix 23p0, 16p7, 8p15 and 0p23 with the shift of the mantissa
+    let (radix, shift) = if bits & 0x7f_ffff == 0 {
+        (0, 23)
+    } else if bits & !0x7f_ffff == 0 {
+        (3, 0)
+    } else if bits & !0x7fff_ffff == 0 {
+        (2, 8)
+    } else if bits & !0x7f_ffff_ffff == 0 {
+        (1, 16)
+    } else {
+        (0, 23)
+    };
+    let mut mantissa = (bits >> shift) as u32 & 0xff_ffff;
+    if number < 0.0 {
+        mantissa = mantissa.wrapping_neg() & 0xff_ffff;
+    }
+    Some(mantissa << 8 | radix << 4 | unit)
+}
+
 pub struct StringPoolBuilder<'a> {
     table: &'a Table,
     attributes: BTreeMap<u32, &'a str>,
@@ -65,7 +165,7 @@ impl<'a> StringPoolBuilder<'a> {
             if ns == "http://schemas.android.com/apk/res/android" {
                 let entry = self.table.entry_by_ref(Ref::attr(attr.name()))?;
                 self.attributes.insert(entry.id().into(), attr.name());
-                if entry.attribute_type() == Some(ResAttributeType::String) {
+                if entry.accepts(ResAttributeType::String) {
                     self.strings.insert(attr.value());
                 }
                 return Ok(());
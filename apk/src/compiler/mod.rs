@@ -3,12 +3,15 @@ use crate::res::{
     Chunk, ResTableConfig, ResTableEntry, ResTableHeader, ResTablePackageHeader,
     ResTableTypeHeader, ResTableTypeSpecHeader, ResTableValue, ResValue, ScreenType,
 };
+use crate::utils::Density;
 use anyhow::Result;
 
 mod attributes;
+mod resources;
 mod table;
 mod xml;
 
+pub use resources::Resources;
 pub use table::Table;
 
 pub fn compile_manifest(manifest: &AndroidManifest, table: &Table) -> Result<Chunk> {
@@ -16,19 +19,59 @@ pub fn compile_manifest(manifest: &AndroidManifest, table: &Table) -> Result<Chu
     xml::compile_xml(&xml, table)
 }
 
-const DPI_SIZE: [u32; 5] = [48, 72, 96, 144, 192];
+/// Compiles an xml resource like a layout or a vector drawable.
+pub fn compile_xml(xml: &str, table: &Table) -> Result<Chunk> {
+    xml::compile_xml(xml, table)
+}
 
-fn variants(name: &str) -> impl Iterator<Item = (String, u32)> + '_ {
-    DPI_SIZE
-        .into_iter()
-        .map(move |size| (format!("res/{0}/{0}{1}.png", name, size), size))
+fn variants<'a>(
+    name: &'a str,
+    densities: &'a [Density],
+) -> impl Iterator<Item = (String, u32)> + 'a {
+    densities.iter().map(move |density| {
+        let size = density.icon_size();
+        (format!("res/{0}/{0}{1}.png", name, size), size)
+    })
 }
 
-pub fn compile_mipmap<'a>(package_name: &str, name: &'a str) -> Result<Mipmap<'a>> {
+/// Compiles a mipmap resource with an icon per density, all densities are
+/// used when `densities` is empty. Devices scale the closest density, so a
+/// single one is enough for debug builds.
+pub fn compile_mipmap<'a>(
+    package_name: &str,
+    name: &'a str,
+    densities: &[Density],
+) -> Result<Mipmap<'a>> {
+    let mut densities = if densities.is_empty() {
+        Density::ALL.to_vec()
+    } else {
+        densities.to_vec()
+    };
+    densities.sort_unstable();
+    densities.dedup();
+    let mut chunks = vec![
+        Chunk::StringPool(vec!["mipmap".to_string()], vec![]),
+        Chunk::StringPool(vec!["icon".to_string()], vec![]),
+        Chunk::TableTypeSpec(
+            ResTableTypeSpecHeader {
+                id: 1,
+                res0: 0,
+                res1: 0,
+                entry_count: 1,
+            },
+            vec![256],
+        ),
+    ];
+    for (string_id, density) in densities.iter().enumerate() {
+        chunks.push(mipmap_table_type(1, density.dpi(), string_id as u32));
+    }
     let chunk = Chunk::Table(
         ResTableHeader { package_count: 1 },
         vec![
-            Chunk::StringPool(variants(name).map(|(res, _)| res).collect(), vec![]),
+            Chunk::StringPool(
+                variants(name, &densities).map(|(res, _)| res).collect(),
+                vec![],
+            ),
             Chunk::TablePackage(
                 ResTablePackageHeader {
                     id: 127,
@@ -39,28 +82,15 @@ pub fn compile_mipmap<'a>(package_name: &str, name: &'a str) -> Result<Mipmap<'a
                     last_public_key: 1,
                     type_id_offset: 0,
                 },
-                vec![
-                    Chunk::StringPool(vec!["mipmap".to_string()], vec![]),
-                    Chunk::StringPool(vec!["icon".to_string()], vec![]),
-                    Chunk::TableTypeSpec(
-                        ResTableTypeSpecHeader {
-                            id: 1,
-                            res0: 0,
-                            res1: 0,
-                            entry_count: 1,
-                        },
-                        vec![256],
-                    ),
-                    mipmap_table_type(1, 160, 0),
-                    mipmap_table_type(1, 240, 1),
-                    mipmap_table_type(1, 320, 2),
-                    mipmap_table_type(1, 480, 3),
-                    mipmap_table_type(1, 640, 4),
-                ],
+                chunks,
             ),
         ],
     );
-    Ok(Mipmap { name, chunk })
+    Ok(Mipmap {
+        name,
+        densities,
+        chunk,
+    })
 }
 
 fn mipmap_table_type(type_id: u8, density: u16, string_id: u32) -> Chunk {
@@ -103,6 +133,7 @@ fn mipmap_table_type(type_id: u8, density: u16, string_id: u32) -> Chunk {
 
 pub struct Mipmap<'a> {
     name: &'a str,
+    densities: Vec<Density>,
     chunk: Chunk,
 }
 
@@ -111,8 +142,8 @@ impl<'a> Mipmap<'a> {
         &self.chunk
     }
 
-    pub fn variants(&self) -> impl Iterator<Item = (String, u32)> + 'a {
-        variants(self.name)
+    pub fn variants(&self) -> impl Iterator<Item = (String, u32)> + '_ {
+        variants(self.name, &self.densities)
     }
 }
 
@@ -126,7 +157,7 @@ mod tests {
     #[test]
     fn test_compile_mipmap() -> Result<()> {
         crate::tests::init_logger();
-        let mipmap = compile_mipmap("com.example.helloworld", "icon")?;
+        let mipmap = compile_mipmap("com.example.helloworld", "icon", &[])?;
         let mut buf = vec![];
         let mut cursor = Cursor::new(&mut buf);
         mipmap.chunk().write(&mut cursor)?;
@@ -138,6 +169,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_compile_mipmap_densities() -> Result<()> {
+        let mipmap = compile_mipmap("com.example.helloworld", "icon", &[Density::Xxhdpi])?;
+        let mut buf = vec![];
+        mipmap.chunk().write(&mut Cursor::new(&mut buf))?;
+        assert_eq!(Chunk::parse(&mut Cursor::new(&buf))?, *mipmap.chunk());
+        let variants = mipmap.variants().collect::<Vec<_>>();
+        assert_eq!(variants, [("res/icon/icon144.png".to_string(), 144)]);
+        Ok(())
+    }
+
     #[test]
     fn test_lookup_attr() -> Result<()> {
         let android = crate::tests::android_jar(31)?;
@@ -157,12 +199,14 @@ mod tests {
         manifest.application.label = Some("helloworld".into());
         manifest.application.theme = Some("@android:style/Theme.Light.NoTitleBar".into());
         manifest.application.debuggable = Some(true);
+        manifest.application.large_heap = Some(true);
         let activity = Activity {
             config_changes: Some("orientation|keyboardHidden".into()),
             launch_mode: Some("singleTop".into()),
             ..Default::default()
         };
         manifest.application.activities.push(activity);
+        manifest.shared_user_id = Some("com.example.shared".into());
         let _chunk = compile_manifest(&manifest, &table)?;
         Ok(())
     }
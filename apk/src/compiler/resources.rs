@@ -0,0 +1,679 @@
+use crate::compiler::attributes::{parse_color, parse_dimension, parse_integer};
+use crate::compiler::table::{Ref, Table};
+use crate::compiler::Mipmap;
+use crate::res::{
+    Chunk, ResTableConfig, ResTableEntry, ResTableHeader, ResTableMap, ResTableMapEntry,
+    ResTablePackageHeader, ResTableRef, ResTableTypeHeader, ResTableTypeSpecHeader, ResTableValue,
+    ResValue, ResValueType, ScreenType,
+};
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Directories of `res/` with a file per resource.
+const FILE_TYPES: [&str; 10] = [
+    "anim", "animator", "color", "drawable", "font", "layout", "menu", "mipmap", "raw", "xml",
+];
+
+const CONFIG_LOCALE: u32 = 0x0004;
+const CONFIG_DENSITY: u32 = 0x0100;
+const CONFIG_VERSION: u32 = 0x0400;
+
+/// Qualifiers of a resource directory, e.g. `values-fr` or `drawable-hdpi`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Qualifiers {
+    language: [u8; 2],
+    region: [u8; 2],
+    density: u16,
+    sdk: u16,
+}
+
+impl Qualifiers {
+    fn parse<'a>(qualifiers: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let mut config = Self::default();
+        for qualifier in qualifiers {
+            let density = match qualifier {
+                "ldpi" => 120,
+                "mdpi" => 160,
+                "tvdpi" => 213,
+                "hdpi" => 240,
+                "xhdpi" => 320,
+                "xxhdpi" => 480,
+                "xxxhdpi" => 640,
+                "anydpi" => 0xfffe,
+                "nodpi" => 0xffff,
+                _ => 0,
+            };
+            let bytes = qualifier.as_bytes();
+            if density != 0 {
+                config.density = density;
+            } else if let Some(sdk) = qualifier.strip_prefix('v').and_then(|v| v.parse().ok()) {
+                config.sdk = sdk;
+            } else if bytes.len() == 2 && bytes.iter().all(u8::is_ascii_lowercase) {
+                config.language = [bytes[0], bytes[1]];
+            } else if bytes.len() == 3
+                && bytes[0] == b'r'
+                && bytes[1..].iter().all(u8::is_ascii_uppercase)
+            {
+                config.region = [bytes[1], bytes[2]];
+            } else {
+                anyhow::bail!("unsupported qualifier {}", qualifier);
+            }
+        }
+        // density qualifiers were added in api 4
+        if config.density != 0 {
+            config.sdk = config.sdk.max(4);
+        }
+        Ok(config)
+    }
+
+    fn mask(self) -> u32 {
+        let mut mask = 0;
+        if self.language != [0; 2] || self.region != [0; 2] {
+            mask |= CONFIG_LOCALE;
+        }
+        if self.density != 0 {
+            mask |= CONFIG_DENSITY;
+        }
+        if self.sdk != 0 {
+            mask |= CONFIG_VERSION;
+        }
+        mask
+    }
+
+    fn to_config(self) -> ResTableConfig {
+        let [l0, l1] = self.language;
+        let [r0, r1] = self.region;
+        ResTableConfig {
+            size: 28 + 36,
+            imsi: 0,
+            locale: u32::from_le_bytes([l0, l1, r0, r1]),
+            screen_type: ScreenType {
+                orientation: 0,
+                touchscreen: 0,
+                density: self.density,
+            },
+            input: 0,
+            screen_size: 0,
+            version: self.sdk as u32,
+            unknown: vec![0; 36],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Value {
+    String(String),
+    /// Path of a file resource in the apk
+    File(String),
+    Reference(String),
+    Typed(u32, ResValueType),
+    Id,
+    /// Parent and the attributes with their values
+    Style(Option<String>, Vec<(String, String)>),
+}
+
+/// Resources of an android `res/` directory, compiled into a resource
+/// table. File resources are written to the apk separately.
+pub struct Resources {
+    package: String,
+    types: BTreeMap<String, BTreeMap<String, BTreeMap<Qualifiers, Value>>>,
+    files: Vec<ResourceFile>,
+}
+
+/// File of a `res/` directory and its path in the apk.
+pub struct ResourceFile {
+    pub path: PathBuf,
+    pub name: String,
+    /// Xml files are compiled to binary xml, everything else is copied.
+    pub xml: bool,
+}
+
+impl Resources {
+    pub fn new(package: &str) -> Self {
+        Self {
+            package: package.to_string(),
+            types: Default::default(),
+            files: vec![],
+        }
+    }
+
+    pub fn files(&self) -> &[ResourceFile] {
+        &self.files
+    }
+
+    /// Adds the resources of a `res/` directory. Supports strings, colors,
+    /// bools, integers and styles in `values/` and file resources like
+    /// `drawable/` and `layout/`.
+    pub fn add_dir(&mut self, dir: &Path) -> Result<()> {
+        for entry in sorted_dir(dir)? {
+            let dir_name = file_name(&entry)?;
+            if !entry.is_dir() {
+                continue;
+            }
+            let mut parts = dir_name.split('-');
+            let kind = parts.next().unwrap_or_default();
+            let qualifiers = Qualifiers::parse(parts)
+                .with_context(|| format!("invalid resource directory {}", entry.display()))?;
+            for path in sorted_dir(&entry)? {
+                let name = file_name(&path)?;
+                if kind == "values" {
+                    self.add_values(&path, qualifiers)
+                        .with_context(|| format!("invalid resources {}", path.display()))?;
+                } else if FILE_TYPES.contains(&kind) {
+                    let (stem, ext) = name.split_once('.').unwrap_or((name, ""));
+                    let xml = ext == "xml" && kind != "raw";
+                    if xml {
+                        self.add_ids(&path)?;
+                    }
+                    let apk_name = format!("res/{}/{}", dir_name, name);
+                    self.insert(kind, stem, qualifiers, Value::File(apk_name.clone()))?;
+                    self.files.push(ResourceFile {
+                        path,
+                        name: apk_name,
+                        xml,
+                    });
+                } else {
+                    anyhow::bail!("unsupported resource directory {}", entry.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a generated launcher icon.
+    pub fn add_mipmap(&mut self, mipmap: &Mipmap) -> Result<()> {
+        for (density, (name, _)) in mipmap.densities.iter().zip(mipmap.variants()) {
+            let qualifiers = Qualifiers {
+                density: density.dpi(),
+                sdk: 4,
+                ..Default::default()
+            };
+            self.insert("mipmap", mipmap.name, qualifiers, Value::File(name))?;
+        }
+        Ok(())
+    }
+
+    fn add_values(&mut self, path: &Path, qualifiers: Qualifiers) -> Result<()> {
+        let xml = std::fs::read_to_string(path)?;
+        let doc = Document::parse(&xml)?;
+        let root = doc.root_element();
+        anyhow::ensure!(
+            root.has_tag_name("resources"),
+            "expected <resources>, found <{}>",
+            root.tag_name().name()
+        );
+        for node in root.children().filter(Node::is_element) {
+            let ty = node.tag_name().name();
+            let name = node
+                .attribute("name")
+                .with_context(|| format!("<{}> without name", ty))?;
+            let text = node.text().unwrap_or_default().trim();
+            let value = if ty == "style" {
+                let parent = node.attribute("parent").map(|parent| {
+                    if parent.starts_with('@') {
+                        parent.to_string()
+                    } else if let Some((package, name)) = parent.split_once(':') {
+                        format!("@{}:style/{}", package, name)
+                    } else {
+                        format!("@style/{}", parent)
+                    }
+                });
+                let mut items = vec![];
+                for item in node.children().filter(Node::is_element) {
+                    anyhow::ensure!(
+                        item.has_tag_name("item"),
+                        "expected <item> in style {}, found <{}>",
+                        name,
+                        item.tag_name().name()
+                    );
+                    let attr = item
+                        .attribute("name")
+                        .with_context(|| format!("<item> without name in style {}", name))?;
+                    let value = item.text().unwrap_or_default().trim();
+                    items.push((attr.to_string(), value.to_string()));
+                }
+                Value::Style(parent, items)
+            } else if text.starts_with('@') {
+                Value::Reference(text.to_string())
+            } else {
+                let typed = match ty {
+                    "string" => {
+                        anyhow::ensure!(
+                            !node.children().any(|child| child.is_element()),
+                            "styled string {} is not supported",
+                            name
+                        );
+                        self.insert(ty, name, qualifiers, Value::String(unescape(text)))?;
+                        continue;
+                    }
+                    "color" => parse_color(text),
+                    "bool" => match text {
+                        "true" => Some((0xffff_ffff, ResValueType::IntBoolean)),
+                        "false" => Some((0, ResValueType::IntBoolean)),
+                        _ => None,
+                    },
+                    "integer" => parse_integer(text),
+                    _ => anyhow::bail!("unsupported resource <{}>", ty),
+                };
+                let (data, data_type) =
+                    typed.with_context(|| format!("invalid {} {}: `{}`", ty, name, text))?;
+                Value::Typed(data, data_type)
+            };
+            self.insert(ty, name, qualifiers, value)?;
+        }
+        Ok(())
+    }
+
+    /// Declares the `@+id/name` ids used in an xml resource.
+    fn add_ids(&mut self, path: &Path) -> Result<()> {
+        let xml = std::fs::read_to_string(path)?;
+        let doc = Document::parse(&xml).with_context(|| format!("invalid {}", path.display()))?;
+        for node in doc.descendants() {
+            for attr in node.attributes() {
+                if let Some(id) = attr.value().strip_prefix("@+id/") {
+                    let ids = self.types.entry("id".into()).or_default();
+                    let id = ids.entry(id.to_string()).or_default();
+                    id.insert(Qualifiers::default(), Value::Id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, ty: &str, name: &str, qualifiers: Qualifiers, value: Value) -> Result<()> {
+        // style names may be namespaced with dots, e.g. `Theme.App`
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || (c == '.' && ty == "style");
+        anyhow::ensure!(
+            !name.is_empty() && name.chars().all(valid),
+            "invalid resource name {}/{}",
+            ty,
+            name
+        );
+        let values = self.types.entry(ty.to_string()).or_default();
+        let values = values.entry(name.to_string()).or_default();
+        anyhow::ensure!(
+            values.insert(qualifiers, value).is_none(),
+            "duplicate resource {}/{}",
+            ty,
+            name
+        );
+        Ok(())
+    }
+
+    /// Id of a resource of this package.
+    fn id(&self, ty: &str, name: &str) -> Option<ResTableRef> {
+        let type_id = self.types.keys().position(|t| t == ty)?;
+        let entry_id = self.types[ty].keys().position(|n| n == name)?;
+        Some(ResTableRef::new(127, type_id as u8 + 1, entry_id as u16))
+    }
+
+    fn resolve(&self, reference: &str, table: &Table) -> Result<ResTableRef> {
+        let r = Ref::parse(reference)?;
+        if r.package().is_none() || r.package() == Some(&self.package) {
+            self.id(r.ty(), r.name())
+                .with_context(|| format!("unknown resource {}", reference))
+        } else {
+            Ok(table.entry_by_ref(r)?.id())
+        }
+    }
+
+    /// Compiles the resource table. `table` resolves references to other
+    /// packages.
+    pub fn compile(&self, table: &Table) -> Result<Chunk> {
+        let mut strings = Pool::default();
+        let mut keys = Pool::default();
+        let mut chunks = vec![];
+        for (type_id, (ty, entries)) in self.types.iter().enumerate() {
+            let type_id = type_id as u8 + 1;
+            let mut configs = BTreeSet::new();
+            let mut spec = vec![];
+            for values in entries.values() {
+                configs.extend(values.keys().copied());
+                spec.push(values.keys().fold(0, |mask, q| mask | q.mask()));
+            }
+            chunks.push(Chunk::TableTypeSpec(
+                ResTableTypeSpecHeader {
+                    id: type_id,
+                    res0: 0,
+                    res1: 0,
+                    entry_count: entries.len() as u32,
+                },
+                spec,
+            ));
+            for qualifiers in configs {
+                let mut offsets = vec![];
+                let mut table_entries = vec![];
+                let mut offset = 0;
+                for (name, values) in entries {
+                    let value = if let Some(value) = values.get(&qualifiers) {
+                        value
+                    } else {
+                        offsets.push(0xffff_ffff);
+                        table_entries.push(None);
+                        continue;
+                    };
+                    let value = self
+                        .compile_value(value, &mut strings, table)
+                        .with_context(|| format!("in {}/{}", ty, name))?;
+                    offsets.push(offset);
+                    offset += match &value {
+                        ResTableValue::Simple(_) => 16,
+                        ResTableValue::Complex(_, map) => 16 + 12 * map.len() as u32,
+                    };
+                    table_entries.push(Some(ResTableEntry {
+                        size: if let ResTableValue::Simple(_) = value {
+                            8
+                        } else {
+                            16
+                        },
+                        flags: if let ResTableValue::Simple(_) = value {
+                            0
+                        } else {
+                            1
+                        },
+                        key: keys.id(name),
+                        value,
+                    }));
+                }
+                chunks.push(Chunk::TableType(
+                    ResTableTypeHeader {
+                        id: type_id,
+                        res0: 0,
+                        res1: 0,
+                        entry_count: entries.len() as u32,
+                        entries_start: 84 + 4 * entries.len() as u32,
+                        config: qualifiers.to_config(),
+                    },
+                    offsets,
+                    table_entries,
+                ));
+            }
+        }
+        let types = self.types.keys().cloned().collect::<Vec<_>>();
+        let mut package = vec![
+            Chunk::StringPool(types.clone(), vec![]),
+            Chunk::StringPool(keys.0.clone(), vec![]),
+        ];
+        package.extend(chunks);
+        Ok(Chunk::Table(
+            ResTableHeader { package_count: 1 },
+            vec![
+                Chunk::StringPool(strings.0, vec![]),
+                Chunk::TablePackage(
+                    ResTablePackageHeader {
+                        id: 127,
+                        name: self.package.clone(),
+                        type_strings: 0,
+                        last_public_type: types.len() as u32,
+                        key_strings: 0,
+                        last_public_key: keys.0.len() as u32,
+                        type_id_offset: 0,
+                    },
+                    package,
+                ),
+            ],
+        ))
+    }
+
+    fn compile_value(
+        &self,
+        value: &Value,
+        strings: &mut Pool,
+        table: &Table,
+    ) -> Result<ResTableValue> {
+        let (data, data_type) = match value {
+            Value::String(s) | Value::File(s) => (strings.id(s), ResValueType::String),
+            Value::Reference(r) => (self.resolve(r, table)?.into(), ResValueType::Reference),
+            Value::Typed(data, data_type) => (*data, *data_type),
+            Value::Id => (0, ResValueType::IntBoolean),
+            Value::Style(parent, items) => {
+                let parent = if let Some(parent) = parent {
+                    self.resolve(parent, table)?.into()
+                } else {
+                    0
+                };
+                let mut map = vec![];
+                for (name, value) in items {
+                    let attr = self
+                        .resolve(&format!("?{}", name), table)
+                        .with_context(|| format!("unknown attribute {}", name))?;
+                    let (data, data_type) = self
+                        .compile_item(value, strings, table)
+                        .with_context(|| format!("invalid value of {}", name))?;
+                    map.push(ResTableMap {
+                        name: attr.into(),
+                        value: ResValue {
+                            size: 8,
+                            res0: 0,
+                            data_type: data_type as u8,
+                            data,
+                        },
+                    });
+                }
+                // the runtime binary searches the attributes
+                map.sort_by_key(|item| item.name);
+                let count = map.len() as u32;
+                return Ok(ResTableValue::Complex(
+                    ResTableMapEntry { parent, count },
+                    map,
+                ));
+            }
+        };
+        Ok(ResTableValue::Simple(ResValue {
+            size: 8,
+            res0: 0,
+            data_type: data_type as u8,
+            data,
+        }))
+    }
+
+    /// Compiles the value of a style item, the type is inferred from the
+    /// value instead of the attribute format.
+    fn compile_item(
+        &self,
+        value: &str,
+        strings: &mut Pool,
+        table: &Table,
+    ) -> Result<(u32, ResValueType)> {
+        Ok(if value == "@null" {
+            (0, ResValueType::Reference)
+        } else if value.starts_with('@') {
+            (self.resolve(value, table)?.into(), ResValueType::Reference)
+        } else if value.starts_with('?') {
+            (self.resolve(value, table)?.into(), ResValueType::Attribute)
+        } else if let Some(color) = parse_color(value) {
+            color
+        } else if value == "true" || value == "false" {
+            let data = if value == "true" { 0xffff_ffff } else { 0 };
+            (data, ResValueType::IntBoolean)
+        } else if let Some(dimension) = parse_dimension(value) {
+            (dimension, ResValueType::Dimension)
+        } else if let Some(int) = parse_integer(value) {
+            int
+        } else if let Ok(float) = value.parse::<f32>() {
+            (float.to_bits(), ResValueType::Float)
+        } else {
+            (strings.id(&unescape(value)), ResValueType::String)
+        })
+    }
+}
+
+/// Deduplicated strings in insertion order.
+#[derive(Default)]
+struct Pool(Vec<String>);
+
+impl Pool {
+    fn id(&mut self, s: &str) -> u32 {
+        if let Some(id) = self.0.iter().position(|s2| s2 == s) {
+            id as u32
+        } else {
+            self.0.push(s.to_string());
+            self.0.len() as u32 - 1
+        }
+    }
+}
+
+fn sorted_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if !file_name(&path)?.starts_with('.') {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("invalid path {}", path.display()))
+}
+
+/// Processes string values like aapt: whitespace is collapsed outside of
+/// double quotes and backslash escapes are resolved.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut quoted = false;
+    let mut space = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() && !quoted {
+            space = true;
+            continue;
+        }
+        if space && !out.is_empty() {
+            out.push(' ');
+        }
+        space = false;
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                    out.extend(c);
+                }
+                Some(c) => out.push(c),
+                None => {}
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("  hello\n   world  "), "hello world");
+        assert_eq!(unescape(r#""  quoted  " text"#), "  quoted   text");
+        assert_eq!(unescape(r"it\'s a\ttab\nline é"), "it's a\ttab\nline é");
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(
+            parse_color("#abc"),
+            Some((0xffaa_bbcc, ResValueType::IntColorRgb4))
+        );
+        assert_eq!(
+            parse_color("#80112233"),
+            Some((0x8011_2233, ResValueType::IntColorArgb8))
+        );
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_dimension("16dp"), Some(16 << 8 | 1));
+        assert_eq!(parse_dimension("1.5sp"), Some(0x00c0_0022));
+        assert_eq!(parse_dimension("16"), None);
+    }
+
+    #[test]
+    fn test_compile_resources() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "xbuild-test-compile-resources-{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        for sub in ["values", "values-fr", "drawable-hdpi", "layout"] {
+            std::fs::create_dir_all(dir.join(sub))?;
+        }
+        std::fs::write(
+            dir.join("values").join("strings.xml"),
+            r#"<resources>
+                <string name="app_name">Hello</string>
+                <string name="title">@string/app_name</string>
+                <color name="accent">#ff0000</color>
+                <style name="Theme.Base" />
+                <style name="Theme.App" parent="Theme.Base" />
+            </resources>"#,
+        )?;
+        std::fs::write(
+            dir.join("values-fr").join("strings.xml"),
+            r#"<resources><string name="app_name">Bonjour</string></resources>"#,
+        )?;
+        std::fs::write(dir.join("drawable-hdpi").join("logo.png"), b"png")?;
+        std::fs::write(
+            dir.join("layout").join("main.xml"),
+            r#"<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android">
+                <TextView android:id="@+id/label" />
+            </LinearLayout>"#,
+        )?;
+
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_dir(&dir)?;
+        let names = resources.files().iter().map(|f| f.name.as_str());
+        assert_eq!(
+            names.collect::<Vec<_>>(),
+            ["res/drawable-hdpi/logo.png", "res/layout/main.xml"]
+        );
+        let chunk = resources.compile(&Table::default())?;
+        let mut buf = vec![];
+        chunk.write(&mut Cursor::new(&mut buf))?;
+        let mut buf2 = vec![];
+        Chunk::parse(&mut Cursor::new(&buf))?.write(&mut Cursor::new(&mut buf2))?;
+        assert_eq!(buf, buf2);
+
+        let mut table = Table::default();
+        table.import_chunk(&chunk);
+        let title = table.entry_by_ref(Ref::parse("@string/title")?)?;
+        assert_eq!(u32::from(title.id()), 0x7f05_0001);
+        let label = table.entry_by_ref(Ref::parse("@+id/label")?)?;
+        assert_eq!(u32::from(label.id()), 0x7f03_0000);
+        let logo = table.entry_by_ref(Ref::parse("@drawable/logo")?)?;
+        assert_eq!(u32::from(logo.id()), 0x7f02_0000);
+        let theme = table.entry_by_ref(Ref::parse("@style/Theme.App")?)?;
+        assert_eq!(u32::from(theme.id()), 0x7f06_0000);
+        let parents = match &chunk {
+            Chunk::Table(_, chunks) => match &chunks[1] {
+                Chunk::TablePackage(_, chunks) => chunks.iter().find_map(|chunk| match chunk {
+                    Chunk::TableType(header, _, entries) if header.id == 6 => {
+                        let parents = entries.iter().flatten().map(|entry| match &entry.value {
+                            ResTableValue::Complex(map, _) => map.parent,
+                            _ => panic!("style is not a complex value"),
+                        });
+                        Some(parents.collect::<Vec<_>>())
+                    }
+                    _ => None,
+                }),
+                _ => None,
+            },
+            _ => None,
+        };
+        assert_eq!(parents, Some(vec![0x7f06_0001, 0]));
+
+        std::fs::write(dir.join("drawable-hdpi").join("logo.xml"), "<shape />")?;
+        let mut resources = Resources::new("com.example.helloworld");
+        assert!(resources.add_dir(&dir).is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -26,13 +26,28 @@ impl<'a> Ref<'a> {
         }
     }
 
+    /// Parses `@[package:]type/name` references. `@+id/name` refers to the
+    /// id it declares and `?[package:][attr/]name` to a theme attribute.
     pub fn parse(s: &'a str) -> Result<Self> {
+        if let Some(s) = s.strip_prefix('?') {
+            let (package, name) = match s.split_once(':') {
+                Some((package, name)) => (Some(package), name),
+                None => (None, s),
+            };
+            let name = name.strip_prefix("attr/").unwrap_or(name);
+            return Ok(Self {
+                package,
+                ty: "attr",
+                name,
+            });
+        }
         let s = s
             .strip_prefix('@')
             .with_context(|| format!("invalid reference {}: expected `@`", s))?;
         let (descr, name) = s
             .split_once('/')
             .with_context(|| format!("invalid reference {}: expected `/`", s))?;
+        let descr = descr.strip_prefix('+').unwrap_or(descr);
         let (package, ty) = if let Some((package, ty)) = descr.split_once(':') {
             (Some(package), ty)
         } else {
@@ -40,6 +55,18 @@ impl<'a> Ref<'a> {
         };
         Ok(Self { package, ty, name })
     }
+
+    pub fn package(&self) -> Option<&'a str> {
+        self.package
+    }
+
+    pub fn ty(&self) -> &'a str {
+        self.ty
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
 }
 
 struct Package<'a> {
@@ -88,19 +115,16 @@ impl<'a> Package<'a> {
         Ok(id as u32)
     }
 
-    fn lookup_type(&self, id: u8) -> Result<Type<'a>> {
-        for chunk in self.chunks {
-            if let Chunk::TableType(header, _offsets, entries) = chunk {
-                if header.id == id {
-                    return Ok(Type {
-                        package: self.id,
-                        id,
-                        entries,
-                    });
-                }
-            }
-        }
-        anyhow::bail!("failed to locate type {}", id);
+    /// Types with the id, one per configuration.
+    fn lookup_types(&self, id: u8) -> impl Iterator<Item = Type<'a>> + '_ {
+        self.chunks.iter().filter_map(move |chunk| match chunk {
+            Chunk::TableType(header, _offsets, entries) if header.id == id => Some(Type {
+                package: self.id,
+                id,
+                entries,
+            }),
+            _ => None,
+        })
     }
 }
 
@@ -149,29 +173,20 @@ impl Entry<'_> {
         self.id
     }
 
-    pub fn attribute_type(self) -> Option<ResAttributeType> {
+    /// Types of values an attribute accepts, a mask of [`ResAttributeType`]s.
+    pub fn attribute_format(self) -> Option<u32> {
         if let ResTableValue::Complex(_, entries) = &self.entry.value {
-            let data = entries[0].value.data;
-            // TODO: android supports multiple types
-            if data == 0b110 {
-                return Some(ResAttributeType::Integer);
-            }
-            if data == 0b11 {
-                return Some(ResAttributeType::String);
-            }
-            if data == 0b111110 {
-                return Some(ResAttributeType::String);
-            }
-            if let Some(value) = ResAttributeType::from_u32(entries[0].value.data) {
-                Some(value)
-            } else {
-                panic!("attribute_type: 0x{:x}", data);
-            }
+            entries.first().map(|entry| entry.value.data)
         } else {
             None
         }
     }
 
+    /// Whether the attribute accepts values of type `ty`.
+    pub fn accepts(self, ty: ResAttributeType) -> bool {
+        self.attribute_format().unwrap_or_default() & ty as u32 != 0
+    }
+
     pub fn lookup_value(&self, id: ResTableRef) -> Option<ResValue> {
         if let ResTableValue::Complex(_, entries) = &self.entry.value {
             for entry in &entries[1..] {
@@ -233,10 +248,14 @@ impl Table {
         let id = self.lookup_package_id(r.package)?;
         let package = self.lookup_package(id)?;
         let id = package.lookup_type_id(r.ty)?;
-        let ty = package.lookup_type(id)?;
         let key = package.lookup_key_id(r.name)?;
-        let id = ty.lookup_entry_id(key)?;
-        ty.lookup_entry(id)
+        // entries missing in the default configuration are in another one
+        for ty in package.lookup_types(id) {
+            if let Ok(id) = ty.lookup_entry_id(key) {
+                return ty.lookup_entry(id);
+            }
+        }
+        anyhow::bail!("failed to locate {}/{}", r.ty, r.name);
     }
 
     /*pub fn entry(&self, r: ResTableRef) -> Result<Entry> {
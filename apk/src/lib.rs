@@ -1,9 +1,11 @@
-use crate::compiler::Table;
+use crate::compiler::{Resources, Table};
 use crate::res::Chunk;
 use anyhow::{Context, Result};
-use std::io::Cursor;
+use std::fs::File;
+use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
-use xcommon::{Scaler, ScalerOpts, Zip, ZipFileOptions};
+use xcommon::{Exclude, Scaler, ScalerOpts, Zip, ZipCompression, ZipFileOptions};
+use zip::{CompressionMethod, ZipArchive};
 
 mod compiler;
 pub mod manifest;
@@ -12,7 +14,7 @@ mod sign;
 mod utils;
 
 pub use crate::manifest::AndroidManifest;
-pub use crate::utils::{Target, VersionCode};
+pub use crate::utils::{Density, Target, VersionCode};
 pub use xcommon::{Certificate, Signer};
 pub use zip;
 
@@ -20,39 +22,98 @@ pub struct Apk {
     manifest: AndroidManifest,
     path: PathBuf,
     zip: Zip,
+    previous: Option<PathBuf>,
+    densities: Vec<Density>,
+    resources: Vec<PathBuf>,
 }
 
 impl Apk {
-    pub fn new(path: PathBuf, manifest: AndroidManifest, compress: bool) -> Result<Self> {
-        let zip = Zip::new(&path, compress)?;
+    pub fn new(
+        path: PathBuf,
+        manifest: AndroidManifest,
+        compression: ZipCompression,
+    ) -> Result<Self> {
+        let zip = Zip::new(&path, compression)?;
         Ok(Self {
             manifest,
             path,
             zip,
+            previous: None,
+            densities: vec![],
+            resources: vec![],
         })
     }
 
+    /// Repackages the apk previously built at `path`, keeping its resources
+    /// and assets. Only libraries and `classes.dex` need to be added again,
+    /// the other entries are copied over on [`Apk::finish`] without
+    /// recompressing them.
+    pub fn update(
+        path: PathBuf,
+        manifest: AndroidManifest,
+        compression: ZipCompression,
+    ) -> Result<Self> {
+        let previous = path.with_extension("apk.prev");
+        std::fs::rename(&path, &previous)?;
+        let mut apk = Self::new(path, manifest, compression)?;
+        apk.previous = Some(previous);
+        Ok(apk)
+    }
+
+    /// Files skipped when adding asset directories.
+    pub fn set_exclude(&mut self, exclude: Exclude) {
+        self.zip.set_exclude(exclude);
+    }
+
+    /// Densities of the generated icons, all densities when empty.
+    pub fn set_icon_densities(&mut self, densities: Vec<Density>) {
+        self.densities = densities;
+    }
+
+    /// Adds an android `res/` directory to the resource table, resources
+    /// of multiple directories must not overlap.
+    pub fn add_resources(&mut self, dir: PathBuf) {
+        self.resources.push(dir);
+    }
+
     pub fn add_res(&mut self, icon: Option<&Path>, android: &Path) -> Result<()> {
         let mut buf = vec![];
         let mut table = Table::default();
         table.import_apk(android)?;
+        let package = if icon.is_some() || !self.resources.is_empty() {
+            self.manifest
+                .package
+                .clone()
+                .context("missing manifest.package")?
+        } else {
+            String::new()
+        };
+        let mut resources = if self.resources.is_empty() {
+            None
+        } else {
+            let mut resources = Resources::new(&package);
+            for dir in &self.resources {
+                resources.add_dir(dir)?;
+            }
+            Some(resources)
+        };
         if let Some(path) = icon {
             let mut scaler = Scaler::open(path)?;
             scaler.optimize();
-            let package = if let Some(package) = self.manifest.package.as_ref() {
-                package
-            } else {
-                anyhow::bail!("missing manifest.package");
-            };
-            let mipmap = crate::compiler::compile_mipmap(package, "icon")?;
+            let mipmap = crate::compiler::compile_mipmap(&package, "icon", &self.densities)?;
 
-            let mut cursor = Cursor::new(&mut buf);
-            mipmap.chunk().write(&mut cursor)?;
-            self.zip.create_file(
-                Path::new("resources.arsc"),
-                ZipFileOptions::Aligned(4),
-                &buf,
-            )?;
+            if let Some(resources) = resources.as_mut() {
+                resources.add_mipmap(&mipmap)?;
+            } else {
+                let mut cursor = Cursor::new(&mut buf);
+                mipmap.chunk().write(&mut cursor)?;
+                self.zip.create_file(
+                    Path::new("resources.arsc"),
+                    ZipFileOptions::Aligned(4),
+                    &buf,
+                )?;
+                table.import_chunk(mipmap.chunk());
+            }
 
             for (name, size) in mipmap.variants() {
                 buf.clear();
@@ -62,9 +123,36 @@ impl Apk {
                     .create_file(name.as_ref(), ZipFileOptions::Aligned(4), &buf)?;
             }
 
-            table.import_chunk(mipmap.chunk());
             self.manifest.application.icon = Some("@mipmap/icon".into());
         }
+        if let Some(resources) = resources {
+            let chunk = resources.compile(&table)?;
+            buf.clear();
+            chunk.write(&mut Cursor::new(&mut buf))?;
+            self.zip.create_file(
+                Path::new("resources.arsc"),
+                ZipFileOptions::Aligned(4),
+                &buf,
+            )?;
+            table.import_chunk(&chunk);
+            for file in resources.files() {
+                if file.xml {
+                    let xml = std::fs::read_to_string(&file.path)?;
+                    let chunk = crate::compiler::compile_xml(&xml, &table)
+                        .with_context(|| format!("While compiling `{}`", file.path.display()))?;
+                    buf.clear();
+                    chunk.write(&mut Cursor::new(&mut buf))?;
+                    self.zip
+                        .create_file(file.name.as_ref(), ZipFileOptions::Compressed, &buf)?;
+                } else {
+                    self.zip.add_file(
+                        &file.path,
+                        file.name.as_ref(),
+                        ZipFileOptions::Aligned(4),
+                    )?;
+                }
+            }
+        }
         let manifest = crate::compiler::compile_manifest(&self.manifest, &table)?;
         buf.clear();
         let mut cursor = Cursor::new(&mut buf);
@@ -107,16 +195,93 @@ impl Apk {
         )
     }
 
-    pub fn finish(self, signer: Option<Signer>) -> Result<()> {
+    pub fn finish(mut self, signer: Option<Signer>) -> Result<()> {
+        if let Some(previous) = self.previous.take() {
+            self.copy_previous(&previous)?;
+            std::fs::remove_file(previous)?;
+        }
         self.zip.finish()?;
         crate::sign::sign(&self.path, signer)?;
         Ok(())
     }
 
+    /// Merges a prebuilt android library. Assets and native libraries are
+    /// copied without recompressing them where possible, the `classes.jar`
+    /// is extracted to `classes_dir` and returned for dexing.
+    pub fn add_aar(&mut self, aar: &Path, classes_dir: &Path) -> Result<Option<PathBuf>> {
+        let mut archive = ZipArchive::new(File::open(aar)?)?;
+        let mut classes = None;
+        for i in 0..archive.len() {
+            let f = archive.by_index_raw(i)?;
+            let name = f.name().to_string();
+            let is_dir = f.is_dir();
+            drop(f);
+            if is_dir {
+                continue;
+            }
+            if name.starts_with("assets/") {
+                self.copy_entry(&mut archive, i, &name)?;
+            } else if let Some(lib) = name.strip_prefix("jni/") {
+                self.copy_entry(&mut archive, i, &format!("lib/{}", lib))?;
+            } else if name.starts_with("res/") {
+                anyhow::bail!(
+                    "{} contains android resources, which are only supported by gradle builds",
+                    aar.display()
+                );
+            } else if name == "classes.jar" {
+                std::fs::create_dir_all(classes_dir)?;
+                let file_stem = aar.file_stem().context("invalid path")?;
+                let jar = classes_dir.join(file_stem).with_extension("jar");
+                std::io::copy(&mut archive.by_index(i)?, &mut File::create(&jar)?)?;
+                classes = Some(jar);
+            }
+        }
+        Ok(classes)
+    }
+
+    fn copy_previous(&mut self, previous: &Path) -> Result<()> {
+        let mut archive = ZipArchive::new(File::open(previous)?)?;
+        for i in 0..archive.len() {
+            let name = archive.by_index_raw(i)?.name().to_string();
+            if name.starts_with("lib/") || name == "classes.dex" {
+                continue;
+            }
+            self.copy_entry(&mut archive, i, &name)?;
+        }
+        Ok(())
+    }
+
+    /// Copies entry `i` of another zip to `name`. Compressed entries are raw
+    /// copied, stored entries are rewritten since raw copies lose their
+    /// alignment. Libraries are always stored page aligned.
+    fn copy_entry(&mut self, archive: &mut ZipArchive<File>, i: usize, name: &str) -> Result<()> {
+        let f = archive.by_index_raw(i)?;
+        let opts = if name.starts_with("lib/") {
+            ZipFileOptions::Aligned(4096)
+        } else if f.compression() == CompressionMethod::Stored {
+            ZipFileOptions::Aligned(1 << f.data_start().trailing_zeros().min(12))
+        } else if f.name() == name {
+            return self.zip.add_zip_file(f);
+        } else {
+            ZipFileOptions::Compressed
+        };
+        drop(f);
+        let mut f = archive.by_index(i)?;
+        let mut buf = Vec::with_capacity(f.size() as usize);
+        f.read_to_end(&mut buf)?;
+        self.zip.create_file(name.as_ref(), opts, &buf)
+    }
+
+    /// Signs the apk, replacing the signing block of an already signed apk.
     pub fn sign(path: &Path, signer: Option<Signer>) -> Result<()> {
         crate::sign::sign(path, signer)
     }
 
+    /// Writes a v4 signature to `<path>.idsig` for `adb install --incremental`.
+    pub fn sign_v4(path: &Path, signer: Option<Signer>) -> Result<()> {
+        crate::sign::sign_v4(path, signer)
+    }
+
     pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
         crate::sign::verify(path)
     }
@@ -207,6 +372,75 @@ pub struct EntryPoint {
     pub activity: String,
 }
 
+/// Metadata read from a built APK by [`Apk::info`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ApkInfo {
+    pub package: Option<String>,
+    pub version_code: Option<String>,
+    pub version_name: Option<String>,
+    pub min_sdk_version: Option<String>,
+    pub target_sdk_version: Option<String>,
+    pub permissions: Vec<String>,
+    /// Abis with native libraries in `lib/`
+    pub abis: Vec<String>,
+}
+
+impl Apk {
+    pub fn info(path: &Path) -> Result<ApkInfo> {
+        let manifest = xcommon::extract_zip_file(path, "AndroidManifest.xml")?;
+        let chunks = if let Chunk::Xml(chunks) = Chunk::parse(&mut Cursor::new(manifest))? {
+            chunks
+        } else {
+            anyhow::bail!("invalid manifest");
+        };
+        let strings = if let Some(Chunk::StringPool(strings, _)) = chunks.first() {
+            strings
+        } else {
+            anyhow::bail!("invalid manifest");
+        };
+        let string = |i: i32| strings.get(i as usize).map(|s| s.as_str());
+        let mut info = ApkInfo::default();
+        for chunk in &chunks {
+            let (el, attrs) = if let Chunk::XmlStartElement(_, el, attrs) = chunk {
+                (el, attrs)
+            } else {
+                continue;
+            };
+            for attr in attrs {
+                // strings are stored as raw values, everything else as typed values
+                let value = if attr.raw_value >= 0 {
+                    string(attr.raw_value).map(ToString::to_string)
+                } else {
+                    Some(attr.typed_value.data.to_string())
+                };
+                let field = match (string(el.name), string(attr.name)) {
+                    (Some("manifest"), Some("package")) => &mut info.package,
+                    (Some("manifest"), Some("versionCode")) => &mut info.version_code,
+                    (Some("manifest"), Some("versionName")) => &mut info.version_name,
+                    (Some("uses-sdk"), Some("minSdkVersion")) => &mut info.min_sdk_version,
+                    (Some("uses-sdk"), Some("targetSdkVersion")) => &mut info.target_sdk_version,
+                    (Some("uses-permission"), Some("name")) => {
+                        info.permissions.extend(value);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                *field = value;
+            }
+        }
+        let archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+        for name in archive.file_names() {
+            if let Some(abi) = name.strip_prefix("lib/").and_then(|n| n.split('/').next()) {
+                if !info.abis.iter().any(|a| a == abi) {
+                    info.abis.push(abi.to_string());
+                }
+            }
+        }
+        info.abis.sort();
+        Ok(info)
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
@@ -245,4 +479,168 @@ pub(crate) mod tests {
             .join("android.jar");
         Ok(android)
     }
+
+    #[test]
+    fn test_update() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("xbuild-test-update-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("app.apk");
+        let lib = dir.join("libxbuild_test_update.so");
+        std::fs::write(&lib, b"new lib")?;
+        let mut zip = Zip::new(&path, ZipCompression::Fast)?;
+        zip.create_file(
+            "assets/data.txt".as_ref(),
+            ZipFileOptions::Compressed,
+            b"data",
+        )?;
+        zip.create_file("res/icon.png".as_ref(), ZipFileOptions::Unaligned, b"x")?;
+        zip.create_file(
+            "resources.arsc".as_ref(),
+            ZipFileOptions::Aligned(4),
+            b"res",
+        )?;
+        zip.create_file(
+            "lib/arm64-v8a/libold.so".as_ref(),
+            ZipFileOptions::Aligned(4096),
+            b"old lib",
+        )?;
+        zip.finish()?;
+        Apk::sign(&path, None)?;
+
+        let mut apk = Apk::update(path.clone(), Default::default(), ZipCompression::Fast)?;
+        apk.add_lib(Target::Arm64V8a, &lib)?;
+        apk.finish(None)?;
+        assert!(!path.with_extension("apk.prev").exists());
+        assert_eq!(Apk::verify(&path)?.len(), 1);
+
+        let mut archive = ZipArchive::new(File::open(&path)?)?;
+        let mut names = archive.file_names().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "assets/data.txt",
+                "lib/arm64-v8a/libxbuild_test_update.so",
+                "res/icon.png",
+                "resources.arsc"
+            ]
+        );
+        assert_eq!(archive.by_name("resources.arsc")?.data_start() % 4, 0);
+        let mut data = String::new();
+        archive
+            .by_name("assets/data.txt")?
+            .read_to_string(&mut data)?;
+        assert_eq!(data, "data");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_keeps_resources_once() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("xbuild-test-reuse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("app.apk");
+        let lib = dir.join("libapp.so");
+        std::fs::write(&lib, b"lib")?;
+        let mut apk = Apk::new(path.clone(), Default::default(), ZipCompression::Fast)?;
+        apk.zip.create_file(
+            "AndroidManifest.xml".as_ref(),
+            ZipFileOptions::Compressed,
+            b"manifest",
+        )?;
+        apk.zip.create_file(
+            "resources.arsc".as_ref(),
+            ZipFileOptions::Aligned(4),
+            b"res",
+        )?;
+        apk.add_lib(Target::Arm64V8a, &lib)?;
+        apk.finish(None)?;
+
+        // the steps of a build whose resources and assets are unchanged
+        for _ in 0..2 {
+            std::fs::write(&lib, b"new lib")?;
+            let mut apk = Apk::update(path.clone(), Default::default(), ZipCompression::Fast)?;
+            apk.set_icon_densities(vec![Density::Hdpi]);
+            apk.add_resources(dir.join("res"));
+            apk.add_lib(Target::Arm64V8a, &lib)?;
+            apk.finish(None)?;
+        }
+        assert_eq!(Apk::verify(&path)?.len(), 1);
+
+        let mut archive = ZipArchive::new(File::open(&path)?)?;
+        let mut names = archive.file_names().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "AndroidManifest.xml",
+                "lib/arm64-v8a/libapp.so",
+                "resources.arsc"
+            ]
+        );
+        let mut data = String::new();
+        archive
+            .by_name("AndroidManifest.xml")?
+            .read_to_string(&mut data)?;
+        assert_eq!(data, "manifest");
+        data.clear();
+        archive
+            .by_name("lib/arm64-v8a/libapp.so")?
+            .read_to_string(&mut data)?;
+        assert_eq!(data, "new lib");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_aar() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("xbuild-test-add-aar-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let aar = dir.join("library.aar");
+        let mut zip = Zip::new(&aar, ZipCompression::Fast)?;
+        zip.create_file(
+            "AndroidManifest.xml".as_ref(),
+            ZipFileOptions::Compressed,
+            b"",
+        )?;
+        zip.create_file(
+            "assets/font.ttf".as_ref(),
+            ZipFileOptions::Compressed,
+            b"font",
+        )?;
+        zip.create_file(
+            "jni/arm64-v8a/libfoo.so".as_ref(),
+            ZipFileOptions::Compressed,
+            b"lib",
+        )?;
+        zip.create_file("classes.jar".as_ref(), ZipFileOptions::Compressed, b"jar")?;
+        zip.finish()?;
+
+        let path = dir.join("app.apk");
+        let mut apk = Apk::new(path.clone(), Default::default(), ZipCompression::Fast)?;
+        let jar = apk.add_aar(&aar, &dir.join("classes"))?.unwrap();
+        apk.finish(None)?;
+        assert_eq!(std::fs::read(jar)?, b"jar");
+
+        let mut archive = ZipArchive::new(File::open(&path)?)?;
+        let mut names = archive.file_names().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["assets/font.ttf", "lib/arm64-v8a/libfoo.so"]);
+        let lib = archive.by_name("lib/arm64-v8a/libfoo.so")?;
+        assert_eq!(lib.compression(), CompressionMethod::Stored);
+        assert_eq!(lib.data_start() % 4096, 0);
+        drop(lib);
+
+        let mut zip = Zip::new(&aar, ZipCompression::Fast)?;
+        zip.create_file(
+            "res/values/values.xml".as_ref(),
+            ZipFileOptions::Compressed,
+            b"",
+        )?;
+        zip.finish()?;
+        let mut apk = Apk::new(path, Default::default(), ZipCompression::Fast)?;
+        assert!(apk.add_aar(&aar, &dir.join("classes")).is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 }
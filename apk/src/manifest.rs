@@ -1,8 +1,9 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize, Serializer};
 
 /// Android [manifest element](https://developer.android.com/guide/topics/manifest/manifest-element), containing an [`Application`] element.
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename = "manifest")]
 #[serde(deny_unknown_fields)]
 pub struct AndroidManifest {
@@ -14,6 +15,10 @@ pub struct AndroidManifest {
     pub version_code: Option<u32>,
     #[serde(rename(serialize = "android:versionName"))]
     pub version_name: Option<String>,
+    /// Deprecated since API level 29 and can't be changed once an app is
+    /// published, only use it for apps which already share a user id.
+    #[serde(rename(serialize = "android:sharedUserId"))]
+    pub shared_user_id: Option<String>,
     #[serde(rename(serialize = "android:compileSdkVersion"))]
     pub compile_sdk_version: Option<u32>,
     #[serde(rename(serialize = "android:compileSdkVersionCodename"))]
@@ -42,6 +47,7 @@ impl Default for AndroidManifest {
             package: Default::default(),
             version_code: Default::default(),
             version_name: Default::default(),
+            shared_user_id: Default::default(),
             sdk: Default::default(),
             uses_feature: Default::default(),
             uses_permission: Default::default(),
@@ -54,14 +60,71 @@ impl Default for AndroidManifest {
     }
 }
 
+impl AndroidManifest {
+    /// Prepares the manifest for packaging with the [`crate::Apk`] builder, which
+    /// compiles every field into the binary manifest.
+    pub fn for_apk(&self) -> Result<Self> {
+        anyhow::ensure!(self.package.is_some(), "missing manifest.package");
+        Ok(self.clone())
+    }
+
+    /// Prepares the manifest for a gradle build. Fields which gradle derives from
+    /// `build.gradle` or the build type are removed from the manifest and returned
+    /// separately.
+    pub fn for_gradle(&self) -> Result<(Self, GradleConfig)> {
+        let mut manifest = self.clone();
+        let config = GradleConfig {
+            package: manifest
+                .package
+                .take()
+                .context("missing manifest.package")?,
+            min_sdk_version: manifest
+                .sdk
+                .min_sdk_version
+                .take()
+                .context("missing manifest.sdk.min_sdk_version")?,
+            target_sdk_version: manifest
+                .sdk
+                .target_sdk_version
+                .take()
+                .context("missing manifest.sdk.target_sdk_version")?,
+            version_code: manifest
+                .version_code
+                .take()
+                .context("missing manifest.version_code")?,
+            version_name: manifest
+                .version_name
+                .take()
+                .context("missing manifest.version_name")?,
+        };
+        manifest.compile_sdk_version = None;
+        manifest.compile_sdk_version_codename = None;
+        manifest.platform_build_version_code = None;
+        manifest.platform_build_version_name = None;
+        manifest.application.debuggable = None;
+        Ok((manifest, config))
+    }
+}
+
 impl std::fmt::Display for AndroidManifest {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", quick_xml::se::to_string(self).unwrap())
     }
 }
 
+/// Manifest fields which are configured in the `defaultConfig` of `build.gradle`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GradleConfig {
+    pub package: String,
+    pub min_sdk_version: u32,
+    pub target_sdk_version: u32,
+    pub version_code: u32,
+    pub version_name: String,
+}
+
 /// Android [application element](https://developer.android.com/guide/topics/manifest/application-element), containing an [`Activity`] element.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Application {
     #[serde(rename(serialize = "android:debuggable"))]
@@ -72,20 +135,50 @@ pub struct Application {
     pub has_code: Option<bool>,
     #[serde(rename(serialize = "android:icon"))]
     pub icon: Option<String>,
+    #[serde(rename(serialize = "android:roundIcon"))]
+    pub round_icon: Option<String>,
     #[serde(rename(serialize = "android:label"))]
     pub label: Option<String>,
     #[serde(rename(serialize = "android:appComponentFactory"))]
     pub app_component_factory: Option<String>,
+    #[serde(rename(serialize = "android:largeHeap"))]
+    pub large_heap: Option<bool>,
+    #[serde(rename(serialize = "android:hardwareAccelerated"))]
+    pub hardware_accelerated: Option<bool>,
+    #[serde(rename(serialize = "android:allowBackup"))]
+    pub allow_backup: Option<bool>,
+    #[serde(rename(serialize = "android:fullBackupContent"))]
+    pub full_backup_content: Option<String>,
+    #[serde(rename(serialize = "android:networkSecurityConfig"))]
+    pub network_security_config: Option<String>,
+    #[serde(rename(serialize = "android:usesCleartextTraffic"))]
+    pub uses_cleartext_traffic: Option<bool>,
+    #[serde(rename(serialize = "android:extractNativeLibs"))]
+    pub extract_native_libs: Option<bool>,
+    #[serde(rename(serialize = "android:requestLegacyExternalStorage"))]
+    pub request_legacy_external_storage: Option<bool>,
+    #[serde(rename(serialize = "android:resizeableActivity"))]
+    pub resizeable_activity: Option<bool>,
+    #[serde(rename(serialize = "android:supportsRtl"))]
+    pub supports_rtl: Option<bool>,
+    /// Process all components run in by default, prefixed with `:` for a
+    /// process private to the app
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
     #[serde(rename(serialize = "meta-data"))]
     #[serde(default)]
     pub meta_data: Vec<MetaData>,
     #[serde(rename(serialize = "activity"))]
     #[serde(default)]
     pub activities: Vec<Activity>,
+    #[serde(rename(serialize = "service"))]
+    #[serde(default)]
+    pub services: Vec<Service>,
 }
 
 /// Android [activity element](https://developer.android.com/guide/topics/manifest/activity-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Activity {
     #[serde(rename(serialize = "android:configChanges"))]
@@ -104,6 +197,8 @@ pub struct Activity {
     pub exported: Option<bool>,
     #[serde(rename(serialize = "android:hardwareAccelerated"))]
     pub hardware_accelerated: Option<bool>,
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
     #[serde(rename(serialize = "meta-data"))]
     #[serde(default)]
     pub meta_data: Vec<MetaData>,
@@ -115,8 +210,37 @@ pub struct Activity {
     pub color_mode: Option<String>,
 }
 
+/// Android [service element](https://developer.android.com/guide/topics/manifest/service-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Service {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    #[serde(rename(serialize = "android:enabled"))]
+    pub enabled: Option<bool>,
+    #[serde(rename(serialize = "android:exported"))]
+    pub exported: Option<bool>,
+    #[serde(rename(serialize = "android:permission"))]
+    pub permission: Option<String>,
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
+    /// Runs the service in an isolated process without permissions of its own
+    #[serde(rename(serialize = "android:isolatedProcess"))]
+    pub isolated_process: Option<bool>,
+    #[serde(rename(serialize = "android:foregroundServiceType"))]
+    pub foreground_service_type: Option<String>,
+    #[serde(rename(serialize = "meta-data"))]
+    #[serde(default)]
+    pub meta_data: Vec<MetaData>,
+    #[serde(rename(serialize = "intent-filter"))]
+    #[serde(default)]
+    pub intent_filters: Vec<IntentFilter>,
+}
+
 /// Android [intent filter element](https://developer.android.com/guide/topics/manifest/intent-filter-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct IntentFilter {
     /// Serialize strings wrapped in `<action android:name="..." />`
@@ -176,6 +300,7 @@ where
 
 /// Android [intent filter data element](https://developer.android.com/guide/topics/manifest/data-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct IntentFilterData {
     #[serde(rename(serialize = "android:scheme"))]
@@ -196,6 +321,7 @@ pub struct IntentFilterData {
 
 /// Android [meta-data element](https://developer.android.com/guide/topics/manifest/meta-data-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct MetaData {
     #[serde(rename(serialize = "android:name"))]
@@ -206,6 +332,7 @@ pub struct MetaData {
 
 /// Android [uses-feature element](https://developer.android.com/guide/topics/manifest/uses-feature-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Feature {
     #[serde(rename(serialize = "android:name"))]
@@ -247,6 +374,7 @@ where
 
 /// Android [uses-permission element](https://developer.android.com/guide/topics/manifest/uses-permission-element).
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Permission {
     #[serde(rename(serialize = "android:name"))]
@@ -257,6 +385,7 @@ pub struct Permission {
 
 /// Android [uses-sdk element](https://developer.android.com/guide/topics/manifest/uses-sdk-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Sdk {
     #[serde(rename(serialize = "android:minSdkVersion"))]
@@ -270,3 +399,82 @@ pub struct Sdk {
 fn default_namespace() -> String {
     "http://schemas.android.com/apk/res/android".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_manifest() -> AndroidManifest {
+        let mut manifest = AndroidManifest {
+            package: Some("com.example.app".into()),
+            version_code: Some(1),
+            version_name: Some("0.1.0".into()),
+            compile_sdk_version: Some(33),
+            compile_sdk_version_codename: Some(13),
+            platform_build_version_code: Some(33),
+            platform_build_version_name: Some(13),
+            ..Default::default()
+        };
+        manifest.sdk.min_sdk_version = Some(21);
+        manifest.sdk.target_sdk_version = Some(33);
+        manifest.application.debuggable = Some(true);
+        manifest
+    }
+
+    #[test]
+    fn for_apk() {
+        let manifest = test_manifest().for_apk().unwrap();
+        assert_eq!(manifest.package.as_deref(), Some("com.example.app"));
+        assert_eq!(manifest.sdk.target_sdk_version, Some(33));
+        assert_eq!(manifest.compile_sdk_version, Some(33));
+        assert_eq!(manifest.application.debuggable, Some(true));
+
+        let manifest = AndroidManifest::default();
+        assert!(manifest.for_apk().is_err());
+    }
+
+    #[test]
+    fn for_gradle() {
+        let (manifest, config) = test_manifest().for_gradle().unwrap();
+        assert_eq!(
+            config,
+            GradleConfig {
+                package: "com.example.app".into(),
+                min_sdk_version: 21,
+                target_sdk_version: 33,
+                version_code: 1,
+                version_name: "0.1.0".into(),
+            }
+        );
+        assert!(manifest.package.is_none());
+        assert!(manifest.version_code.is_none());
+        assert!(manifest.version_name.is_none());
+        assert!(manifest.sdk.min_sdk_version.is_none());
+        assert!(manifest.sdk.target_sdk_version.is_none());
+        assert!(manifest.compile_sdk_version.is_none());
+        assert!(manifest.compile_sdk_version_codename.is_none());
+        assert!(manifest.platform_build_version_code.is_none());
+        assert!(manifest.platform_build_version_name.is_none());
+        assert!(manifest.application.debuggable.is_none());
+
+        let mut manifest = test_manifest();
+        manifest.version_code = None;
+        assert!(manifest.for_gradle().is_err());
+    }
+
+    #[test]
+    fn shared_user_id_and_process() {
+        let mut manifest = test_manifest();
+        manifest.shared_user_id = Some("com.example.shared".into());
+        manifest.application.process = Some(":app".into());
+        manifest.application.services.push(Service {
+            name: ".Worker".into(),
+            isolated_process: Some(true),
+            ..Default::default()
+        });
+        let xml = manifest.to_string();
+        assert!(xml.contains(r#"android:sharedUserId="com.example.shared""#));
+        assert!(xml.contains(r#"android:process=":app""#));
+        assert!(xml.contains(r#"<service android:name=".Worker" android:isolatedProcess="true"/>"#));
+    }
+}
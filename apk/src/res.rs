@@ -897,13 +897,13 @@ impl Chunk {
                     if string_pool_header.is_utf8() {
                         let charsh = r.read_u8()? as u16;
                         let _chars = if charsh > 0x7f {
-                            charsh & 0x7f | r.read_u8()? as u16
+                            (charsh & 0x7f) << 8 | r.read_u8()? as u16
                         } else {
                             charsh
                         };
                         let bytesh = r.read_u8()? as u16;
                         let bytes = if bytesh > 0x7f {
-                            bytesh & 0x7f | r.read_u8()? as u16
+                            (bytesh & 0x7f) << 8 | r.read_u8()? as u16
                         } else {
                             bytesh
                         };
@@ -1109,10 +1109,9 @@ impl Chunk {
                 let strings_start = w.stream_position()?;
                 for string in strings {
                     indices.push(w.stream_position()? - strings_start);
-                    assert!(string.len() < 0x7f);
-                    let chars = string.chars().count();
-                    w.write_u8(chars as u8)?;
-                    w.write_u8(string.len() as u8)?;
+                    anyhow::ensure!(string.len() <= 0x7fff, "string too long: {}", string);
+                    write_utf8_len(w, string.chars().count())?;
+                    write_utf8_len(w, string.len())?;
                     w.write_all(string.as_bytes())?;
                     w.write_u8(0)?;
                 }
@@ -1252,6 +1251,15 @@ impl Chunk {
     }
 }
 
+/// Lengths of utf8 strings take a second byte above `0x7f`.
+fn write_utf8_len(w: &mut impl Write, len: usize) -> Result<()> {
+    if len > 0x7f {
+        w.write_u8((len >> 8) as u8 | 0x80)?;
+    }
+    w.write_u8(len as u8)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1281,4 +1289,13 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_long_strings() -> Result<()> {
+        let chunk = Chunk::StringPool(vec!["short".into(), "long ".repeat(60)], vec![]);
+        let mut buf = vec![];
+        chunk.write(&mut Cursor::new(&mut buf))?;
+        assert_eq!(Chunk::parse(&mut Cursor::new(&buf))?, chunk);
+        Ok(())
+    }
 }
@@ -1,13 +1,14 @@
 use anyhow::Result;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use p256::ecdsa::signature::Verifier;
 use rasn_pkix::Certificate;
-use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
+use rsa::pkcs8::DecodePublicKey;
 use rsa::{PaddingScheme, PublicKey, RsaPublicKey};
 use sha2::{Digest as _, Sha256};
 use std::fs::File;
-use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-use xcommon::{Signer, ZipInfo};
+use xcommon::{KeyType, Signer, ZipInfo};
 
 const DEBUG_PEM: &str = include_str!("../assets/debug.pem");
 
@@ -16,7 +17,12 @@ const APK_SIGNING_BLOCK_V2_ID: u32 = 0x7109871a;
 const APK_SIGNING_BLOCK_V3_ID: u32 = 0xf05368c0;
 const APK_SIGNING_BLOCK_V4_ID: u32 = 0x42726577;
 const RSA_PKCS1V15_SHA2_256: u32 = 0x0103;
+const ECDSA_SHA2_256: u32 = 0x0201;
 const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+const V4_VERSION: u32 = 2;
+const V4_HASH_ALGORITHM_SHA256: u32 = 1;
+const V4_LOG2_BLOCK_SIZE: u8 = 12;
+const VERITY_BLOCK_SIZE: usize = 1 << V4_LOG2_BLOCK_SIZE;
 
 pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
     let f = File::open(path)?;
@@ -54,15 +60,23 @@ pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
             "found no signatures in v2 block"
         );
         for sig in &signer.signatures {
-            anyhow::ensure!(
-                sig.algorithm == RSA_PKCS1V15_SHA2_256,
-                "found unsupported signature algorithm 0x{:x}",
-                sig.algorithm
-            );
-            let pubkey = RsaPublicKey::from_public_key_der(&signer.public_key)?;
-            let digest = Sha256::digest(&signer.signed_data);
-            let padding = PaddingScheme::new_pkcs1v15_sign::<sha2::Sha256>();
-            pubkey.verify(padding, &digest, &sig.signature)?;
+            match sig.algorithm {
+                RSA_PKCS1V15_SHA2_256 => {
+                    let pubkey = RsaPublicKey::from_public_key_der(&signer.public_key)?;
+                    let digest = Sha256::digest(&signer.signed_data);
+                    let padding = PaddingScheme::new_pkcs1v15_sign::<sha2::Sha256>();
+                    pubkey.verify(padding, &digest, &sig.signature)?;
+                }
+                ECDSA_SHA2_256 => {
+                    let pubkey =
+                        p256::ecdsa::VerifyingKey::from_public_key_der(&signer.public_key)?;
+                    let signature = p256::ecdsa::Signature::from_der(&sig.signature)?;
+                    pubkey.verify(&signer.signed_data, &signature)?;
+                }
+                algorithm => {
+                    anyhow::bail!("found unsupported signature algorithm 0x{:x}", algorithm)
+                }
+            }
         }
         let mut r = Cursor::new(&signer.signed_data[..]);
         let signed_data = SignedData::read(&mut r)?;
@@ -72,7 +86,7 @@ pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
         );
         for digest in &signed_data.digests {
             anyhow::ensure!(
-                digest.algorithm == RSA_PKCS1V15_SHA2_256,
+                [RSA_PKCS1V15_SHA2_256, ECDSA_SHA2_256].contains(&digest.algorithm),
                 "found unsupported digest algorithm 0x{:x}",
                 digest.algorithm
             );
@@ -114,6 +128,95 @@ pub fn sign(path: &Path, signer: Option<Signer>) -> Result<()> {
     Ok(())
 }
 
+/// Writes the v4 signature used by `adb install --incremental` to `<path>.idsig`.
+/// The apk needs to be signed by the same signer first.
+pub fn sign_v4(path: &Path, signer: Option<Signer>) -> Result<()> {
+    let signer = signer.map(Ok).unwrap_or_else(|| Signer::new(DEBUG_PEM))?;
+    let apk = std::fs::read(path)?;
+    let mut r = Cursor::new(&apk);
+    let block = parse_apk_signing_block(&mut r)?;
+    // the v4 signature references the v2 content digest
+    let apk_digest = compute_digest(&mut r, block.sb_start, block.cd_start, block.cde_start)?;
+    let tree = verity_tree(&apk);
+    let root_hash = Sha256::digest(&tree[..VERITY_BLOCK_SIZE]);
+    let certificate = rasn::der::encode(signer.cert()).map_err(|err| anyhow::anyhow!("{}", err))?;
+    let public_key = signer.public_key_der()?;
+    let salt = [];
+    let additional_data = [];
+
+    let mut signed_data = vec![];
+    signed_data.write_u32::<LittleEndian>(0)?;
+    signed_data.write_u64::<LittleEndian>(apk.len() as u64)?;
+    signed_data.write_u32::<LittleEndian>(V4_HASH_ALGORITHM_SHA256)?;
+    signed_data.write_u8(V4_LOG2_BLOCK_SIZE)?;
+    write_v4_bytes(&mut signed_data, &salt)?;
+    write_v4_bytes(&mut signed_data, &root_hash)?;
+    write_v4_bytes(&mut signed_data, &apk_digest)?;
+    write_v4_bytes(&mut signed_data, &certificate)?;
+    write_v4_bytes(&mut signed_data, &additional_data)?;
+    let size = signed_data.len() as u32;
+    signed_data[..4].copy_from_slice(&size.to_le_bytes());
+    let signature = signer.sign(&signed_data)?;
+
+    let mut hashing_info = vec![];
+    hashing_info.write_u32::<LittleEndian>(V4_HASH_ALGORITHM_SHA256)?;
+    hashing_info.write_u8(V4_LOG2_BLOCK_SIZE)?;
+    write_v4_bytes(&mut hashing_info, &salt)?;
+    write_v4_bytes(&mut hashing_info, &root_hash)?;
+
+    let mut signing_info = vec![];
+    write_v4_bytes(&mut signing_info, &apk_digest)?;
+    write_v4_bytes(&mut signing_info, &certificate)?;
+    write_v4_bytes(&mut signing_info, &additional_data)?;
+    write_v4_bytes(&mut signing_info, &public_key)?;
+    signing_info.write_u32::<LittleEndian>(signature_algorithm(&signer))?;
+    write_v4_bytes(&mut signing_info, &signature)?;
+
+    let mut idsig = path.as_os_str().to_owned();
+    idsig.push(".idsig");
+    let mut f = BufWriter::new(File::create(idsig)?);
+    f.write_u32::<LittleEndian>(V4_VERSION)?;
+    write_v4_bytes(&mut f, &hashing_info)?;
+    write_v4_bytes(&mut f, &signing_info)?;
+    write_v4_bytes(&mut f, &tree)?;
+    Ok(())
+}
+
+fn write_v4_bytes(w: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    w.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    w.write_all(bytes)?;
+    Ok(())
+}
+
+/// Computes the fs-verity merkle tree of `data` with the root level first.
+fn verity_tree(data: &[u8]) -> Vec<u8> {
+    let mut levels = vec![];
+    let mut level = hash_level(data);
+    while level.len() > VERITY_BLOCK_SIZE {
+        let next = hash_level(&level);
+        levels.push(level);
+        level = next;
+    }
+    levels.push(level);
+    levels.into_iter().rev().flatten().collect()
+}
+
+/// Hashes each zero padded 4K block and pads the result to a multiple of 4K.
+fn hash_level(data: &[u8]) -> Vec<u8> {
+    let mut level = vec![];
+    for block in data.chunks(VERITY_BLOCK_SIZE) {
+        let mut hasher = Sha256::new();
+        hasher.update(block);
+        hasher.update(&[0; VERITY_BLOCK_SIZE][block.len()..]);
+        level.extend_from_slice(&hasher.finalize());
+    }
+    level.resize(
+        level.len().div_ceil(VERITY_BLOCK_SIZE) * VERITY_BLOCK_SIZE,
+        0,
+    );
+    level
+}
+
 fn compute_digest<R: Read + Seek>(
     r: &mut R,
     sb_start: u64,
@@ -184,10 +287,19 @@ struct Digest {
     pub digest: Vec<u8>,
 }
 
+/// The apk signature scheme id of the signer's algorithm, which is also used
+/// to identify the content digest.
+fn signature_algorithm(signer: &Signer) -> u32 {
+    match signer.key_type() {
+        KeyType::Rsa => RSA_PKCS1V15_SHA2_256,
+        KeyType::Ec => ECDSA_SHA2_256,
+    }
+}
+
 impl Digest {
-    fn new(hash: [u8; 32]) -> Self {
+    fn new(hash: [u8; 32], algorithm: u32) -> Self {
         Self {
-            algorithm: RSA_PKCS1V15_SHA2_256,
+            algorithm,
             digest: hash.to_vec(),
         }
     }
@@ -224,7 +336,7 @@ struct SignedData {
 impl SignedData {
     fn new(hash: [u8; 32], signer: &Signer) -> Result<Self> {
         Ok(Self {
-            digests: vec![Digest::new(hash)],
+            digests: vec![Digest::new(hash, signature_algorithm(signer))],
             certificates: vec![
                 rasn::der::encode(signer.cert()).map_err(|err| anyhow::anyhow!("{}", err))?
             ],
@@ -307,15 +419,15 @@ impl ApkSignatureBlockV2 {
     fn new(hash: [u8; 32], signer: &Signer) -> Result<Self> {
         let mut signed_data = vec![];
         SignedData::new(hash, signer)?.write(&mut signed_data)?;
-        let signature = signer.sign(&signed_data);
+        let signature = signer.sign(&signed_data)?;
         Ok(Self {
             signers: vec![ApkSigner {
                 signed_data,
                 signatures: vec![ApkSignature {
-                    algorithm: RSA_PKCS1V15_SHA2_256,
+                    algorithm: signature_algorithm(signer),
                     signature,
                 }],
-                public_key: signer.pubkey().to_public_key_der()?.as_ref().to_vec(),
+                public_key: signer.public_key_der()?,
             }],
         })
     }
@@ -445,3 +557,49 @@ fn parse_apk_signing_block<R: Read + Seek>(r: &mut R) -> Result<ApkSignatureBloc
     }
     Ok(block)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verity_tree() {
+        // 129 leaf hashes don't fit a single block, requiring a second level.
+        let data = vec![1; VERITY_BLOCK_SIZE * 128 + 1];
+        let tree = verity_tree(&data);
+        assert_eq!(tree.len(), 3 * VERITY_BLOCK_SIZE);
+        let leaves = &tree[VERITY_BLOCK_SIZE..];
+        assert_eq!(leaves[..32], Sha256::digest(&data[..VERITY_BLOCK_SIZE])[..]);
+        let mut last = [0; VERITY_BLOCK_SIZE];
+        last[0] = 1;
+        assert_eq!(leaves[128 * 32..129 * 32], Sha256::digest(last)[..]);
+        assert!(leaves[129 * 32..].iter().all(|b| *b == 0));
+        assert_eq!(tree[..32], Sha256::digest(&leaves[..VERITY_BLOCK_SIZE])[..]);
+        assert_eq!(
+            tree[32..64],
+            Sha256::digest(&leaves[VERITY_BLOCK_SIZE..])[..]
+        );
+        assert!(tree[64..VERITY_BLOCK_SIZE].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_resign() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("xbuild-test-resign-{}.apk", std::process::id()));
+        let mut zip = xcommon::Zip::new(&path, Default::default())?;
+        zip.create_file(
+            "classes.dex".as_ref(),
+            xcommon::ZipFileOptions::Compressed,
+            b"dex",
+        )?;
+        zip.finish()?;
+        sign(&path, None)?;
+        let signed = std::fs::read(&path)?;
+        sign(&path, None)?;
+        // the signing block is replaced instead of a second one being added
+        assert_eq!(std::fs::read(&path)?, signed);
+        assert_eq!(verify(&path)?.len(), 1);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u8)]
@@ -7,6 +8,7 @@ pub enum Target {
     Arm64V8a = 2,
     X86 = 3,
     X86_64 = 4,
+    Armeabi = 5,
 }
 
 impl Target {
@@ -17,10 +19,48 @@ impl Target {
             Self::ArmV7a => "armeabi-v7a",
             Self::X86 => "x86",
             Self::X86_64 => "x86_64",
+            Self::Armeabi => "armeabi",
         }
     }
 }
 
+/// Screen density buckets of generated mipmaps.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    Mdpi,
+    Hdpi,
+    Xhdpi,
+    Xxhdpi,
+    Xxxhdpi,
+}
+
+impl Density {
+    pub const ALL: [Self; 5] = [
+        Self::Mdpi,
+        Self::Hdpi,
+        Self::Xhdpi,
+        Self::Xxhdpi,
+        Self::Xxxhdpi,
+    ];
+
+    pub fn dpi(self) -> u16 {
+        match self {
+            Self::Mdpi => 160,
+            Self::Hdpi => 240,
+            Self::Xhdpi => 320,
+            Self::Xxhdpi => 480,
+            Self::Xxxhdpi => 640,
+        }
+    }
+
+    /// Size of a launcher icon in pixels.
+    pub fn icon_size(self) -> u32 {
+        self.dpi() as u32 * 48 / 160
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct VersionCode {
     major: u8,
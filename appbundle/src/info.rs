@@ -1,143 +1,312 @@
 use serde::{Deserialize, Serialize};
 
 // NOTE: keep fields alphabetically ordered.
+// Fields serialize to their Info.plist key, which is also accepted when
+// deserializing so existing plists can be read.
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct InfoPlist {
     /// The default language and region for the bundle, as a
     /// language ID.
-    #[serde(rename(serialize = "CFBundleDevelopmentRegion"))]
+    #[serde(
+        rename(serialize = "CFBundleDevelopmentRegion"),
+        alias = "CFBundleDevelopmentRegion"
+    )]
     pub cf_bundle_development_region: Option<String>,
     /// The user-visible name for the bundle, used by Siri and visible
     /// on the iOS Home screen.
-    #[serde(rename(serialize = "CFBundleDisplayName"))]
+    #[serde(
+        rename(serialize = "CFBundleDisplayName"),
+        alias = "CFBundleDisplayName"
+    )]
     pub cf_bundle_display_name: Option<String>,
+    /// The document types the app can open.
+    #[serde(
+        rename(serialize = "CFBundleDocumentTypes"),
+        alias = "CFBundleDocumentTypes"
+    )]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cf_bundle_document_types: Vec<CfBundleDocumentType>,
     /// The entry point of the bundle.
-    #[serde(rename(serialize = "CFBundleExecutable"))]
+    #[serde(rename(serialize = "CFBundleExecutable"), alias = "CFBundleExecutable")]
     pub cf_bundle_executable: Option<String>,
     /// The icons of the bundle.
-    #[serde(rename(serialize = "CFBundleIcons"))]
+    #[serde(rename(serialize = "CFBundleIcons"), alias = "CFBundleIcons")]
     pub cf_bundle_icons: Option<CfBundleIcons>,
     /// The icon file of the bundle.
-    #[serde(rename(serialize = "CFBundleIconFile"))]
+    #[serde(rename(serialize = "CFBundleIconFile"), alias = "CFBundleIconFile")]
     pub cf_bundle_icon_file: Option<String>,
     /// The icon files of the bundle.
-    #[serde(rename(serialize = "CFBundleIconFiles"))]
+    #[serde(rename(serialize = "CFBundleIconFiles"), alias = "CFBundleIconFiles")]
     #[serde(default)]
     pub cf_bundle_icon_files: Vec<String>,
     /// The icon name of the bundle.
-    #[serde(rename(serialize = "CFBundleIconName"))]
+    #[serde(rename(serialize = "CFBundleIconName"), alias = "CFBundleIconName")]
     pub cf_bundle_icon_name: Option<String>,
     /// A unique identifier for a bundle.
-    #[serde(rename(serialize = "CFBundleIdentifier"))]
+    #[serde(rename(serialize = "CFBundleIdentifier"), alias = "CFBundleIdentifier")]
     pub cf_bundle_identifier: Option<String>,
     /// The current version of the Information Property List structure.
-    #[serde(rename(serialize = "CFBundleInfoDictionaryVersion"))]
+    #[serde(
+        rename(serialize = "CFBundleInfoDictionaryVersion"),
+        alias = "CFBundleInfoDictionaryVersion"
+    )]
     pub cf_bundle_info_dictionary_version: Option<String>,
     /// A user-visible short name for the bundle.
-    #[serde(rename(serialize = "CFBundleName"))]
+    #[serde(rename(serialize = "CFBundleName"), alias = "CFBundleName")]
     pub cf_bundle_name: Option<String>,
     /// The type of bundle.
-    #[serde(rename(serialize = "CFBundlePackageType"))]
+    #[serde(
+        rename(serialize = "CFBundlePackageType"),
+        alias = "CFBundlePackageType"
+    )]
     pub cf_bundle_package_type: Option<String>,
     /// The release or version number of the bundle.
-    #[serde(rename(serialize = "CFBundleShortVersionString"))]
+    #[serde(
+        rename(serialize = "CFBundleShortVersionString"),
+        alias = "CFBundleShortVersionString"
+    )]
     pub cf_bundle_short_version_string: Option<String>,
     /// A replacement for the app name in text-to-speech operations.
-    #[serde(rename(serialize = "CFBundleSpokenName"))]
+    #[serde(rename(serialize = "CFBundleSpokenName"), alias = "CFBundleSpokenName")]
     pub cf_bundle_spoken_name: Option<String>,
-    #[serde(rename(serialize = "CFBundleSupportedPlatforms"))]
+    #[serde(
+        rename(serialize = "CFBundleSupportedPlatforms"),
+        alias = "CFBundleSupportedPlatforms"
+    )]
     #[serde(default)]
     pub cf_bundle_supported_platforms: Option<Vec<String>>,
     /// The version of the build that identifies an iteration of the
     /// bundle.
-    #[serde(rename(serialize = "CFBundleVersion"))]
+    #[serde(rename(serialize = "CFBundleVersion"), alias = "CFBundleVersion")]
     pub cf_bundle_version: Option<String>,
 
-    #[serde(rename(serialize = "DTCompiler"))]
+    #[serde(rename(serialize = "DTCompiler"), alias = "DTCompiler")]
     pub dt_compiler: Option<String>,
-    #[serde(rename(serialize = "DTPlatformBuild"))]
+    #[serde(rename(serialize = "DTPlatformBuild"), alias = "DTPlatformBuild")]
     pub dt_platform_build: Option<String>,
-    #[serde(rename(serialize = "DTPlatformName"))]
+    #[serde(rename(serialize = "DTPlatformName"), alias = "DTPlatformName")]
     pub dt_platform_name: Option<String>,
-    #[serde(rename(serialize = "DTPlatformVersion"))]
+    #[serde(rename(serialize = "DTPlatformVersion"), alias = "DTPlatformVersion")]
     pub dt_platform_version: Option<String>,
-    #[serde(rename(serialize = "DTSDKBuild"))]
+    #[serde(rename(serialize = "DTSDKBuild"), alias = "DTSDKBuild")]
     pub dt_sdk_build: Option<String>,
-    #[serde(rename(serialize = "DTSDKName"))]
+    #[serde(rename(serialize = "DTSDKName"), alias = "DTSDKName")]
     pub dt_sdk_name: Option<String>,
-    #[serde(rename(serialize = "DTXcode"))]
+    #[serde(rename(serialize = "DTXcode"), alias = "DTXcode")]
     pub dt_xcode: Option<String>,
-    #[serde(rename(serialize = "DTXcodeBuild"))]
+    #[serde(rename(serialize = "DTXcodeBuild"), alias = "DTXcodeBuild")]
     pub dt_xcode_build: Option<String>,
 
     /// The category that best describes your app for the App Store.
-    #[serde(rename(serialize = "LSApplicationCategoryType"))]
+    #[serde(
+        rename(serialize = "LSApplicationCategoryType"),
+        alias = "LSApplicationCategoryType"
+    )]
     pub ls_application_category_type: Option<String>,
     /// The minimum version of the operating system required for
     /// the app to run in macOS.
-    #[serde(rename(serialize = "LSMinimumSystemVersion"))]
+    #[serde(
+        rename(serialize = "LSMinimumSystemVersion"),
+        alias = "LSMinimumSystemVersion"
+    )]
     pub ls_minimum_system_version: Option<String>,
     /// A boolean value indicating whether the app must run in iOS.
-    #[serde(rename(serialize = "LSRequiresIPhoneOS"))]
+    #[serde(rename(serialize = "LSRequiresIPhoneOS"), alias = "LSRequiresIPhoneOS")]
     pub ls_requires_ios: Option<bool>,
 
     /// The minimum version of the operating system required for
     /// the app to run in iOS, iPadOS, tvOS, and watchOS.
-    #[serde(rename(serialize = "MinimumOSVersion"))]
+    #[serde(rename(serialize = "MinimumOSVersion"), alias = "MinimumOSVersion")]
     pub minimum_os_version: Option<String>,
 
     /// A message that tells the user why the app is requesting
     /// access to the device's camera.
-    #[serde(rename(serialize = "NSCameraUsageDescription"))]
+    #[serde(
+        rename(serialize = "NSCameraUsageDescription"),
+        alias = "NSCameraUsageDescription"
+    )]
     pub ns_camera_usage_description: Option<String>,
     /// A human-readable copyright notice for the bundle.
-    #[serde(rename(serialize = "NSHumanReadableCopyright"))]
+    #[serde(
+        rename(serialize = "NSHumanReadableCopyright"),
+        alias = "NSHumanReadableCopyright"
+    )]
     pub ns_human_readable_copyright: Option<String>,
 
-    #[serde(rename(serialize = "UIDeviceFamily"))]
+    #[serde(rename(serialize = "UIDeviceFamily"), alias = "UIDeviceFamily")]
     pub ui_device_family: Option<Vec<u64>>,
-    #[serde(rename(serialize = "UILaunchScreen"))]
+    #[serde(rename(serialize = "UILaunchScreen"), alias = "UILaunchScreen")]
     pub ui_launch_screen: Option<UiLaunchScreen>,
-    #[serde(rename(serialize = "UILaunchStoryboardName"))]
+    #[serde(
+        rename(serialize = "UILaunchStoryboardName"),
+        alias = "UILaunchStoryboardName"
+    )]
     pub ui_launch_storyboard_name: Option<String>,
-    #[serde(rename(serialize = "UIRequiredDeviceCapabilities"))]
+    #[serde(
+        rename(serialize = "UIRequiredDeviceCapabilities"),
+        alias = "UIRequiredDeviceCapabilities"
+    )]
     pub ui_required_device_capabilities: Option<Vec<String>>,
-    #[serde(rename(serialize = "UISupportedInterfaceOrientations~ipad"))]
+    #[serde(
+        rename(serialize = "UISupportedInterfaceOrientations~ipad"),
+        alias = "UISupportedInterfaceOrientations~ipad"
+    )]
     #[serde(default)]
     pub ui_supported_interface_orientations_ipad: Vec<String>,
-    #[serde(rename(serialize = "UISupportedInterfaceOrientations~iphone"))]
+    #[serde(
+        rename(serialize = "UISupportedInterfaceOrientations~iphone"),
+        alias = "UISupportedInterfaceOrientations~iphone"
+    )]
     #[serde(default)]
     pub ui_supported_interface_orientations_iphone: Vec<String>,
+
+    /// Uniform type identifiers owned by the app.
+    #[serde(
+        rename(serialize = "UTExportedTypeDeclarations"),
+        alias = "UTExportedTypeDeclarations"
+    )]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ut_exported_type_declarations: Vec<UtTypeDeclaration>,
+    /// Uniform type identifiers of other apps the app uses.
+    #[serde(
+        rename(serialize = "UTImportedTypeDeclarations"),
+        alias = "UTImportedTypeDeclarations"
+    )]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ut_imported_type_declarations: Vec<UtTypeDeclaration>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct UiLaunchScreen {
-    #[serde(rename(serialize = "UIColorName"))]
+    #[serde(rename(serialize = "UIColorName"), alias = "UIColorName")]
     pub ui_color_name: Option<String>,
-    #[serde(rename(serialize = "UIImageName"))]
+    #[serde(rename(serialize = "UIImageName"), alias = "UIImageName")]
     pub ui_image_name: Option<String>,
-    #[serde(rename(serialize = "UIImageRespectsSafeAreaInsets"))]
+    #[serde(
+        rename(serialize = "UIImageRespectsSafeAreaInsets"),
+        alias = "UIImageRespectsSafeAreaInsets"
+    )]
     pub ui_image_respects_safe_area_insets: Option<bool>,
-    #[serde(rename(serialize = "UINavigationBar"))]
+    #[serde(rename(serialize = "UINavigationBar"), alias = "UINavigationBar")]
     pub ui_navigation_bar: Option<bool>,
-    #[serde(rename(serialize = "UITabBar"))]
+    #[serde(rename(serialize = "UITabBar"), alias = "UITabBar")]
     pub ui_tab_bar: Option<bool>,
-    #[serde(rename(serialize = "UIToolbar"))]
+    #[serde(rename(serialize = "UIToolbar"), alias = "UIToolbar")]
     pub ui_toolbar: Option<bool>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct CfBundleIcons {
-    #[serde(rename(serialize = "CFBundlePrimaryIcon"))]
+    #[serde(
+        rename(serialize = "CFBundlePrimaryIcon"),
+        alias = "CFBundlePrimaryIcon"
+    )]
     pub cf_bundle_primary_icon: Option<CfBundlePrimaryIcon>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct CfBundlePrimaryIcon {
-    #[serde(rename(serialize = "CFBundleIconName"))]
+    #[serde(rename(serialize = "CFBundleIconName"), alias = "CFBundleIconName")]
     pub cf_bundle_icon_name: Option<String>,
 }
+
+/// A document type the app can open, see
+/// <https://developer.apple.com/documentation/bundleresources/information_property_list/cfbundledocumenttypes>.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct CfBundleDocumentType {
+    /// The name of the document type.
+    #[serde(rename(serialize = "CFBundleTypeName"), alias = "CFBundleTypeName")]
+    pub cf_bundle_type_name: String,
+    /// The app's role with respect to the type.
+    #[serde(rename(serialize = "CFBundleTypeRole"), alias = "CFBundleTypeRole")]
+    pub cf_bundle_type_role: Option<CfBundleTypeRole>,
+    /// The icon file of the document type, relative to the bundle resources.
+    #[serde(
+        rename(serialize = "CFBundleTypeIconFile"),
+        alias = "CFBundleTypeIconFile"
+    )]
+    pub cf_bundle_type_icon_file: Option<String>,
+    /// File name extensions without the leading dot.
+    #[serde(
+        rename(serialize = "CFBundleTypeExtensions"),
+        alias = "CFBundleTypeExtensions"
+    )]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cf_bundle_type_extensions: Vec<String>,
+    /// Uniform type identifiers of the document type.
+    #[serde(rename(serialize = "LSItemContentTypes"), alias = "LSItemContentTypes")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ls_item_content_types: Vec<String>,
+    /// How the app ranks among the handlers of the type.
+    #[serde(rename(serialize = "LSHandlerRank"), alias = "LSHandlerRank")]
+    pub ls_handler_rank: Option<LsHandlerRank>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum CfBundleTypeRole {
+    Editor,
+    Viewer,
+    Shell,
+    None,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum LsHandlerRank {
+    Owner,
+    Default,
+    Alternate,
+    None,
+}
+
+/// A uniform type identifier declaration, see
+/// <https://developer.apple.com/documentation/uniformtypeidentifiers/defining_file_and_data_types_for_your_app>.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct UtTypeDeclaration {
+    /// The identifier, e.g. `com.example.myproj`.
+    #[serde(rename(serialize = "UTTypeIdentifier"), alias = "UTTypeIdentifier")]
+    pub ut_type_identifier: String,
+    #[serde(rename(serialize = "UTTypeDescription"), alias = "UTTypeDescription")]
+    pub ut_type_description: Option<String>,
+    /// Identifiers of the parent types, e.g. `public.data`.
+    #[serde(rename(serialize = "UTTypeConformsTo"), alias = "UTTypeConformsTo")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ut_type_conforms_to: Vec<String>,
+    #[serde(rename(serialize = "UTTypeIconFile"), alias = "UTTypeIconFile")]
+    pub ut_type_icon_file: Option<String>,
+    #[serde(
+        rename(serialize = "UTTypeTagSpecification"),
+        alias = "UTTypeTagSpecification"
+    )]
+    #[serde(default)]
+    pub ut_type_tag_specification: UtTypeTagSpecification,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct UtTypeTagSpecification {
+    /// File name extensions without the leading dot.
+    #[serde(
+        rename(serialize = "public.filename-extension"),
+        alias = "public.filename-extension"
+    )]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub public_filename_extension: Vec<String>,
+    #[serde(rename(serialize = "public.mime-type"), alias = "public.mime-type")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub public_mime_type: Vec<String>,
+}
@@ -6,44 +6,92 @@ use apple_codesign::notarization::{
 use apple_codesign::stapling::Stapler;
 use apple_codesign::{BundleSigner, CodeSignatureFlags, SettingsScope, SigningSettings};
 use icns::{IconFamily, Image};
-use pkcs8::EncodePrivateKey;
 use plist::Value;
-use rasn_cms::{ContentInfo, SignedData};
+use sha2::{Digest, Sha256};
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Cursor};
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::time::{Duration, Instant};
 use x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair};
-use xcommon::{Scaler, ScalerOpts, Signer};
+use xcommon::{Exclude, Scaler, ScalerOpts, Signer};
 
 mod info;
+mod profile;
 
-pub use info::InfoPlist;
+pub use info::{
+    CfBundleDocumentType, CfBundleTypeRole, InfoPlist, LsHandlerRank, UtTypeDeclaration,
+    UtTypeTagSpecification,
+};
+use info::{CfBundleIcons, CfBundlePrimaryIcon};
+pub use profile::ProvisioningProfile;
 
 const MACOS_ICON_SIZES: [u32; 6] = [16, 32, 64, 128, 256, 512];
 const IOS_ICON_SIZES: [u32; 7] = [58, 76, 80, 120, 152, 167, 1024];
 
+/// `(idiom, size, scale)` of the icons in an iOS `AppIcon.appiconset`.
+const IOS_APP_ICON_SET: [(&str, &str, u32); 7] = [
+    ("iphone", "29x29", 2),
+    ("iphone", "40x40", 2),
+    ("iphone", "60x60", 2),
+    ("ipad", "76x76", 1),
+    ("ipad", "76x76", 2),
+    ("ipad", "83.5x83.5", 2),
+    ("ios-marketing", "1024x1024", 1),
+];
+
+/// `(idiom, size, scale)` of the icons in a macOS `AppIcon.appiconset`.
+const MACOS_APP_ICON_SET: [(&str, &str, u32); 10] = [
+    ("mac", "16x16", 1),
+    ("mac", "16x16", 2),
+    ("mac", "32x32", 1),
+    ("mac", "32x32", 2),
+    ("mac", "128x128", 1),
+    ("mac", "128x128", 2),
+    ("mac", "256x256", 1),
+    ("mac", "256x256", 2),
+    ("mac", "512x512", 1),
+    ("mac", "512x512", 2),
+];
+
 pub struct AppBundle {
     appdir: PathBuf,
     info: InfoPlist,
     entitlements: Option<Value>,
     development: bool,
+    exclude: Exclude,
+    signing_identity: Option<String>,
 }
 
 impl AppBundle {
     pub fn new(build_dir: &Path, info: InfoPlist) -> Result<Self> {
+        if let Some(name) = info.cf_bundle_name.as_ref() {
+            std::fs::remove_dir_all(build_dir.join(format!("{}.app", name))).ok();
+        }
+        Self::reuse(build_dir, info)
+    }
+
+    /// Like [`AppBundle::new`], but keeps the files of previous builds in the
+    /// bundle.
+    pub fn reuse(build_dir: &Path, info: InfoPlist) -> Result<Self> {
         anyhow::ensure!(info.cf_bundle_name.is_some(), "missing info.name");
         let appdir = build_dir.join(format!("{}.app", info.cf_bundle_name.as_ref().unwrap()));
-        std::fs::remove_dir_all(&appdir).ok();
         std::fs::create_dir_all(&appdir)?;
         Ok(Self {
             appdir,
             info,
             entitlements: None,
             development: false,
+            exclude: Default::default(),
+            signing_identity: None,
         })
     }
 
+    /// Files skipped when copying directories into the bundle.
+    pub fn set_exclude(&mut self, exclude: Exclude) {
+        self.exclude = exclude;
+    }
+
     pub fn appdir(&self) -> &Path {
         &self.appdir
     }
@@ -81,21 +129,55 @@ impl AppBundle {
         }
     }
 
+    /// Adds the icon, reusing the icons generated by the previous build while
+    /// neither the source icon nor the list of sizes changed.
     pub fn add_icon(&mut self, path: &Path) -> Result<()> {
-        let scaler = Scaler::open(path)?;
         let sizes = if self.ios() {
             &IOS_ICON_SIZES[..]
         } else {
             &MACOS_ICON_SIZES[..]
         };
+        // the appdir is recreated on every build, so the icons are kept next to it
+        let cache = self.appdir.with_extension("icons");
+        let stamp = cache.join("stamp");
+        let key = icon_stamp_key(path, sizes)?;
+        if xcommon::is_stamp_dirty(path, &stamp)?
+            || std::fs::read_to_string(&stamp).ok().as_deref() != Some(&key)
+        {
+            std::fs::remove_dir_all(&cache).ok();
+            std::fs::create_dir_all(&cache)?;
+            self.write_icons(path, sizes, &cache)?;
+            std::fs::write(&stamp, key)?;
+        } else {
+            log::debug!("icon of {} is up to date", self.appdir.display());
+        }
 
         if self.ios() {
             for size in sizes {
                 let filename = format!("icon_{}x{}.png", size, size);
-                let icon = self.appdir.join(&filename);
+                std::fs::copy(cache.join(&filename), self.appdir.join(&filename))?;
+                self.info.cf_bundle_icon_files.push(filename);
+            }
+        } else {
+            let resource_dir = self.resource_dir();
+            std::fs::create_dir_all(&resource_dir)?;
+            std::fs::copy(
+                cache.join("AppIcon.icns"),
+                resource_dir.join("AppIcon.icns"),
+            )?;
+            self.info.cf_bundle_icon_file = Some("AppIcon".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn write_icons(&self, path: &Path, sizes: &[u32], dir: &Path) -> Result<()> {
+        let scaler = Scaler::open(path)?;
+        if self.ios() {
+            for size in sizes {
+                let icon = dir.join(format!("icon_{}x{}.png", size, size));
                 let mut icon = BufWriter::new(File::create(icon)?);
                 scaler.write(&mut icon, ScalerOpts::new(*size))?;
-                self.info.cf_bundle_icon_files.push(filename);
             }
         } else {
             let mut icns = IconFamily::new();
@@ -107,14 +189,126 @@ impl AppBundle {
                 let image = Image::read_png(&*buf)?;
                 icns.add_icon(&image)?;
             }
-            let path = self.resource_dir().join("AppIcon.icns");
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
+            icns.write(BufWriter::new(File::create(dir.join("AppIcon.icns"))?))?;
+        }
+        Ok(())
+    }
+
+    /// Compiles an asset catalog into `Assets.car` using `actool`. The catalog is
+    /// created from the optional user-provided `.xcassets` directory, and an
+    /// `AppIcon.appiconset` is generated from `icon` unless the catalog already
+    /// contains one. When `actool` is unavailable (it requires Xcode) the icon is
+    /// added as loose images with [`Self::add_icon`] instead.
+    pub fn add_assets(&mut self, icon: Option<&Path>, xcassets: Option<&Path>) -> Result<()> {
+        if !has_actool() {
+            if let Some(xcassets) = xcassets {
+                log::warn!(
+                    "actool is not available, skipping asset catalog {}",
+                    xcassets.display()
+                );
+            }
+            if let Some(icon) = icon {
+                self.add_icon(icon)?;
+            }
+            return Ok(());
+        }
+        if icon.is_none() && xcassets.is_none() {
+            return Ok(());
+        }
+        let build_dir = self.appdir.parent().context("invalid appdir")?;
+        let catalog = build_dir.join("Assets.xcassets");
+        std::fs::remove_dir_all(&catalog).ok();
+        std::fs::create_dir_all(&catalog)?;
+        if let Some(xcassets) = xcassets {
+            xcommon::copy_dir_all(xcassets, &catalog)?;
+        }
+        let contents = catalog.join("Contents.json");
+        if !contents.exists() {
+            std::fs::write(
+                contents,
+                r#"{ "info": { "author": "xcode", "version": 1 } }"#,
+            )?;
+        }
+        let app_icon_set = catalog.join("AppIcon.appiconset");
+        if let Some(icon) = icon {
+            if !app_icon_set.exists() {
+                self.write_app_icon_set(icon, &app_icon_set)?;
             }
-            icns.write(BufWriter::new(File::create(path)?))?;
-            self.info.cf_bundle_icon_file = Some("AppIcon".to_string());
         }
 
+        let resource_dir = self.resource_dir();
+        std::fs::create_dir_all(&resource_dir)?;
+        let (platform, minimum_version) = if self.ios() {
+            ("iphoneos", self.info.minimum_os_version.as_deref())
+        } else {
+            ("macosx", self.info.ls_minimum_system_version.as_deref())
+        };
+        let mut cmd = Command::new("xcrun");
+        cmd.arg("actool")
+            .arg(&catalog)
+            .arg("--compile")
+            .arg(&resource_dir)
+            .arg("--platform")
+            .arg(platform)
+            .arg("--output-partial-info-plist")
+            .arg(build_dir.join("assetcatalog_generated_info.plist"));
+        if let Some(minimum_version) = minimum_version {
+            cmd.arg("--minimum-deployment-target").arg(minimum_version);
+        }
+        if self.ios() {
+            cmd.arg("--target-device")
+                .arg("iphone")
+                .arg("--target-device")
+                .arg("ipad");
+        }
+        if app_icon_set.exists() {
+            cmd.arg("--app-icon").arg("AppIcon");
+        }
+        let status = cmd.status()?;
+        anyhow::ensure!(status.success(), "failed to compile asset catalog");
+        if app_icon_set.exists() {
+            if self.ios() {
+                self.info.cf_bundle_icons = Some(CfBundleIcons {
+                    cf_bundle_primary_icon: Some(CfBundlePrimaryIcon {
+                        cf_bundle_icon_name: Some("AppIcon".into()),
+                    }),
+                });
+            } else {
+                self.info.cf_bundle_icon_file = Some("AppIcon".into());
+            }
+            self.info.cf_bundle_icon_name = Some("AppIcon".into());
+        }
+        Ok(())
+    }
+
+    fn write_app_icon_set(&self, icon: &Path, dir: &Path) -> Result<()> {
+        let scaler = Scaler::open(icon)?;
+        let icon_set = if self.ios() {
+            &IOS_APP_ICON_SET[..]
+        } else {
+            &MACOS_APP_ICON_SET[..]
+        };
+        std::fs::create_dir_all(dir)?;
+        let mut images = vec![];
+        for (idiom, size, scale) in icon_set {
+            let points: f32 = size.split_once('x').unwrap().0.parse()?;
+            let pixels = (points * *scale as f32) as u32;
+            let filename = format!("icon_{}x{}.png", pixels, pixels);
+            let path = dir.join(&filename);
+            if !path.exists() {
+                let mut icon = BufWriter::new(File::create(path)?);
+                scaler.write(&mut icon, ScalerOpts::new(pixels))?;
+            }
+            images.push(format!(
+                r#"{{ "filename": "{}", "idiom": "{}", "scale": "{}x", "size": "{}" }}"#,
+                filename, idiom, scale, size
+            ));
+        }
+        let contents = format!(
+            r#"{{ "images": [{}], "info": {{ "author": "xcode", "version": 1 }} }}"#,
+            images.join(", ")
+        );
+        std::fs::write(dir.join("Contents.json"), contents)?;
         Ok(())
     }
 
@@ -130,7 +324,7 @@ impl AppBundle {
     pub fn add_directory(&self, source: &Path, dest: &Path) -> Result<()> {
         let resource_dir = self.resource_dir().join(dest);
         std::fs::create_dir_all(&resource_dir)?;
-        xcommon::copy_dir_all(source, &resource_dir)?;
+        xcommon::copy_dir_filtered(source, &resource_dir, &self.exclude)?;
         Ok(())
     }
 
@@ -148,7 +342,7 @@ impl AppBundle {
     pub fn add_framework(&self, path: &Path) -> Result<()> {
         let framework_dir = self.framework_dir().join(path.file_name().unwrap());
         std::fs::create_dir_all(&framework_dir)?;
-        xcommon::copy_dir_all(path, &framework_dir)?;
+        xcommon::copy_dir_filtered(path, &framework_dir, &self.exclude)?;
         Ok(())
     }
 
@@ -161,54 +355,45 @@ impl AppBundle {
     }
 
     pub fn add_provisioning_profile(&mut self, raw_profile: &[u8]) -> Result<()> {
-        let info = rasn::der::decode::<ContentInfo>(raw_profile)
-            .map_err(|err| anyhow::anyhow!("{}", err))?;
-        let data = rasn::der::decode::<SignedData>(info.content.as_bytes())
-            .map_err(|err| anyhow::anyhow!("{}", err))?;
-        let xml = data.encap_content_info.content.as_ref().unwrap().as_ref();
-        let profile: plist::Value = plist::from_reader_xml(xml)?;
-        log::debug!("provisioning profile: {:?}", profile);
-        let dict = profile
-            .as_dictionary()
-            .context("invalid provisioning profile")?;
-        let entitlements = dict
-            .get("Entitlements")
-            .context("missing key Entitlements")?
-            .clone();
-        let app_id = entitlements
-            .as_dictionary()
-            .context("invalid entitlements")?
-            .get("application-identifier")
-            .context("missing application identifier")?
-            .as_string()
-            .context("missing application identifier")?;
-        let bundle_prefix = app_id
-            .split_once('.')
-            .with_context(|| format!("invalid app id {}", app_id))?
-            .1;
-        self.development = dict.get("ProvisionedDevices").is_some();
-
-        if let Some(bundle_identifier) = self.info.cf_bundle_identifier.as_ref() {
-            let bundle_prefix = if bundle_prefix.ends_with('*') {
-                bundle_prefix.strip_suffix('*').unwrap()
-            } else {
-                bundle_prefix
-            };
-            anyhow::ensure!(
-                bundle_identifier.starts_with(bundle_prefix),
-                "bundle identifier mismatch"
+        let profile = ProvisioningProfile::parse(raw_profile.to_vec())?;
+        self.add_provisioning_profiles(&[profile])
+    }
+
+    /// Embeds the profile matching the bundle identifier, preferring an
+    /// explicit app id over wildcards.
+    pub fn add_provisioning_profiles(&mut self, profiles: &[ProvisioningProfile]) -> Result<()> {
+        let profile = if let Some(bundle_identifier) = self.info.cf_bundle_identifier.as_ref() {
+            ProvisioningProfile::select(profiles, bundle_identifier)?
+        } else if let [profile] = profiles {
+            profile
+        } else {
+            anyhow::bail!(
+                "selecting one of multiple provisioning profiles requires a bundle identifier"
             );
-        }
-        self.entitlements = Some(entitlements);
-        std::fs::write(self.appdir().join("embedded.mobileprovision"), raw_profile)?;
+        };
+        self.development = profile.is_development();
+        self.entitlements = Some(profile.entitlements().clone());
+        std::fs::write(
+            self.appdir().join("embedded.mobileprovision"),
+            profile.raw(),
+        )?;
         Ok(())
     }
 
+    /// Signs with a keychain identity returned by [`keychain_identity`]
+    /// instead of a [`Signer`], using `codesign`.
+    pub fn set_signing_identity(&mut self, identity: Option<String>) {
+        self.signing_identity = identity;
+    }
+
     pub fn finish(&self, signer: Option<Signer>) -> Result<()> {
         let path = self.content_dir().join("Info.plist");
         plist::to_file_xml(path, &self.info)?;
 
-        if let Some(signer) = signer {
+        if let Some(identity) = self.signing_identity.as_deref() {
+            println!("signing {}", self.appdir().display());
+            self.codesign(self.appdir(), identity, true)?;
+        } else if let Some(signer) = signer {
             println!("signing {}", self.appdir().display());
             anyhow::ensure!(
                 self.info.cf_bundle_identifier.is_some(),
@@ -217,8 +402,8 @@ impl AppBundle {
             let mut signing_settings = SigningSettings::default();
             let cert =
                 CapturedX509Certificate::from_der(rasn::der::encode(signer.cert()).unwrap())?;
-            let secret = signer.key().to_pkcs8_der().unwrap();
-            let key = InMemorySigningKeyPair::from_pkcs8_der(secret.as_bytes())?;
+            let secret = signer.private_key_der()?;
+            let key = InMemorySigningKeyPair::from_pkcs8_der(&secret)?;
             signing_settings.set_signing_key(&key, cert);
             signing_settings.chain_apple_certificates();
             signing_settings
@@ -243,13 +428,54 @@ impl AppBundle {
         Ok(())
     }
 
+    /// Signs the dmg with the identity set by [`AppBundle::set_signing_identity`].
+    pub fn sign_dmg_with_identity(&self, path: &Path) -> Result<()> {
+        let identity = self
+            .signing_identity
+            .as_deref()
+            .context("no signing identity set")?;
+        println!("signing {}", path.display());
+        self.codesign(path, identity, false)
+    }
+
+    fn codesign(&self, path: &Path, identity: &str, bundle: bool) -> Result<()> {
+        let mut cmd = Command::new("codesign");
+        cmd.arg("--force").arg("--sign").arg(identity);
+        if self.development {
+            cmd.arg("--timestamp=none");
+        } else {
+            cmd.arg("--timestamp");
+        }
+        if let Some(identifier) = self.info.cf_bundle_identifier.as_ref() {
+            cmd.arg("--identifier").arg(identifier);
+        }
+        let entitlements = self.appdir.with_extension("entitlements");
+        if bundle {
+            if !self.ios() {
+                cmd.arg("--options").arg("runtime");
+            }
+            if let Some(value) = self.entitlements.as_ref() {
+                value.to_file_xml(&entitlements)?;
+                cmd.arg("--entitlements").arg(&entitlements);
+            }
+        }
+        let status = cmd.arg(path).status().context("codesign not found")?;
+        std::fs::remove_file(&entitlements).ok();
+        anyhow::ensure!(
+            status.success(),
+            "codesign failed with exit code {:?}",
+            status
+        );
+        Ok(())
+    }
+
     pub fn sign_dmg(&self, path: &Path, signer: &Signer) -> Result<()> {
         println!("signing {}", path.display());
         let mut f = OpenOptions::new().read(true).write(true).open(path)?;
         let mut signing_settings = SigningSettings::default();
         let cert = CapturedX509Certificate::from_der(rasn::der::encode(signer.cert()).unwrap())?;
-        let secret = signer.key().to_pkcs8_der().unwrap();
-        let key = InMemorySigningKeyPair::from_pkcs8_der(secret.as_bytes())?;
+        let secret = signer.private_key_der()?;
+        let key = InMemorySigningKeyPair::from_pkcs8_der(&secret)?;
         signing_settings.set_signing_key(&key, cert);
         signing_settings.chain_apple_certificates();
         signing_settings
@@ -265,6 +491,123 @@ impl AppBundle {
     }
 }
 
+/// Resolves a code signing identity in the keychain by its name, e.g.
+/// `Developer ID Application: Example (TEAMID)`, or its SHA-1 hash. Returns
+/// the hash, which names the identity unambiguously for `codesign`.
+pub fn keychain_identity(name: &str) -> Result<String> {
+    anyhow::ensure!(
+        cfg!(target_os = "macos"),
+        "keychain signing identities are only supported on macos"
+    );
+    let output = Command::new("security")
+        .arg("find-identity")
+        .arg("-v")
+        .arg("-p")
+        .arg("codesigning")
+        .output()
+        .context("security not found")?;
+    anyhow::ensure!(output.status.success(), "security find-identity failed");
+    // lines look like `  1) <SHA-1> "<name>"`
+    let stdout = std::str::from_utf8(&output.stdout)?;
+    let identities = stdout
+        .lines()
+        .filter_map(|line| line.trim().split_once(") ")?.1.split_once(' '))
+        .map(|(hash, identity)| (hash, identity.trim_matches('"')))
+        .collect::<Vec<_>>();
+    let matches = identities
+        .iter()
+        .filter(|(hash, identity)| {
+            *identity == name || identity.starts_with(name) || hash.eq_ignore_ascii_case(name)
+        })
+        .collect::<Vec<_>>();
+    match matches[..] {
+        [(hash, _)] => Ok(hash.to_string()),
+        [] => anyhow::bail!("no valid code signing identity `{}` in the keychain", name),
+        _ => anyhow::bail!(
+            "signing identity `{}` is ambiguous, matches: {}",
+            name,
+            matches
+                .iter()
+                .map(|(_, identity)| *identity)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Replaces the code signature of a built `.app` or `.dmg` using a keychain
+/// identity, keeping the identifier, entitlements and flags of the previous
+/// signature.
+pub fn resign_with_identity(path: &Path, identity: &str) -> Result<()> {
+    println!("signing {}", path.display());
+    let status = Command::new("codesign")
+        .arg("--force")
+        .arg("--sign")
+        .arg(identity)
+        .arg("--timestamp")
+        .arg("--preserve-metadata=identifier,entitlements,flags")
+        .arg(path)
+        .status()
+        .context("codesign not found")?;
+    anyhow::ensure!(
+        status.success(),
+        "codesign failed with exit code {:?}",
+        status
+    );
+    Ok(())
+}
+
+/// Replaces the code signature of a built `.app` or `.dmg` using `signer`.
+pub fn resign(path: &Path, signer: &Signer) -> Result<()> {
+    println!("signing {}", path.display());
+    let mut signing_settings = SigningSettings::default();
+    let cert = CapturedX509Certificate::from_der(rasn::der::encode(signer.cert()).unwrap())?;
+    let secret = signer.private_key_der()?;
+    let key = InMemorySigningKeyPair::from_pkcs8_der(&secret)?;
+    signing_settings.set_signing_key(&key, cert);
+    signing_settings.chain_apple_certificates();
+    signing_settings
+        .set_team_id_from_signing_certificate()
+        .context("signing certificate is missing team id")?;
+    signing_settings.set_time_stamp_url("http://timestamp.apple.com/ts01")?;
+    if path.is_dir() {
+        let bundle_signer = BundleSigner::new_from_path(path)?;
+        bundle_signer.write_signed_bundle(path, &signing_settings)?;
+    } else {
+        let identifier = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .context("invalid dmg path")?;
+        signing_settings.set_binary_identifier(SettingsScope::Main, identifier);
+        let mut f = OpenOptions::new().read(true).write(true).open(path)?;
+        DmgSigner::default().sign_file(&signing_settings, &mut f)?;
+    }
+    Ok(())
+}
+
+/// Hash of the source icon and the generated sizes.
+fn icon_stamp_key(icon: &Path, sizes: &[u32]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(std::fs::read(icon)?);
+    for size in sizes {
+        hasher.update(size.to_le_bytes());
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn has_actool() -> bool {
+    Command::new("xcrun")
+        .arg("--find")
+        .arg("actool")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or_default()
+}
+
 pub fn app_bundle_identifier(bundle: &Path) -> Result<String> {
     let plist = if bundle.join("Contents").exists() {
         bundle.join("Contents").join("Info.plist")
@@ -283,7 +626,19 @@ pub fn app_bundle_identifier(bundle: &Path) -> Result<String> {
     Ok(bundle_identifier.to_string())
 }
 
-pub fn notarize(path: &Path, api_key: &Path) -> Result<()> {
+/// Longest wait between two polls of the notarization status.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Submits `path` for notarization and staples the ticket once it is accepted.
+/// The status is polled starting at `poll_interval`, doubling the interval
+/// up to a minute. Fails with the submission id when notarization doesn't
+/// finish within `timeout`.
+pub fn notarize(
+    path: &Path,
+    api_key: &Path,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<()> {
     println!("notarizing {}", path.display());
     let notarizer = Notarizer::from_api_key(api_key)?;
     let submission_id =
@@ -294,6 +649,7 @@ pub fn notarize(path: &Path, api_key: &Path) -> Result<()> {
         };
     println!("submission id: {}", submission_id);
     let start_time = Instant::now();
+    let mut interval = poll_interval;
     loop {
         let resp = notarizer.get_submission(&submission_id)?;
         let status = resp.data.attributes.status;
@@ -305,7 +661,15 @@ pub fn notarize(path: &Path, api_key: &Path) -> Result<()> {
             resp.into_result()?;
             break;
         }
-        std::thread::sleep(Duration::from_secs(3));
+        anyhow::ensure!(
+            elapsed < timeout,
+            "notarization of submission {} didn't finish within {} minutes, check it later with `xcrun notarytool info {}`",
+            submission_id,
+            timeout.as_secs() / 60,
+            submission_id
+        );
+        std::thread::sleep(interval.min(timeout - elapsed));
+        interval = (interval * 2).min(MAX_POLL_INTERVAL.max(poll_interval));
     }
     let stapler = Stapler::new()?;
     stapler.staple_path(path)?;
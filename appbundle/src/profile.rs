@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use plist::Value;
+use rasn_cms::{ContentInfo, SignedData};
+
+/// An apple provisioning profile, parsed far enough to match it against
+/// bundle identifiers.
+#[derive(Clone, Debug)]
+pub struct ProvisioningProfile {
+    raw: Vec<u8>,
+    app_id: String,
+    entitlements: Value,
+    development: bool,
+}
+
+impl ProvisioningProfile {
+    pub fn parse(raw: Vec<u8>) -> Result<Self> {
+        let info =
+            rasn::der::decode::<ContentInfo>(&raw).map_err(|err| anyhow::anyhow!("{}", err))?;
+        let data = rasn::der::decode::<SignedData>(info.content.as_bytes())
+            .map_err(|err| anyhow::anyhow!("{}", err))?;
+        let xml = data
+            .encap_content_info
+            .content
+            .as_ref()
+            .context("invalid provisioning profile")?
+            .as_ref();
+        let profile: Value = plist::from_reader_xml(xml)?;
+        log::debug!("provisioning profile: {:?}", profile);
+        let dict = profile
+            .as_dictionary()
+            .context("invalid provisioning profile")?;
+        let entitlements = dict
+            .get("Entitlements")
+            .context("missing key Entitlements")?
+            .clone();
+        let app_id = entitlements
+            .as_dictionary()
+            .context("invalid entitlements")?
+            .get("application-identifier")
+            .context("missing application identifier")?
+            .as_string()
+            .context("missing application identifier")?;
+        // strip the team id
+        let app_id = app_id
+            .split_once('.')
+            .with_context(|| format!("invalid app id {}", app_id))?
+            .1
+            .to_string();
+        let development = dict.get("ProvisionedDevices").is_some();
+        Ok(Self {
+            raw,
+            app_id,
+            entitlements,
+            development,
+        })
+    }
+
+    /// Bundle identifier the profile is valid for without the team id. May
+    /// end with a `*` wildcard.
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn entitlements(&self) -> &Value {
+        &self.entitlements
+    }
+
+    /// Profiles listing devices are for development, the others are for
+    /// distribution.
+    pub fn is_development(&self) -> bool {
+        self.development
+    }
+
+    /// How specific the match of `bundle_identifier` is, an explicit app id
+    /// beats any wildcard.
+    fn score(&self, bundle_identifier: &str) -> Option<usize> {
+        if let Some(prefix) = self.app_id.strip_suffix('*') {
+            bundle_identifier
+                .starts_with(prefix)
+                .then_some(prefix.len())
+        } else {
+            (bundle_identifier == self.app_id).then_some(usize::MAX)
+        }
+    }
+
+    /// Selects the most specific profile for `bundle_identifier`.
+    pub fn select<'a>(profiles: &'a [Self], bundle_identifier: &str) -> Result<&'a Self> {
+        profiles
+            .iter()
+            .filter_map(|profile| Some((profile.score(bundle_identifier)?, profile)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, profile)| profile)
+            .with_context(|| {
+                let app_ids = profiles
+                    .iter()
+                    .map(|profile| profile.app_id.as_str())
+                    .collect::<Vec<_>>();
+                format!(
+                    "no provisioning profile matches bundle identifier {}, available app ids: {}",
+                    bundle_identifier,
+                    app_ids.join(", ")
+                )
+            })
+    }
+}
@@ -1,25 +1,215 @@
 use anyhow::{Context, Result};
+use serde::Deserialize;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use xcommon::Signer;
+use xcommon::{Exclude, Signer};
+
+#[cfg(feature = "squashfs")]
+mod squashfs;
+mod update;
 
 static RUNTIME: &[u8] = include_bytes!("../assets/runtime-x86_64");
 
+/// Compressor used by `mksquashfs`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Gzip,
+    Lzo,
+    Lz4,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Lzo => "lzo",
+            Self::Lz4 => "lz4",
+            Self::Xz => "xz",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Range of `-Xcompression-level`, if the compressor supports it.
+    fn levels(self) -> Option<(u32, u32)> {
+        match self {
+            Self::Gzip | Self::Lzo => Some((1, 9)),
+            Self::Zstd => Some((1, 22)),
+            Self::Lz4 | Self::Xz => None,
+        }
+    }
+
+    /// First `mksquashfs` version supporting the compressor.
+    fn min_mksquashfs_version(self) -> (u32, u32) {
+        match self {
+            Self::Gzip => (1, 0),
+            Self::Lzo | Self::Xz => (4, 1),
+            Self::Lz4 => (4, 3),
+            Self::Zstd => (4, 4),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct SquashfsOptions {
+    #[serde(default)]
+    pub compression: Compression,
+    pub compression_level: Option<u32>,
+    /// Block size in bytes, a power of two between 4K and 1M.
+    pub block_size: Option<u32>,
+}
+
+impl SquashfsOptions {
+    fn args(&self) -> Result<Vec<String>> {
+        let mut args = vec![];
+        let compression = self.compression;
+        if compression != Compression::Gzip {
+            args.push("-comp".to_string());
+            args.push(compression.as_str().to_string());
+        }
+        if let Some(level) = self.compression_level {
+            let (min, max) = compression.levels().with_context(|| {
+                format!(
+                    "{} does not support setting a compression level",
+                    compression.as_str()
+                )
+            })?;
+            anyhow::ensure!(
+                (min..=max).contains(&level),
+                "{} compression level must be between {} and {}",
+                compression.as_str(),
+                min,
+                max
+            );
+            args.push("-Xcompression-level".to_string());
+            args.push(level.to_string());
+        }
+        if let Some(block_size) = self.block_size {
+            anyhow::ensure!(
+                block_size.is_power_of_two() && (4096..=1048576).contains(&block_size),
+                "block size must be a power of two between 4K and 1M"
+            );
+            args.push("-b".to_string());
+            args.push(block_size.to_string());
+        }
+        Ok(args)
+    }
+}
+
+fn apprun_env_script(name: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+APPDIR="${{APPDIR:-$(dirname "$(readlink -f "$0")")}}"
+export LD_LIBRARY_PATH="$APPDIR/lib${{LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}}"
+export PATH="$APPDIR${{PATH:+:$PATH}}"
+export XDG_DATA_DIRS="$APPDIR/share:${{XDG_DATA_DIRS:-/usr/local/share:/usr/share}}"
+exec "$APPDIR/{}" "$@"
+"#,
+        name
+    )
+}
+
+/// Checks that the installed `mksquashfs` is recent enough for `compression`.
+/// Compressors disabled when building mksquashfs aren't listed by `-version`,
+/// mksquashfs reports those itself.
+fn ensure_compressor(compression: Compression) -> Result<()> {
+    if compression == Compression::Gzip {
+        return Ok(());
+    }
+    let output = Command::new("mksquashfs")
+        .arg("-version")
+        .output()
+        .context("mksquashfs not found")?;
+    let mut version = String::from_utf8_lossy(&output.stdout).into_owned();
+    version.push_str(&String::from_utf8_lossy(&output.stderr));
+    let Some((major, minor)) = parse_mksquashfs_version(&version) else {
+        return Ok(());
+    };
+    let (min_major, min_minor) = compression.min_mksquashfs_version();
+    anyhow::ensure!(
+        (major, minor) >= (min_major, min_minor),
+        "{} compression requires mksquashfs {}.{} or newer, found {}.{}",
+        compression.as_str(),
+        min_major,
+        min_minor,
+        major,
+        minor
+    );
+    Ok(())
+}
+
+/// Major and minor version of the `mksquashfs version 4.5.1 (2022/03/17)`
+/// line printed by `mksquashfs -version`.
+fn parse_mksquashfs_version(output: &str) -> Option<(u32, u32)> {
+    let version = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("mksquashfs version "))?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
 pub struct AppImage {
     appdir: PathBuf,
     name: String,
+    squashfs: SquashfsOptions,
+    exclude: Exclude,
+    update_info: Option<String>,
+    zsync: bool,
 }
 
 impl AppImage {
     pub fn new(build_dir: &Path, name: String) -> Result<Self> {
+        std::fs::remove_dir_all(build_dir.join(format!("{}.AppDir", name))).ok();
+        Self::reuse(build_dir, name)
+    }
+
+    /// Like [`AppImage::new`], but keeps the files of previous builds in the
+    /// AppDir.
+    pub fn reuse(build_dir: &Path, name: String) -> Result<Self> {
         let appdir = build_dir.join(format!("{}.AppDir", name));
-        std::fs::remove_dir_all(&appdir).ok();
         std::fs::create_dir_all(&appdir)?;
-        Ok(Self { appdir, name })
+        Ok(Self {
+            appdir,
+            name,
+            squashfs: Default::default(),
+            exclude: Default::default(),
+            update_info: None,
+            zsync: false,
+        })
+    }
+
+    pub fn set_squashfs_options(&mut self, options: SquashfsOptions) {
+        self.squashfs = options;
+    }
+
+    /// Embeds update information for AppImageUpdate, e.g.
+    /// `zsync|https://example.com/app-latest.AppImage.zsync`.
+    pub fn set_update_info(&mut self, info: String) -> Result<()> {
+        update::validate(&info)?;
+        self.update_info = Some(info);
+        Ok(())
+    }
+
+    /// Writes `<out>.zsync` with `zsyncmake` after building.
+    pub fn set_zsync(&mut self, zsync: bool) {
+        self.zsync = zsync;
+    }
+
+    /// Files skipped by [`AppImage::add_directory`].
+    pub fn set_exclude(&mut self, exclude: Exclude) {
+        self.exclude = exclude;
     }
 
     pub fn appdir(&self) -> &Path {
@@ -27,8 +217,34 @@ impl AppImage {
     }
 
     pub fn add_apprun(&self) -> Result<()> {
+        let apprun = self.appdir.join("AppRun");
+        std::fs::remove_file(&apprun).ok();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&self.name, apprun)?;
+        Ok(())
+    }
+
+    /// Installs `script` as `AppRun`, for apps which need to set up their
+    /// environment before launching the executable.
+    pub fn add_apprun_script(&self, script: &Path) -> Result<()> {
+        let apprun = self.appdir.join("AppRun");
+        // a reused AppDir links AppRun to the executable
+        std::fs::remove_file(&apprun).ok();
+        std::fs::copy(script, &apprun)
+            .with_context(|| format!("failed to copy AppRun script {}", script.display()))?;
         #[cfg(unix)]
-        std::os::unix::fs::symlink(&self.name, self.appdir.join("AppRun"))?;
+        std::fs::set_permissions(&apprun, std::fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+
+    /// Generates an `AppRun` script which adds the bundled libraries to
+    /// `LD_LIBRARY_PATH` and the app dir to `PATH` and `XDG_DATA_DIRS`.
+    pub fn add_apprun_env(&self) -> Result<()> {
+        let apprun = self.appdir.join("AppRun");
+        std::fs::remove_file(&apprun).ok();
+        std::fs::write(&apprun, apprun_env_script(&self.name))?;
+        #[cfg(unix)]
+        std::fs::set_permissions(&apprun, std::fs::Permissions::from_mode(0o755))?;
         Ok(())
     }
 
@@ -53,8 +269,10 @@ impl AppImage {
             .context("unsupported extension")?;
         let name = format!("{}.{}", self.name, ext);
         self.add_file(path, Path::new(&name))?;
+        let dir_icon = self.appdir.join(".DirIcon");
+        std::fs::remove_file(&dir_icon).ok();
         #[cfg(unix)]
-        std::os::unix::fs::symlink(name, self.appdir.join(".DirIcon"))?;
+        std::os::unix::fs::symlink(name, dir_icon)?;
         Ok(())
     }
 
@@ -70,7 +288,7 @@ impl AppImage {
     pub fn add_directory(&self, source: &Path, dest: &Path) -> Result<()> {
         let dest = self.appdir.join(dest);
         std::fs::create_dir_all(&dest)?;
-        xcommon::copy_dir_all(source, &dest)?;
+        xcommon::copy_dir_filtered(source, &dest, &self.exclude)?;
         Ok(())
     }
 
@@ -80,26 +298,95 @@ impl AppImage {
             .parent()
             .unwrap()
             .join(format!("{}.squashfs", self.name));
+        self.mksquashfs(&squashfs)?;
+        let mut squashfs = BufReader::new(File::open(squashfs)?);
+        let mut f = File::create(out)?;
+        #[cfg(unix)]
+        f.set_permissions(std::fs::Permissions::from_mode(0o755))?;
+        let mut runtime = RUNTIME.to_vec();
+        if let Some(info) = self.update_info.as_deref() {
+            update::embed(&mut runtime, info)?;
+        }
+        let mut writer = BufWriter::new(&mut f);
+        writer.write_all(&runtime)?;
+        std::io::copy(&mut squashfs, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        // TODO: sign
+        if self.zsync {
+            update::zsyncmake(out)?;
+        }
+        Ok(())
+    }
+
+    /// The bundled writer only supports gzip, other compressors still require
+    /// `mksquashfs`.
+    #[cfg(feature = "squashfs")]
+    fn mksquashfs(&self, out: &Path) -> Result<()> {
+        if self.squashfs.compression != Compression::Gzip {
+            return self.external_mksquashfs(out);
+        }
+        let level = self.squashfs.compression_level.unwrap_or(9);
+        anyhow::ensure!(
+            (1..=9).contains(&level),
+            "gzip compression level must be between 1 and 9"
+        );
+        squashfs::write(
+            &self.appdir,
+            out,
+            self.squashfs.block_size.unwrap_or(131072),
+            level,
+        )
+    }
+
+    #[cfg(not(feature = "squashfs"))]
+    fn mksquashfs(&self, out: &Path) -> Result<()> {
+        self.external_mksquashfs(out)
+    }
+
+    fn external_mksquashfs(&self, out: &Path) -> Result<()> {
+        let args = self.squashfs.args()?;
+        ensure_compressor(self.squashfs.compression)?;
         let status = Command::new("mksquashfs")
             .arg(&self.appdir)
-            .arg(&squashfs)
+            .arg(out)
             .arg("-root-owned")
             .arg("-noappend")
             .arg("-quiet")
+            .args(args)
             .status()?;
         anyhow::ensure!(
             status.success(),
             "mksquashfs failed with exit code {:?}",
             status
         );
-        let mut squashfs = BufReader::new(File::open(squashfs)?);
-        let mut f = File::create(out)?;
-        #[cfg(unix)]
-        f.set_permissions(std::fs::Permissions::from_mode(0o755))?;
-        let mut out = BufWriter::new(&mut f);
-        out.write_all(RUNTIME)?;
-        std::io::copy(&mut squashfs, &mut out)?;
-        // TODO: sign
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mksquashfs_version() {
+        let output = "mksquashfs version 4.5.1 (2022/03/17)\ncopyright (C) 2022 Phillip Lougher\n";
+        assert_eq!(parse_mksquashfs_version(output), Some((4, 5)));
+        assert_eq!(
+            parse_mksquashfs_version("mksquashfs version 4.4-git.1 (2020/10/25)"),
+            Some((4, 4))
+        );
+        assert_eq!(parse_mksquashfs_version("mksquashfs 4.6"), None);
+        assert!((4, 3) < Compression::Zstd.min_mksquashfs_version());
+    }
+
+    #[test]
+    fn test_apprun_env_script() {
+        let script = apprun_env_script("app");
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains(
+            r#"export LD_LIBRARY_PATH="$APPDIR/lib${LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}""#
+        ));
+        assert!(script.ends_with("exec \"$APPDIR/app\" \"$@\"\n"));
+    }
+}
@@ -0,0 +1,648 @@
+//! Minimal squashfs 4.0 writer producing gzip compressed images without
+//! fragments or extended attributes, which is what the AppImage runtime
+//! expects to find appended to itself.
+use anyhow::{Context, Result};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const MAGIC: u32 = 0x7371_7368;
+const METADATA_SIZE: usize = 8192;
+const METADATA_UNCOMPRESSED: u16 = 1 << 15;
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+const COMPRESSION_GZIP: u16 = 1;
+const FLAG_NO_FRAGMENTS: u16 = 1 << 4;
+const FLAG_NO_XATTRS: u16 = 1 << 9;
+const INVALID: u64 = u64::MAX;
+const SUPERBLOCK_SIZE: u64 = 96;
+
+const BASIC_DIR: u16 = 1;
+const BASIC_FILE: u16 = 2;
+const BASIC_SYMLINK: u16 = 3;
+
+/// Writes the contents of `source` as a root owned squashfs image to `out`.
+pub fn write(source: &Path, out: &Path, block_size: u32, level: u32) -> Result<()> {
+    anyhow::ensure!(
+        block_size.is_power_of_two() && (4096..=1048576).contains(&block_size),
+        "block size must be a power of two between 4K and 1M"
+    );
+    let root = Node::read(source)?;
+    let mut w = Writer {
+        out: BufWriter::new(File::create(out)?),
+        pos: SUPERBLOCK_SIZE,
+        block_size,
+        level: Compression::new(level),
+        inodes: MetadataWriter::new(Compression::new(level)),
+        dirs: MetadataWriter::new(Compression::new(level)),
+    };
+    w.out.write_all(&[0; SUPERBLOCK_SIZE as usize])?;
+    let inode_count = root.count();
+    let (root_ref, _) = w.write_node(&root, 1, inode_count + 1)?;
+    w.finish(root_ref, inode_count)
+}
+
+enum Node {
+    Dir {
+        mode: u16,
+        mtime: u32,
+        children: Vec<(Vec<u8>, Node)>,
+    },
+    File {
+        mode: u16,
+        mtime: u32,
+        path: PathBuf,
+        size: u64,
+    },
+    Symlink {
+        mode: u16,
+        mtime: u32,
+        target: PathBuf,
+    },
+}
+
+impl Node {
+    fn read(path: &Path) -> Result<Self> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        let mode = mode(&metadata);
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|time| time.as_secs() as u32)
+            .unwrap_or_default();
+        let file_type = metadata.file_type();
+        if file_type.is_dir() {
+            let mut children = vec![];
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                children.push((name_bytes(&entry.file_name()), Self::read(&entry.path())?));
+            }
+            children.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(Self::Dir {
+                mode,
+                mtime,
+                children,
+            })
+        } else if file_type.is_symlink() {
+            Ok(Self::Symlink {
+                mode,
+                mtime,
+                target: std::fs::read_link(path)?,
+            })
+        } else if file_type.is_file() {
+            Ok(Self::File {
+                mode,
+                mtime,
+                path: path.to_path_buf(),
+                size: metadata.len(),
+            })
+        } else {
+            anyhow::bail!("unsupported file type {}", path.display());
+        }
+    }
+
+    /// Number of inodes in this subtree.
+    fn count(&self) -> u32 {
+        match self {
+            Self::Dir { children, .. } => {
+                1 + children.iter().map(|(_, child)| child.count()).sum::<u32>()
+            }
+            _ => 1,
+        }
+    }
+
+    fn inode_type(&self) -> u16 {
+        match self {
+            Self::Dir { .. } => BASIC_DIR,
+            Self::File { .. } => BASIC_FILE,
+            Self::Symlink { .. } => BASIC_SYMLINK,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> u16 {
+    use std::os::unix::fs::PermissionsExt;
+    (metadata.permissions().mode() & 0o7777) as u16
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &std::fs::Metadata) -> u16 {
+    if metadata.is_file() {
+        0o644
+    } else {
+        0o755
+    }
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().replace('\\', "/").into_bytes()
+}
+
+/// Packs metadata into blocks of 8K which are compressed individually.
+struct MetadataWriter {
+    level: Compression,
+    buf: Vec<u8>,
+    out: Vec<u8>,
+}
+
+impl MetadataWriter {
+    fn new(level: Compression) -> Self {
+        Self {
+            level,
+            buf: Vec::with_capacity(METADATA_SIZE),
+            out: vec![],
+        }
+    }
+
+    /// Start of the current block relative to the start of the table and offset
+    /// into the uncompressed block.
+    fn position(&self) -> (u32, u16) {
+        (self.out.len() as u32, self.buf.len() as u16)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.buf.extend_from_slice(data);
+        while self.buf.len() >= METADATA_SIZE {
+            self.flush(METADATA_SIZE)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, len: usize) -> Result<()> {
+        let block = self.buf.drain(..len).collect::<Vec<_>>();
+        let compressed = compress(&block, self.level)?;
+        if compressed.len() < block.len() {
+            self.out
+                .extend_from_slice(&(compressed.len() as u16).to_le_bytes());
+            self.out.extend_from_slice(&compressed);
+        } else {
+            let header = block.len() as u16 | METADATA_UNCOMPRESSED;
+            self.out.extend_from_slice(&header.to_le_bytes());
+            self.out.extend_from_slice(&block);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        if !self.buf.is_empty() {
+            self.flush(self.buf.len())?;
+        }
+        Ok(self.out)
+    }
+}
+
+fn compress(data: &[u8], level: Compression) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(data.len()), level);
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+struct Writer<W: Write + Seek> {
+    out: W,
+    pos: u64,
+    block_size: u32,
+    level: Compression,
+    inodes: MetadataWriter,
+    dirs: MetadataWriter,
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Writes the subtree in post order, so that the inode references of all
+    /// children are known when writing a directory. Inodes are numbered in the
+    /// same order starting at `first`. Returns the inode reference and number.
+    fn write_node(&mut self, node: &Node, first: u32, parent: u32) -> Result<(u64, u32)> {
+        let number = first + node.count() - 1;
+        let mut inode = vec![];
+        match node {
+            Node::Dir {
+                mode,
+                mtime,
+                children,
+            } => {
+                let mut entries = Vec::with_capacity(children.len());
+                let mut next = first;
+                for (name, child) in children {
+                    let (inode_ref, child_number) = self.write_node(child, next, number)?;
+                    next += child.count();
+                    entries.push((name.clone(), child.inode_type(), inode_ref, child_number));
+                }
+                let subdirs = children
+                    .iter()
+                    .filter(|(_, child)| matches!(child, Node::Dir { .. }))
+                    .count() as u32;
+                let (block_start, block_offset) = self.dirs.position();
+                let listing = dir_listing(&entries)?;
+                let file_size = u16::try_from(listing.len() + 3)
+                    .ok()
+                    .context("directory has too many entries")?;
+                self.dirs.write(&listing)?;
+                inode_header(&mut inode, BASIC_DIR, *mode, *mtime, number);
+                inode.extend_from_slice(&block_start.to_le_bytes());
+                inode.extend_from_slice(&(2 + subdirs).to_le_bytes());
+                inode.extend_from_slice(&file_size.to_le_bytes());
+                inode.extend_from_slice(&block_offset.to_le_bytes());
+                inode.extend_from_slice(&parent.to_le_bytes());
+            }
+            Node::File {
+                mode,
+                mtime,
+                path,
+                size,
+            } => {
+                let file_size = u32::try_from(*size)
+                    .ok()
+                    .with_context(|| format!("{} is too large", path.display()))?;
+                let blocks_start = u32::try_from(self.pos)
+                    .ok()
+                    .context("squashfs image is too large")?;
+                let block_sizes = self.write_data(path, *size)?;
+                inode_header(&mut inode, BASIC_FILE, *mode, *mtime, number);
+                inode.extend_from_slice(&blocks_start.to_le_bytes());
+                inode.extend_from_slice(&u32::MAX.to_le_bytes());
+                inode.extend_from_slice(&0u32.to_le_bytes());
+                inode.extend_from_slice(&file_size.to_le_bytes());
+                for block_size in block_sizes {
+                    inode.extend_from_slice(&block_size.to_le_bytes());
+                }
+            }
+            Node::Symlink {
+                mode,
+                mtime,
+                target,
+            } => {
+                let target = path_bytes(target);
+                inode_header(&mut inode, BASIC_SYMLINK, *mode, *mtime, number);
+                inode.extend_from_slice(&1u32.to_le_bytes());
+                inode.extend_from_slice(&(target.len() as u32).to_le_bytes());
+                inode.extend_from_slice(&target);
+            }
+        }
+        let (block_start, offset) = self.inodes.position();
+        self.inodes.write(&inode)?;
+        Ok((((block_start as u64) << 16) | offset as u64, number))
+    }
+
+    /// Writes the file contents as individually compressed blocks and returns
+    /// the block size list of the inode.
+    fn write_data(&mut self, path: &Path, size: u64) -> Result<Vec<u32>> {
+        let mut f = File::open(path)?;
+        let mut block_sizes = vec![];
+        let mut remaining = size;
+        let mut buf = vec![0; self.block_size as usize];
+        while remaining > 0 {
+            let len = std::cmp::min(remaining, self.block_size as u64) as usize;
+            f.read_exact(&mut buf[..len])?;
+            let block = &buf[..len];
+            let compressed = compress(block, self.level)?;
+            if compressed.len() < block.len() {
+                self.out.write_all(&compressed)?;
+                self.pos += compressed.len() as u64;
+                block_sizes.push(compressed.len() as u32);
+            } else {
+                self.out.write_all(block)?;
+                self.pos += block.len() as u64;
+                block_sizes.push(block.len() as u32 | DATA_UNCOMPRESSED);
+            }
+            remaining -= len as u64;
+        }
+        Ok(block_sizes)
+    }
+
+    fn finish(mut self, root_ref: u64, inode_count: u32) -> Result<()> {
+        let inode_table_start = self.pos;
+        let inodes = std::mem::replace(&mut self.inodes, MetadataWriter::new(self.level));
+        self.append(&inodes.finish()?)?;
+
+        let directory_table_start = self.pos;
+        let dirs = std::mem::replace(&mut self.dirs, MetadataWriter::new(self.level));
+        self.append(&dirs.finish()?)?;
+
+        // all files are root owned, so the id table contains a single entry.
+        let fragment_table_start = self.pos;
+        let id_block_start = self.pos;
+        let mut ids = MetadataWriter::new(self.level);
+        ids.write(&0u32.to_le_bytes())?;
+        self.append(&ids.finish()?)?;
+        let id_table_start = self.pos;
+        self.append(&id_block_start.to_le_bytes())?;
+
+        let bytes_used = self.pos;
+        let padding = (4096 - bytes_used % 4096) % 4096;
+        self.out.write_all(&vec![0; padding as usize])?;
+
+        let modification_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as u32;
+        let mut sb = Vec::with_capacity(SUPERBLOCK_SIZE as usize);
+        sb.extend_from_slice(&MAGIC.to_le_bytes());
+        sb.extend_from_slice(&inode_count.to_le_bytes());
+        sb.extend_from_slice(&modification_time.to_le_bytes());
+        sb.extend_from_slice(&self.block_size.to_le_bytes());
+        sb.extend_from_slice(&0u32.to_le_bytes());
+        sb.extend_from_slice(&COMPRESSION_GZIP.to_le_bytes());
+        sb.extend_from_slice(&(self.block_size.trailing_zeros() as u16).to_le_bytes());
+        sb.extend_from_slice(&(FLAG_NO_FRAGMENTS | FLAG_NO_XATTRS).to_le_bytes());
+        sb.extend_from_slice(&1u16.to_le_bytes());
+        sb.extend_from_slice(&4u16.to_le_bytes());
+        sb.extend_from_slice(&0u16.to_le_bytes());
+        sb.extend_from_slice(&root_ref.to_le_bytes());
+        sb.extend_from_slice(&bytes_used.to_le_bytes());
+        sb.extend_from_slice(&id_table_start.to_le_bytes());
+        sb.extend_from_slice(&INVALID.to_le_bytes());
+        sb.extend_from_slice(&inode_table_start.to_le_bytes());
+        sb.extend_from_slice(&directory_table_start.to_le_bytes());
+        sb.extend_from_slice(&fragment_table_start.to_le_bytes());
+        sb.extend_from_slice(&INVALID.to_le_bytes());
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&sb)?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.out.write_all(data)?;
+        self.pos += data.len() as u64;
+        Ok(())
+    }
+}
+
+fn inode_header(inode: &mut Vec<u8>, inode_type: u16, mode: u16, mtime: u32, number: u32) {
+    inode.extend_from_slice(&inode_type.to_le_bytes());
+    inode.extend_from_slice(&mode.to_le_bytes());
+    // uid and gid index into the id table
+    inode.extend_from_slice(&0u16.to_le_bytes());
+    inode.extend_from_slice(&0u16.to_le_bytes());
+    inode.extend_from_slice(&mtime.to_le_bytes());
+    inode.extend_from_slice(&number.to_le_bytes());
+}
+
+/// Encodes the sorted directory `entries` of `(name, inode type, inode ref, inode number)`.
+/// Entries are grouped under headers which share the inode metadata block and a
+/// base inode number.
+fn dir_listing(entries: &[(Vec<u8>, u16, u64, u32)]) -> Result<Vec<u8>> {
+    let mut listing = vec![];
+    let mut i = 0;
+    while i < entries.len() {
+        let start = (entries[i].2 >> 16) as u32;
+        let base = entries[i].3;
+        let run = entries[i..]
+            .iter()
+            .take(256)
+            .take_while(|(_, _, inode_ref, number)| {
+                (inode_ref >> 16) as u32 == start
+                    && i16::try_from(*number as i64 - base as i64).is_ok()
+            })
+            .count();
+        listing.extend_from_slice(&(run as u32 - 1).to_le_bytes());
+        listing.extend_from_slice(&start.to_le_bytes());
+        listing.extend_from_slice(&base.to_le_bytes());
+        for (name, inode_type, inode_ref, number) in &entries[i..i + run] {
+            anyhow::ensure!(
+                !name.is_empty() && name.len() <= 256,
+                "invalid file name length"
+            );
+            listing.extend_from_slice(&(*inode_ref as u16).to_le_bytes());
+            listing.extend_from_slice(&((*number as i64 - base as i64) as i16).to_le_bytes());
+            listing.extend_from_slice(&inode_type.to_le_bytes());
+            listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+            listing.extend_from_slice(name);
+        }
+        i += run;
+    }
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::collections::BTreeMap;
+    use std::process::Command;
+
+    const BLOCK_SIZE: u32 = 4096;
+
+    fn u16_at(b: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes(b[pos..pos + 2].try_into().unwrap())
+    }
+
+    fn u32_at(b: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(b[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn u64_at(b: &[u8], pos: usize) -> u64 {
+        u64::from_le_bytes(b[pos..pos + 8].try_into().unwrap())
+    }
+
+    /// Decompresses a metadata table, returning its contents and the offset
+    /// of each block in them keyed by its position in the table.
+    fn metadata(image: &[u8], start: u64, end: u64) -> (Vec<u8>, BTreeMap<u64, usize>) {
+        let mut data = vec![];
+        let mut blocks = BTreeMap::new();
+        let mut pos = start;
+        while pos < end {
+            blocks.insert(pos - start, data.len());
+            let header = u16_at(image, pos as usize);
+            let len = (header & !METADATA_UNCOMPRESSED) as usize;
+            let block = &image[pos as usize + 2..pos as usize + 2 + len];
+            if header & METADATA_UNCOMPRESSED != 0 {
+                data.extend_from_slice(block);
+            } else {
+                ZlibDecoder::new(block).read_to_end(&mut data).unwrap();
+            }
+            pos += 2 + len as u64;
+        }
+        (data, blocks)
+    }
+
+    /// Reads the image back into a map of paths to file contents, directories
+    /// are listed with a trailing `/` and symlinks as `-> target`.
+    struct Reader {
+        image: Vec<u8>,
+        inodes: (Vec<u8>, BTreeMap<u64, usize>),
+        dirs: (Vec<u8>, BTreeMap<u64, usize>),
+        numbers: Vec<u32>,
+    }
+
+    impl Reader {
+        fn new(image: Vec<u8>) -> Self {
+            let inode_table = u64_at(&image, 64);
+            let dir_table = u64_at(&image, 72);
+            let fragment_table = u64_at(&image, 80);
+            let inodes = metadata(&image, inode_table, dir_table);
+            let dirs = metadata(&image, dir_table, fragment_table);
+            Self {
+                image,
+                inodes,
+                dirs,
+                numbers: vec![],
+            }
+        }
+
+        fn inode(&self, inode_ref: u64) -> &[u8] {
+            let (data, blocks) = &self.inodes;
+            &data[blocks[&(inode_ref >> 16)] + (inode_ref & 0xffff) as usize..]
+        }
+
+        fn walk(&mut self, inode_ref: u64, path: &str, tree: &mut BTreeMap<String, Vec<u8>>) {
+            let inode = self.inode(inode_ref).to_vec();
+            self.numbers.push(u32_at(&inode, 12));
+            match u16_at(&inode, 0) {
+                BASIC_DIR => {
+                    tree.insert(format!("{}/", path), vec![]);
+                    let (data, blocks) = &self.dirs;
+                    let start = blocks[&(u32_at(&inode, 16) as u64)] + u16_at(&inode, 26) as usize;
+                    let listing = data[start..start + u16_at(&inode, 24) as usize - 3].to_vec();
+                    let mut pos = 0;
+                    while pos < listing.len() {
+                        let count = u32_at(&listing, pos) + 1;
+                        let block = u32_at(&listing, pos + 4) as u64;
+                        pos += 12;
+                        for _ in 0..count {
+                            let offset = u16_at(&listing, pos) as u64;
+                            let name_len = u16_at(&listing, pos + 6) as usize + 1;
+                            let name = std::str::from_utf8(&listing[pos + 8..pos + 8 + name_len]);
+                            let name = format!("{}/{}", path, name.unwrap());
+                            pos += 8 + name_len;
+                            self.walk(block << 16 | offset, &name, tree);
+                        }
+                    }
+                }
+                BASIC_FILE => {
+                    let mut pos = u32_at(&inode, 16) as usize;
+                    let size = u32_at(&inode, 28) as usize;
+                    let mut contents = vec![];
+                    let mut block_sizes = inode[32..].chunks(4);
+                    while contents.len() < size {
+                        let block_size = u32_at(block_sizes.next().unwrap(), 0);
+                        let len = (block_size & !DATA_UNCOMPRESSED) as usize;
+                        let block = &self.image[pos..pos + len];
+                        if block_size & DATA_UNCOMPRESSED != 0 {
+                            contents.extend_from_slice(block);
+                        } else {
+                            ZlibDecoder::new(block).read_to_end(&mut contents).unwrap();
+                        }
+                        pos += len;
+                    }
+                    assert_eq!(contents.len(), size);
+                    tree.insert(path.into(), contents);
+                }
+                BASIC_SYMLINK => {
+                    let len = u32_at(&inode, 20) as usize;
+                    let target = inode[24..24 + len].to_vec();
+                    tree.insert(format!("{} ->", path), target);
+                }
+                ty => panic!("unexpected inode type {}", ty),
+            }
+        }
+    }
+
+    fn write_tree(name: &str) -> (PathBuf, PathBuf, Vec<u8>) {
+        let dir = std::env::temp_dir().join(format!("xbuild-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let source = dir.join("AppDir");
+        std::fs::create_dir_all(source.join("usr").join("lib")).unwrap();
+        std::fs::write(source.join("empty"), []).unwrap();
+        // compressible blocks followed by a block that doesn't compress
+        let mut big = b"squashfs ".repeat(1000);
+        let mut x = 1u32;
+        big.extend((0..BLOCK_SIZE).map(|_| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 16) as u8
+        }));
+        std::fs::write(source.join("usr").join("lib").join("big"), &big).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("usr/lib/big", source.join("link")).unwrap();
+        (dir, source, big)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (dir, source, big) = write_tree("squashfs");
+        let out = dir.join("image.squashfs");
+        write(&source, &out, BLOCK_SIZE, 9).unwrap();
+        let image = std::fs::read(&out).unwrap();
+
+        let inode_count = if cfg!(unix) { 6 } else { 5 };
+        assert_eq!(u32_at(&image, 0), MAGIC);
+        assert_eq!(u32_at(&image, 4), inode_count);
+        assert_eq!(u32_at(&image, 12), BLOCK_SIZE);
+        assert_eq!(u32_at(&image, 16), 0);
+        assert_eq!(u16_at(&image, 20), COMPRESSION_GZIP);
+        assert_eq!(u16_at(&image, 22), 12);
+        assert_eq!(u16_at(&image, 24), FLAG_NO_FRAGMENTS | FLAG_NO_XATTRS);
+        assert_eq!(u16_at(&image, 26), 1);
+        assert_eq!((u16_at(&image, 28), u16_at(&image, 30)), (4, 0));
+        let bytes_used = u64_at(&image, 40);
+        assert!(bytes_used <= image.len() as u64);
+        assert_eq!(image.len() % 4096, 0);
+        let id_table = u64_at(&image, 48) as usize;
+        let id_block = u64_at(&image, id_table) as usize;
+        let (ids, _) = metadata(&image, id_block as u64, id_table as u64);
+        assert_eq!(ids, 0u32.to_le_bytes());
+
+        let mut reader = Reader::new(image);
+        let mut tree = BTreeMap::new();
+        reader.walk(u64_at(&reader.image, 32), "", &mut tree);
+        let mut expected = vec!["/", "/empty", "/usr/", "/usr/lib/", "/usr/lib/big"];
+        if cfg!(unix) {
+            expected.insert(2, "/link ->");
+            assert_eq!(tree["/link ->"], b"usr/lib/big");
+        }
+        assert_eq!(tree.keys().collect::<Vec<_>>(), expected);
+        assert!(tree["/empty"].is_empty());
+        assert_eq!(tree["/usr/lib/big"], big);
+        // inodes are numbered 1..=inode_count with the root last
+        reader.numbers.sort_unstable();
+        assert_eq!(reader.numbers, (1..=inode_count).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Lists the image with squashfs-tools when they are installed.
+    #[cfg(unix)]
+    #[test]
+    fn test_unsquashfs() {
+        let (dir, source, _) = write_tree("unsquashfs");
+        let out = dir.join("image.squashfs");
+        write(&source, &out, BLOCK_SIZE, 9).unwrap();
+        let output = Command::new("unsquashfs").arg("-l").arg(&out).output();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let output = match output {
+            Ok(output) => output,
+            Err(_) => return,
+        };
+        assert!(output.status.success(), "{:?}", output);
+        let listing = String::from_utf8(output.stdout).unwrap();
+        let mut paths = listing
+            .lines()
+            .filter_map(|line| line.strip_prefix("squashfs-root"))
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            ["", "/empty", "/link", "/usr", "/usr/lib", "/usr/lib/big"]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Update information read by AppImageUpdate, see
+//! <https://github.com/AppImage/AppImageSpec/blob/master/draft.md#update-information>.
+use anyhow::{Context, Result};
+use std::ops::Range;
+use std::path::Path;
+use std::process::Command;
+
+/// Section of the runtime the update information is stored in.
+const SECTION: &str = ".upd_info";
+
+/// Checks `info` against the transports defined by the spec.
+pub fn validate(info: &str) -> Result<()> {
+    let fields = info.split('|').collect::<Vec<_>>();
+    let (transport, fields) = fields.split_first().unwrap();
+    let expected = match *transport {
+        "zsync" => {
+            anyhow::ensure!(fields.len() == 1, "expected `zsync|<url>`, got `{}`", info);
+            anyhow::ensure!(
+                fields[0].starts_with("https://") || fields[0].starts_with("http://"),
+                "zsync update url must be http(s), got `{}`",
+                fields[0]
+            );
+            1
+        }
+        "gh-releases-zsync" => 4,
+        "pling-v1-zsync" => 2,
+        _ => anyhow::bail!(
+            "unknown update transport `{}`, expected zsync, gh-releases-zsync or pling-v1-zsync",
+            transport
+        ),
+    };
+    anyhow::ensure!(
+        fields.len() == expected && fields.iter().all(|field| !field.is_empty()),
+        "{} update info requires {} non-empty fields, got `{}`",
+        transport,
+        expected,
+        info
+    );
+    Ok(())
+}
+
+/// File range of the section called `name` in a 64-bit little endian ELF.
+fn elf_section(elf: &[u8], name: &str) -> Result<Range<usize>> {
+    let u16_at = |pos: usize| -> Result<usize> {
+        let bytes = elf.get(pos..pos + 2).context("truncated elf")?;
+        Ok(u16::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let u32_at = |pos: usize| -> Result<usize> {
+        let bytes = elf.get(pos..pos + 4).context("truncated elf")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let u64_at = |pos: usize| -> Result<usize> {
+        let bytes = elf.get(pos..pos + 8).context("truncated elf")?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    anyhow::ensure!(
+        elf.starts_with(b"\x7fELF\x02\x01"),
+        "runtime is not a 64-bit little endian elf"
+    );
+    let shoff = u64_at(0x28)?;
+    let shentsize = u16_at(0x3a)?;
+    let shnum = u16_at(0x3c)?;
+    let shstrndx = u16_at(0x3e)?;
+    let header = |index: usize| shoff + index * shentsize;
+    let strtab = u64_at(header(shstrndx) + 0x18)?;
+    for index in 0..shnum {
+        let name_offset = strtab + u32_at(header(index))?;
+        let section_name = elf
+            .get(name_offset..)
+            .and_then(|bytes| bytes.split(|b| *b == 0).next())
+            .context("truncated elf")?;
+        if section_name == name.as_bytes() {
+            let offset = u64_at(header(index) + 0x18)?;
+            let size = u64_at(header(index) + 0x20)?;
+            anyhow::ensure!(offset + size <= elf.len(), "truncated elf");
+            return Ok(offset..offset + size);
+        }
+    }
+    anyhow::bail!("runtime has no {} section", name)
+}
+
+/// Writes `info` into the update information section of `runtime`.
+pub fn embed(runtime: &mut [u8], info: &str) -> Result<()> {
+    let range = elf_section(runtime, SECTION)?;
+    // the section is NUL terminated
+    anyhow::ensure!(
+        info.len() < range.len(),
+        "update info must be shorter than {} bytes",
+        range.len()
+    );
+    let section = &mut runtime[range];
+    section.fill(0);
+    section[..info.len()].copy_from_slice(info.as_bytes());
+    Ok(())
+}
+
+/// Runs `zsyncmake` to write `<appimage>.zsync` next to the AppImage.
+pub fn zsyncmake(appimage: &Path) -> Result<()> {
+    let file_name = appimage.file_name().context("invalid path")?;
+    let mut zsync = appimage.as_os_str().to_owned();
+    zsync.push(".zsync");
+    let status = Command::new("zsyncmake")
+        .arg("-u")
+        .arg(file_name)
+        .arg("-o")
+        .arg(&zsync)
+        .arg(appimage)
+        .status()
+        .context("zsyncmake not found")?;
+    anyhow::ensure!(
+        status.success(),
+        "zsyncmake failed with exit code {:?}",
+        status
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        validate("zsync|https://example.com/app-latest.AppImage.zsync").unwrap();
+        validate("gh-releases-zsync|user|app|latest|app-*x86_64.AppImage.zsync").unwrap();
+        validate("pling-v1-zsync|1234|app-*.AppImage.zsync").unwrap();
+        assert!(validate("zsync|ftp://example.com/app.zsync").is_err());
+        assert!(validate("gh-releases-zsync|user|app|latest").is_err());
+        assert!(validate("bintray-zsync|user|repo|app|file").is_err());
+    }
+
+    #[test]
+    fn test_embed() {
+        let mut runtime = crate::RUNTIME.to_vec();
+        let info = "zsync|https://example.com/app-latest.AppImage.zsync";
+        embed(&mut runtime, info).unwrap();
+        let range = elf_section(&runtime, SECTION).unwrap();
+        assert_eq!(range.len(), 1024);
+        assert!(runtime[range.clone()].starts_with(info.as_bytes()));
+        assert_eq!(runtime[range.start + info.len()], 0);
+        assert!(embed(&mut runtime, &"x".repeat(1024)).is_err());
+    }
+}
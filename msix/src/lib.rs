@@ -1,13 +1,17 @@
 use crate::block_map::BlockMapBuilder;
 use crate::content_types::ContentTypesBuilder;
 use crate::p7x::Digests;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Serialize;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, Cursor, Read, Seek};
 use std::path::{Path, PathBuf};
-use xcommon::{Scaler, ScalerOptsBuilder, Signer, Zip, ZipFileOptions, ZipInfo};
+use std::process::Command;
+use xcommon::{
+    Exclude, Scaler, ScalerOptsBuilder, Signer, Zip, ZipCompression, ZipFileOptions, ZipInfo,
+};
 use zip::ZipArchive;
 
 mod block_map;
@@ -20,6 +24,13 @@ pub use crate::manifest::AppxManifest;
 
 const DEBUG_PEM: &str = include_str!("../assets/debug.pem");
 
+/// Files added by [`Msix::sign`].
+const SIGNATURE_FILES: [&str; 3] = [
+    "[Content_Types].xml",
+    "AppxBlockMap.xml",
+    "AppxSignature.p7x",
+];
+
 const IMAGES: [(&str, (u32, u32), f32); 8] = [
     ("SmallTile", (71, 71), 0.34),
     ("Square150x150Logo", (150, 150), 0.34),
@@ -35,37 +46,79 @@ pub struct Msix {
     manifest: AppxManifest,
     path: PathBuf,
     zip: Zip,
-    compress: bool,
+    compression: ZipCompression,
+    jobs: usize,
+    images: Vec<(PathBuf, Vec<u8>)>,
+    strings: BTreeMap<String, BTreeMap<String, String>>,
 }
 
 impl Msix {
-    pub fn new(path: PathBuf, manifest: AppxManifest, compress: bool) -> Result<Self> {
+    pub fn new(path: PathBuf, manifest: AppxManifest, compression: ZipCompression) -> Result<Self> {
         Ok(Self {
             manifest,
-            zip: Zip::new(&path, compress)?,
+            zip: Zip::new(&path, compression)?,
             path,
-            compress,
+            compression,
+            jobs: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            images: vec![],
+            strings: BTreeMap::new(),
         })
     }
 
+    /// Files skipped by [`Msix::add_directory`].
+    pub fn set_exclude(&mut self, exclude: Exclude) {
+        self.zip.set_exclude(exclude);
+    }
+
+    /// Number of threads encoding icons, defaults to the number of CPUs.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
     pub fn add_icon(&mut self, path: &Path) -> Result<()> {
         let mut scaler = Scaler::open(path)?;
         scaler.optimize();
         let images = Path::new("Images");
-        let mut buf = vec![];
+        let mut jobs = vec![];
         for (base_name, (width, height), padding) in IMAGES {
             for scale in [1.0, 1.25, 1.5, 2.0, 4.0] {
-                buf.clear();
                 let opts = ScalerOptsBuilder::new(width, height)
                     .scale(scale)
                     .padding(padding)
                     .build();
-                scaler.write(&mut Cursor::new(&mut buf), opts)?;
                 let name = format!("{}.scale-{}.png", base_name, (scale * 100.0) as u32);
-                self.zip
-                    .create_file(&images.join(name), ZipFileOptions::Unaligned, &buf)?;
+                jobs.push((name, opts));
             }
         }
+        // the encodes are independent, only writing them to the zip is ordered
+        let chunk_size = jobs.len().div_ceil(self.jobs);
+        let scaler = &scaler;
+        let pngs = std::thread::scope(|s| {
+            let handles = jobs
+                .chunks(chunk_size)
+                .map(|jobs| {
+                    s.spawn(move || {
+                        jobs.iter()
+                            .map(|(_, opts)| {
+                                let mut buf = vec![];
+                                scaler.write(&mut Cursor::new(&mut buf), *opts)?;
+                                Ok(buf)
+                            })
+                            .collect::<Result<Vec<_>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Result<Vec<_>>>()
+        })?;
+        for ((name, _), png) in jobs.iter().zip(pngs.into_iter().flatten()) {
+            let path = images.join(name);
+            self.zip
+                .create_file(&path, ZipFileOptions::Unaligned, &png)?;
+            self.images.push((path, png));
+        }
         Ok(())
     }
 
@@ -73,6 +126,79 @@ impl Msix {
         self.zip.add_file(source, dest, opts)
     }
 
+    /// Indexes localized strings into `resources.pri` when the msix is
+    /// finished, which resolves the `ms-resource:<name>` references of the
+    /// manifest. `strings` maps languages to resource names and values. The
+    /// icons are indexed too, so their scale qualifiers keep resolving.
+    /// Requires `makepri` of the windows sdk and therefore a windows host.
+    pub fn add_strings(
+        &mut self,
+        strings: &BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            cfg!(target_os = "windows"),
+            "localized msix strings are indexed with makepri of the windows sdk, which requires a windows host"
+        );
+        which_makepri()?;
+        self.strings = strings.clone();
+        Ok(())
+    }
+
+    /// Writes `resources.pri` indexing the strings and icons.
+    fn add_pri(&mut self) -> Result<()> {
+        let makepri = which_makepri()?;
+        let dir = self.path.with_extension("pri");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        let root = dir.join("root");
+        for (path, png) in &self.images {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, png)?;
+        }
+        for (language, strings) in &self.strings {
+            let lang_dir = root.join("Strings").join(language);
+            std::fs::create_dir_all(&lang_dir)?;
+            std::fs::write(lang_dir.join("Resources.resw"), resw(strings))?;
+        }
+        let default_language = self
+            .manifest
+            .resources
+            .resource
+            .first()
+            .map(|resource| resource.language.as_str())
+            .unwrap_or("en-US");
+        let config = dir.join("priconfig.xml");
+        let manifest = dir.join("AppxManifest.xml");
+        std::fs::write(&manifest, to_xml(&self.manifest, true))?;
+        let pri = dir.join("resources.pri");
+        run_makepri(
+            Command::new(&makepri)
+                .arg("createconfig")
+                .arg("/cf")
+                .arg(&config)
+                .arg("/dq")
+                .arg(default_language)
+                .arg("/o"),
+        )?;
+        run_makepri(
+            Command::new(&makepri)
+                .arg("new")
+                .arg("/pr")
+                .arg(&root)
+                .arg("/cf")
+                .arg(&config)
+                .arg("/mn")
+                .arg(&manifest)
+                .arg("/of")
+                .arg(&pri)
+                .arg("/o"),
+        )?;
+        self.zip
+            .add_file(&pri, "resources.pri".as_ref(), ZipFileOptions::Compressed)
+    }
+
     pub fn add_directory(
         &mut self,
         source: &Path,
@@ -83,16 +209,38 @@ impl Msix {
     }
 
     pub fn finish(mut self, signer: Option<Signer>) -> Result<()> {
+        self.manifest.sort_capabilities();
+        self.manifest.validate()?;
+        if !self.strings.is_empty() {
+            self.add_pri()?;
+        }
         self.zip.create_file(
             "AppxManifest.xml".as_ref(),
             ZipFileOptions::Compressed,
             &to_xml(&self.manifest, true),
         )?;
         self.zip.finish()?;
-        Self::sign(&self.path, signer, self.compress)
+        Self::sign(&self.path, signer, self.compression)
     }
 
-    pub fn sign(path: &Path, signer: Option<Signer>, compress: bool) -> Result<()> {
+    /// Replaces the signature of a signed msix. The block map, content types
+    /// and signature are regenerated for the remaining files.
+    pub fn resign(path: &Path, signer: Option<Signer>, compression: ZipCompression) -> Result<()> {
+        let unsigned = path.with_extension("unsigned.msix");
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        let mut zip = Zip::new(&unsigned, compression)?;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !SIGNATURE_FILES.contains(&file.name()) {
+                zip.add_zip_file(file)?;
+            }
+        }
+        zip.finish()?;
+        std::fs::rename(&unsigned, path)?;
+        Self::sign(path, signer, compression)
+    }
+
+    pub fn sign(path: &Path, signer: Option<Signer>, compression: ZipCompression) -> Result<()> {
         let signer = signer
             .map(Ok)
             .unwrap_or_else(|| Signer::new(DEBUG_PEM))
@@ -111,7 +259,7 @@ impl Msix {
         let axct = Sha256::digest(&content_types);
         let block_map = to_xml(&block_map.finish(), false);
         let axbm = Sha256::digest(&block_map);
-        let mut zip = Zip::append(path, compress)?;
+        let mut zip = Zip::append(path, compression)?;
         zip.create_file(
             "[Content_Types].xml".as_ref(),
             ZipFileOptions::Compressed,
@@ -144,8 +292,8 @@ impl Msix {
         };
 
         // sign zip
-        let sig = p7x::p7x(&signer, &digests);
-        let mut zip = Zip::append(path, compress)?;
+        let sig = p7x::p7x(&signer, &digests)?;
+        let mut zip = Zip::append(path, compression)?;
         zip.create_file(
             "AppxSignature.p7x".as_ref(),
             ZipFileOptions::Compressed,
@@ -156,6 +304,54 @@ impl Msix {
     }
 }
 
+fn which_makepri() -> Result<PathBuf> {
+    let paths = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&paths)
+        .flat_map(|dir| [dir.join("makepri.exe"), dir.join("makepri")])
+        .find(|path| path.is_file())
+        .context("makepri not found, add the windows sdk bin directory to PATH")
+}
+
+fn run_makepri(cmd: &mut Command) -> Result<()> {
+    let output = cmd
+        .output()
+        .with_context(|| format!("failed to run {:?}", cmd))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{:?} failed: {}",
+        cmd,
+        String::from_utf8_lossy(&output.stdout).trim()
+    );
+    Ok(())
+}
+
+/// `Resources.resw` with the string resources of one language.
+fn resw(strings: &BTreeMap<String, String>) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let mut resw = String::from(concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#,
+        "\n<root>\n",
+        r#"  <resheader name="resmimetype"><value>text/microsoft-resx</value></resheader>"#,
+        "\n",
+        r#"  <resheader name="version"><value>2.0</value></resheader>"#,
+        "\n",
+    ));
+    for (name, value) in strings {
+        resw.push_str(&format!(
+            "  <data name=\"{}\" xml:space=\"preserve\"><value>{}</value></data>\n",
+            escape(name),
+            escape(value)
+        ));
+    }
+    resw.push_str("</root>\n");
+    resw
+}
+
 fn to_xml<T: Serialize>(xml: &T, standalone: bool) -> Vec<u8> {
     let mut buf = vec![];
     let standalone = if standalone { "yes" } else { "no" };
@@ -169,3 +365,34 @@ fn to_xml<T: Serialize>(xml: &T, standalone: bool) -> Vec<u8> {
     quick_xml::se::to_writer(&mut buf, xml).unwrap();
     buf
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resw() {
+        let strings = BTreeMap::from([
+            ("DisplayName".to_string(), "Mon & appli".to_string()),
+            ("ShortName".to_string(), "<appli>".to_string()),
+        ]);
+        let resw = resw(&strings);
+        assert!(resw.contains(
+            r#"<data name="DisplayName" xml:space="preserve"><value>Mon &amp; appli</value></data>"#
+        ));
+        assert!(resw.contains("<value>&lt;appli&gt;</value>"));
+        assert!(resw.ends_with("</root>\n"));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_add_strings_requires_windows() {
+        let path = std::env::temp_dir().join(format!("xbuild-msix-{}.msix", std::process::id()));
+        let mut msix = Msix::new(path.clone(), Default::default(), Default::default()).unwrap();
+        let strings = BTreeMap::from([("en-US".to_string(), BTreeMap::new())]);
+        let err = msix.add_strings(&strings).unwrap_err();
+        assert!(err.to_string().contains("requires a windows host"));
+        drop(msix);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
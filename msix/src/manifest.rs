@@ -3,6 +3,7 @@ use serde::ser::{SerializeTuple, Serializer};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename = "Package")]
 pub struct AppxManifest {
     #[serde(rename(serialize = "xmlns"))]
@@ -14,6 +15,9 @@ pub struct AppxManifest {
     #[serde(rename(serialize = "xmlns:rescap"))]
     #[serde(default = "default_rescap_namespace")]
     ns_rescap: String,
+    #[serde(rename(serialize = "IgnorableNamespaces"))]
+    #[serde(default = "default_ignorable_namespaces")]
+    ignorable_namespaces: String,
     #[serde(rename(serialize = "Identity"))]
     pub identity: Identity,
     #[serde(rename(serialize = "Properties"))]
@@ -35,6 +39,7 @@ impl Default for AppxManifest {
             ns: default_namespace(),
             ns_uap: default_uap_namespace(),
             ns_rescap: default_rescap_namespace(),
+            ignorable_namespaces: default_ignorable_namespaces(),
             identity: Default::default(),
             properties: Default::default(),
             resources: Default::default(),
@@ -45,25 +50,110 @@ impl Default for AppxManifest {
     }
 }
 
+/// Entry point of desktop apps that run outside of the app container.
+pub const FULL_TRUST_ENTRY_POINT: &str = "Windows.FullTrustApplication";
+
+impl AppxManifest {
+    /// Orders the capabilities the way the schema expects them: general
+    /// capabilities first, followed by restricted and device capabilities.
+    pub fn sort_capabilities(&mut self) {
+        self.capabilities
+            .sort_by_key(|capability| match capability {
+                Capability::Capability { .. } => 0,
+                Capability::Uap { .. } => 1,
+                Capability::Restricted { .. } => 2,
+                Capability::Device { .. } => 3,
+            });
+    }
+
+    /// Checks the parts of the manifest that Windows only reports when
+    /// installing the package.
+    pub fn validate(&self) -> Result<()> {
+        anyhow::ensure!(
+            !self.dependencies.target_device_family.is_empty(),
+            "msix requires at least one target device family"
+        );
+        for family in &self.dependencies.target_device_family {
+            let min = parse_version(&family.min_version)?;
+            let max = parse_version(&family.max_version)?;
+            anyhow::ensure!(
+                min <= max,
+                "{} min version {} is newer than the max version tested {}",
+                family.name,
+                family.min_version,
+                family.max_version
+            );
+        }
+        anyhow::ensure!(
+            !self.applications.application.is_empty(),
+            "msix requires at least one application"
+        );
+        for app in &self.applications.application {
+            anyhow::ensure!(
+                app.id.is_some() && app.executable.is_some() && app.entry_point.is_some(),
+                "msix application requires an id, executable and entry point"
+            );
+        }
+        for capability in &self.capabilities {
+            let expected = Capability::new(capability.name());
+            anyhow::ensure!(
+                std::mem::discriminant(capability) == std::mem::discriminant(&expected),
+                "capability `{}` must be declared as {}",
+                capability.name(),
+                expected.kind()
+            );
+        }
+        let full_trust = self
+            .applications
+            .application
+            .iter()
+            .any(|app| app.entry_point.as_deref() == Some(FULL_TRUST_ENTRY_POINT));
+        let run_full_trust = self
+            .capabilities
+            .iter()
+            .any(|capability| capability.name() == "runFullTrust");
+        anyhow::ensure!(
+            !full_trust || run_full_trust,
+            "entry point {} requires the runFullTrust capability",
+            FULL_TRUST_ENTRY_POINT
+        );
+        Ok(())
+    }
+}
+
+fn parse_version(version: &str) -> Result<Vec<u32>> {
+    version
+        .split('.')
+        .map(|part| {
+            part.parse()
+                .map_err(|_| anyhow::anyhow!("invalid version `{}`", version))
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Applications {
     #[serde(rename(serialize = "Application"))]
     pub application: Vec<Application>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Resources {
     #[serde(rename(serialize = "Resource"))]
     pub resource: Vec<Resource>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Dependencies {
     #[serde(rename(serialize = "TargetDeviceFamily"))]
     pub target_device_family: Vec<TargetDeviceFamily>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Identity {
     #[serde(rename(serialize = "Name"))]
     pub name: Option<String>,
@@ -76,6 +166,7 @@ pub struct Identity {
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Properties {
     #[serde(rename(serialize = "DisplayName"))]
     #[serde(serialize_with = "serialize_element")]
@@ -101,12 +192,14 @@ where
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Resource {
     #[serde(rename(serialize = "Language"))]
     pub language: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct TargetDeviceFamily {
     #[serde(rename(serialize = "Name"))]
     pub name: String,
@@ -127,6 +220,7 @@ impl Default for TargetDeviceFamily {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Capability {
     #[serde(rename(deserialize = "capability"))]
     #[serde(rename(serialize = "Capability"))]
@@ -134,6 +228,12 @@ pub enum Capability {
         #[serde(rename(serialize = "Name"))]
         name: String,
     },
+    #[serde(rename(deserialize = "uap"))]
+    #[serde(rename(serialize = "uap:Capability"))]
+    Uap {
+        #[serde(rename(serialize = "Name"))]
+        name: String,
+    },
     #[serde(rename(deserialize = "restricted"))]
     #[serde(rename(serialize = "rescap:Capability"))]
     Restricted {
@@ -148,7 +248,82 @@ pub enum Capability {
     },
 }
 
+/// Capabilities defined in the foundation namespace, all other general
+/// capabilities live in the uap namespace.
+const FOUNDATION_CAPABILITIES: &[&str] = &[
+    "internetClient",
+    "internetClientServer",
+    "privateNetworkClientServer",
+    "allJoyn",
+    "codeGeneration",
+];
+
+const RESTRICTED_CAPABILITIES: &[&str] = &[
+    "runFullTrust",
+    "allowElevation",
+    "broadFileSystemAccess",
+    "confirmAppClose",
+    "extendedBackgroundTaskTime",
+    "extendedExecutionUnconstrained",
+    "inputInjectionBrokered",
+    "localSystemServices",
+    "packageManagement",
+    "packageQuery",
+    "unvirtualizedResources",
+];
+
+const DEVICE_CAPABILITIES: &[&str] = &[
+    "bluetooth",
+    "gazeInput",
+    "humaninterfacedevice",
+    "location",
+    "lowLevel",
+    "microphone",
+    "pointOfService",
+    "proximity",
+    "radios",
+    "serialcommunication",
+    "usb",
+    "webcam",
+    "wiFiControl",
+];
+
+impl Capability {
+    /// Declares the capability `name` in the namespace it is defined in.
+    pub fn new(name: &str) -> Self {
+        let name = name.to_string();
+        if FOUNDATION_CAPABILITIES.contains(&name.as_str()) {
+            Self::Capability { name }
+        } else if RESTRICTED_CAPABILITIES.contains(&name.as_str()) {
+            Self::Restricted { name }
+        } else if DEVICE_CAPABILITIES.contains(&name.as_str()) {
+            Self::Device { name }
+        } else {
+            Self::Uap { name }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Capability { name }
+            | Self::Uap { name }
+            | Self::Restricted { name }
+            | Self::Device { name } => name,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Capability { .. } => "a capability",
+            Self::Uap { .. } => "a uap capability",
+            Self::Restricted { .. } => "a restricted capability",
+            Self::Device { .. } => "a device capability",
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Application {
     #[serde(rename(serialize = "Id"))]
     pub id: Option<String>,
@@ -161,6 +336,7 @@ pub struct Application {
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct VisualElements {
     #[serde(rename(serialize = "BackgroundColor"))]
     pub background_color: Option<String>,
@@ -181,6 +357,7 @@ pub struct VisualElements {
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct DefaultTile {
     #[serde(rename(serialize = "ShortName"))]
     pub short_name: Option<String>,
@@ -195,24 +372,28 @@ pub struct DefaultTile {
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ShowNameOnTiles {
     #[serde(rename(serialize = "uap:ShowOn"))]
     pub show_on: Vec<ShowOn>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ShowOn {
     #[serde(rename(serialize = "Tile"))]
     pub tile: String,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SplashScreen {
     #[serde(rename(serialize = "Image"))]
     pub image: String,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct LockScreen {
     #[serde(rename(serialize = "BadgeLogo"))]
     pub badge_logo: String,
@@ -233,6 +414,10 @@ fn default_rescap_namespace() -> String {
         .to_string()
 }
 
+fn default_ignorable_namespaces() -> String {
+    "uap rescap".to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -327,7 +512,47 @@ mod tests {
             },
             ..Default::default()
         };
+        manifest.validate().unwrap();
         let xml = quick_xml::se::to_string(&manifest).unwrap();
         println!("{}", xml);
     }
+
+    #[test]
+    fn test_validate_capabilities() {
+        let mut manifest = AppxManifest {
+            dependencies: Dependencies {
+                target_device_family: vec![Default::default()],
+            },
+            capabilities: vec![
+                Capability::new("location"),
+                Capability::new("internetClient"),
+            ],
+            applications: Applications {
+                application: vec![Application {
+                    id: Some("app".into()),
+                    executable: Some("app.exe".into()),
+                    entry_point: Some(FULL_TRUST_ENTRY_POINT.into()),
+                    ..Default::default()
+                }],
+            },
+            ..Default::default()
+        };
+        assert!(manifest.validate().is_err());
+        manifest.capabilities.push(Capability::Capability {
+            name: "runFullTrust".into(),
+        });
+        assert!(manifest.validate().is_err());
+        manifest.capabilities.pop();
+        manifest.capabilities.push(Capability::new("runFullTrust"));
+        manifest.sort_capabilities();
+        manifest.validate().unwrap();
+        let names = manifest
+            .capabilities
+            .iter()
+            .map(Capability::name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["internetClient", "runFullTrust", "location"]);
+        manifest.dependencies.target_device_family[0].min_version = "10.0.30000.0".into();
+        assert!(manifest.validate().is_err());
+    }
 }
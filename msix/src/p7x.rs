@@ -27,13 +27,13 @@ pub fn read_p7x(path: &Path) -> Result<SignedData> {
     Ok(data)
 }
 
-pub fn p7x(signer: &Signer, digests: &Digests) -> Vec<u8> {
+pub fn p7x(signer: &Signer, digests: &Digests) -> Result<Vec<u8>> {
     let payload = Payload::encode(digests);
     let encap_content_info = EncapsulatedContentInfo {
         content_type: SPC_INDIRECT_DATA_OBJID.into(),
         content: Any::new(payload),
     };
-    let signed_data = build_pkcs7(signer, encap_content_info);
+    let signed_data = build_pkcs7(signer, encap_content_info)?;
     let content_info = ContentInfo {
         content_type: CONTENT_SIGNED_DATA.into(),
         content: Any::new(rasn::der::encode(&signed_data).unwrap()),
@@ -41,7 +41,7 @@ pub fn p7x(signer: &Signer, digests: &Digests) -> Vec<u8> {
     let mut p7x = vec![];
     p7x.extend_from_slice(&P7X_MAGIC.to_be_bytes());
     p7x.extend(rasn::der::encode(&content_info).unwrap());
-    p7x
+    Ok(p7x)
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
@@ -1,19 +1,25 @@
 use crate::Signer;
+use anyhow::Result;
 use rasn::prelude::*;
 use rasn_cms::pkcs7_compat::{EncapsulatedContentInfo, SignedData};
 use rasn_cms::{AlgorithmIdentifier, IssuerAndSerialNumber, SignerIdentifier, SignerInfo};
 use rasn_pkix::Attribute;
 use sha2::{Digest, Sha256};
 use std::collections::BTreeSet;
+use xcommon::KeyType;
 
 pub const SPC_INDIRECT_DATA_OBJID: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 311, 2, 1, 4]);
 pub const SPC_SP_OPUS_INFO_OBJID: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 311, 2, 1, 12]);
 pub const SPC_SIPINFO_OBJID: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 311, 2, 1, 30]);
+pub const ECDSA_WITH_SHA256_OBJID: ConstOid = ConstOid(&[1, 2, 840, 10045, 4, 3, 2]);
 
 #[allow(clippy::mutable_key_type)]
-pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo) -> SignedData {
+pub fn build_pkcs7(
+    signer: &Signer,
+    encap_content_info: EncapsulatedContentInfo,
+) -> Result<SignedData> {
     let digest = Sha256::digest(&encap_content_info.content.as_bytes()[8..]);
-    let signature = signer.sign(&encap_content_info.content.as_bytes()[8..]);
+    let signature = signer.sign(&encap_content_info.content.as_bytes()[8..])?;
     let cert = signer.cert();
 
     let digest_algorithm = AlgorithmIdentifier {
@@ -54,9 +60,15 @@ pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo)
             });
             signed_attrs
         }),
-        signature_algorithm: AlgorithmIdentifier {
-            algorithm: Oid::ISO_MEMBER_BODY_US_RSADSI_PKCS1.into(),
-            parameters: Some(Any::new(vec![5, 0])),
+        signature_algorithm: match signer.key_type() {
+            KeyType::Rsa => AlgorithmIdentifier {
+                algorithm: Oid::ISO_MEMBER_BODY_US_RSADSI_PKCS1.into(),
+                parameters: Some(Any::new(vec![5, 0])),
+            },
+            KeyType::Ec => AlgorithmIdentifier {
+                algorithm: ECDSA_WITH_SHA256_OBJID.into(),
+                parameters: None,
+            },
         },
         signature: OctetString::from(signature.to_vec()),
         unsigned_attrs: Some({
@@ -64,7 +76,7 @@ pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo)
             SetOf::default()
         }),
     };
-    SignedData {
+    Ok(SignedData {
         version: 1.into(),
         digest_algorithms: {
             let mut digest_algorithms = SetOf::default();
@@ -79,5 +91,5 @@ pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo)
             signer_infos.insert(signer_info);
             signer_infos
         },
-    }
+    })
 }
@@ -0,0 +1,285 @@
+use anyhow::{Context, Result};
+use app_store_connect::{ConnectTokenEncoder, UnifiedApiKey};
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const API_URL: &str = "https://api.appstoreconnect.apple.com/v1";
+
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const FAT_MAGIC: u32 = 0xcafe_babe;
+const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+const LC_SEGMENT_64: u32 = 0x19;
+
+/// Version information of an app bundle accepted by the app store.
+#[derive(Clone, Debug)]
+pub struct AppInfo {
+    pub bundle_identifier: String,
+    pub version: String,
+    pub short_version: String,
+}
+
+/// Checks that an ios app bundle can be submitted to the app store: it needs
+/// a version, a provisioning profile and a signature, and the executable
+/// needs to be a thin arm64 binary without embedded bitcode.
+pub fn validate_app(app: &Path) -> Result<AppInfo> {
+    let info = plist::Value::from_file(app.join("Info.plist"))?;
+    let info = info.as_dictionary().context("invalid Info.plist")?;
+    let field = |key: &str| -> Result<String> {
+        let value = info.get(key).and_then(|value| value.as_string());
+        Ok(value
+            .with_context(|| format!("Info.plist is missing {}", key))?
+            .to_string())
+    };
+    let bundle_identifier = field("CFBundleIdentifier")?;
+    let version = field("CFBundleVersion")?;
+    let short_version = field("CFBundleShortVersionString")?;
+    let executable = field("CFBundleExecutable")?;
+    anyhow::ensure!(
+        app.join("embedded.mobileprovision").exists(),
+        "app store builds require a --provisioning-profile"
+    );
+    anyhow::ensure!(
+        app.join("_CodeSignature").join("CodeResources").exists(),
+        "app store builds require a --pem or --signing-identity"
+    );
+    let bin = std::fs::read(app.join(&executable))?;
+    validate_executable(&bin).with_context(|| format!("invalid executable {}", executable))?;
+    Ok(AppInfo {
+        bundle_identifier,
+        version,
+        short_version,
+    })
+}
+
+fn validate_executable(bin: &[u8]) -> Result<()> {
+    let u32_at = |offset: usize| -> Result<u32> {
+        let bytes = bin
+            .get(offset..offset + 4)
+            .context("truncated mach-o header")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    anyhow::ensure!(
+        u32_at(0)?.swap_bytes() != FAT_MAGIC,
+        "universal binaries are not thinned, only arm64 is accepted"
+    );
+    anyhow::ensure!(u32_at(0)? == MH_MAGIC_64, "not a 64-bit mach-o binary");
+    anyhow::ensure!(
+        u32_at(4)? == CPU_TYPE_ARM64,
+        "only arm64 is accepted by the app store"
+    );
+    let ncmds = u32_at(16)?;
+    let mut offset = 32;
+    for _ in 0..ncmds {
+        let cmd = u32_at(offset)?;
+        let cmdsize = u32_at(offset + 4)? as usize;
+        if cmd == LC_SEGMENT_64 {
+            let name = bin
+                .get(offset + 8..offset + 24)
+                .context("truncated load command")?;
+            anyhow::ensure!(
+                !name.starts_with(b"__LLVM\0"),
+                "bitcode is not accepted by the app store, build without -Cembed-bitcode"
+            );
+        }
+        anyhow::ensure!(cmdsize >= 8, "invalid load command size");
+        offset += cmdsize;
+    }
+    Ok(())
+}
+
+struct AppStoreConnect {
+    client: Client,
+    encoder: ConnectTokenEncoder,
+}
+
+impl AppStoreConnect {
+    fn from_api_key(api_key: &Path, client: Client) -> Result<Self> {
+        let key = UnifiedApiKey::from_json_path(api_key)?;
+        Ok(Self {
+            client,
+            encoder: ConnectTokenEncoder::try_from(key)?,
+        })
+    }
+
+    fn send(&self, method: Method, url: &str, body: Option<Value>) -> Result<Value> {
+        let token = self.encoder.new_token(300)?;
+        let mut req = self.client.request(method.clone(), url).bearer_auth(token);
+        if let Some(body) = body {
+            req = req
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_vec(&body)?);
+        }
+        let resp = req.send()?;
+        let status = resp.status();
+        let bytes = resp.bytes()?;
+        anyhow::ensure!(
+            status.is_success(),
+            "{} {} returned status code {}: {}",
+            method,
+            url,
+            status,
+            String::from_utf8_lossy(&bytes)
+        );
+        if bytes.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn app_id(&self, bundle_identifier: &str) -> Result<String> {
+        let url = format!("{}/apps?filter[bundleId]={}", API_URL, bundle_identifier);
+        let resp = self.send(Method::GET, &url, None)?;
+        let id = resp["data"][0]["id"].as_str().with_context(|| {
+            format!(
+                "no app with bundle identifier {} in App Store Connect",
+                bundle_identifier
+            )
+        })?;
+        Ok(id.to_string())
+    }
+}
+
+/// Uploads an ipa to App Store Connect and waits until it is processed and
+/// available in TestFlight.
+pub fn upload_ipa(ipa: &Path, info: &AppInfo, api_key: &Path, client: Client) -> Result<()> {
+    println!("uploading {}", ipa.display());
+    let api = AppStoreConnect::from_api_key(api_key, client)?;
+    let app_id = api.app_id(&info.bundle_identifier)?;
+    let upload = api.send(
+        Method::POST,
+        &format!("{}/buildUploads", API_URL),
+        Some(json!({
+            "data": {
+                "type": "buildUploads",
+                "attributes": {
+                    "cfBundleShortVersionString": info.short_version,
+                    "cfBundleVersion": info.version,
+                    "platform": "IOS",
+                },
+                "relationships": {
+                    "app": { "data": { "type": "apps", "id": app_id } },
+                },
+            },
+        })),
+    )?;
+    let upload_id = upload["data"]["id"]
+        .as_str()
+        .context("invalid build upload response")?
+        .to_string();
+    let bytes = std::fs::read(ipa)?;
+    let file_name = ipa.file_name().unwrap().to_str().unwrap();
+    let file = api.send(
+        Method::POST,
+        &format!("{}/buildUploadFiles", API_URL),
+        Some(json!({
+            "data": {
+                "type": "buildUploadFiles",
+                "attributes": {
+                    "assetType": "ASSET",
+                    "fileName": file_name,
+                    "fileSize": bytes.len(),
+                    "uti": "com.apple.ipa",
+                },
+                "relationships": {
+                    "buildUpload": { "data": { "type": "buildUploads", "id": upload_id } },
+                },
+            },
+        })),
+    )?;
+    let file_id = file["data"]["id"]
+        .as_str()
+        .context("invalid build upload file response")?;
+    let operations = file["data"]["attributes"]["uploadOperations"]
+        .as_array()
+        .context("invalid build upload file response")?;
+    for op in operations {
+        let method = op["method"].as_str().context("invalid upload operation")?;
+        let url = op["url"].as_str().context("invalid upload operation")?;
+        let offset = op["offset"].as_u64().unwrap_or_default() as usize;
+        let length = op["length"].as_u64().context("invalid upload operation")? as usize;
+        let chunk = bytes
+            .get(offset..offset + length)
+            .context("upload operation out of bounds")?;
+        let mut req = api
+            .client
+            .request(Method::from_bytes(method.as_bytes())?, url)
+            .body(chunk.to_vec());
+        for header in op["requestHeaders"].as_array().into_iter().flatten() {
+            if let (Some(name), Some(value)) = (header["name"].as_str(), header["value"].as_str()) {
+                req = req.header(name, value);
+            }
+        }
+        let resp = req.send()?;
+        anyhow::ensure!(
+            resp.status().is_success(),
+            "uploading part at offset {} returned status code {}",
+            offset,
+            resp.status()
+        );
+    }
+    api.send(
+        Method::PATCH,
+        &format!("{}/buildUploadFiles/{}", API_URL, file_id),
+        Some(json!({
+            "data": {
+                "type": "buildUploadFiles",
+                "id": file_id,
+                "attributes": { "uploaded": true },
+            },
+        })),
+    )?;
+    println!("build upload id: {}", upload_id);
+    let start_time = Instant::now();
+    loop {
+        let resp = api.send(
+            Method::GET,
+            &format!("{}/buildUploads/{}", API_URL, upload_id),
+            None,
+        )?;
+        let state = &resp["data"]["attributes"]["state"];
+        let status = state["state"].as_str().unwrap_or("UNKNOWN");
+        let elapsed = start_time.elapsed();
+        println!("poll state after {}s: {}", elapsed.as_secs(), status);
+        if status == "FAILED" || status == "COMPLETE" {
+            for kind in ["errors", "warnings", "infos"] {
+                for detail in state[kind].as_array().into_iter().flatten() {
+                    let message = detail["description"]
+                        .as_str()
+                        .or(detail["message"].as_str());
+                    println!("{}: {}", kind, message.unwrap_or_default());
+                }
+            }
+            anyhow::ensure!(status == "COMPLETE", "processing of the build failed");
+            break;
+        }
+        std::thread::sleep(Duration::from_secs(10));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate_executable() {
+    fn macho(cputype: u32, segname: &[u8]) -> Vec<u8> {
+        let mut bin = vec![];
+        bin.extend(MH_MAGIC_64.to_le_bytes());
+        bin.extend(cputype.to_le_bytes());
+        bin.extend([0; 8]);
+        bin.extend(1u32.to_le_bytes());
+        bin.extend(72u32.to_le_bytes());
+        bin.extend([0; 8]);
+        bin.extend(LC_SEGMENT_64.to_le_bytes());
+        bin.extend(72u32.to_le_bytes());
+        let mut name = [0; 16];
+        name[..segname.len()].copy_from_slice(segname);
+        bin.extend(name);
+        bin.extend([0; 48]);
+        bin
+    }
+    assert!(validate_executable(&macho(CPU_TYPE_ARM64, b"__TEXT")).is_ok());
+    assert!(validate_executable(&macho(CPU_TYPE_ARM64, b"__LLVM")).is_err());
+    assert!(validate_executable(&macho(0x0100_0007, b"__TEXT")).is_err());
+    assert!(validate_executable(&FAT_MAGIC.to_be_bytes()).is_err());
+}
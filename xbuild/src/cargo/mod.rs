@@ -12,7 +12,9 @@ pub use artifact::{Artifact, CrateType};
 
 use self::config::LocalizedConfig;
 use self::manifest::Manifest;
-use crate::{CompileTarget, Opt};
+use crate::config::{DebugInfo, Panic};
+use crate::container::Container;
+use crate::{Arch, CompileTarget, Opt};
 
 pub struct Cargo {
     package: String,
@@ -20,6 +22,7 @@ pub struct Cargo {
     workspace_manifest: Option<Manifest>,
     manifest: Manifest,
     package_root: PathBuf,
+    workspace_root: PathBuf,
     target_dir: PathBuf,
     offline: bool,
 }
@@ -32,6 +35,17 @@ impl Cargo {
         target_dir: Option<PathBuf>,
         offline: bool,
     ) -> Result<Self> {
+        // `-p ./crates/app` selects the package by path instead of by name
+        let (package, manifest_path) = match package.and_then(utils::package_manifest_path) {
+            Some(package_manifest_path) => {
+                anyhow::ensure!(
+                    manifest_path.is_none(),
+                    "--manifest-path can't be combined with a package path"
+                );
+                (None, Some(package_manifest_path))
+            }
+            None => (package, manifest_path),
+        };
         let manifest_path = manifest_path
             .map(|path| {
                 if path.file_name() != Some(OsStr::new("Cargo.toml")) || !path.is_file() {
@@ -91,14 +105,15 @@ impl Cargo {
                 }
             });
 
+        let workspace_root = workspace_manifest
+            .as_ref()
+            .map(|(path, _)| path)
+            .unwrap_or_else(|| &manifest_path)
+            .parent()
+            .unwrap()
+            .to_path_buf();
         let target_dir = target_dir.unwrap_or_else(|| {
-            workspace_manifest
-                .as_ref()
-                .map(|(path, _)| path)
-                .unwrap_or_else(|| &manifest_path)
-                .parent()
-                .unwrap()
-                .join(utils::get_target_dir_name(config.as_deref()).unwrap())
+            workspace_root.join(utils::get_target_dir_name(config.as_deref()).unwrap())
         });
 
         Ok(Self {
@@ -107,6 +122,7 @@ impl Cargo {
             workspace_manifest: workspace_manifest.map(|(_path, manifest)| manifest),
             manifest,
             package_root: package_root.to_owned(),
+            workspace_root,
             target_dir,
             offline,
         })
@@ -132,6 +148,12 @@ impl Cargo {
         &self.package_root
     }
 
+    /// Directory of the workspace manifest, or the package root outside of a
+    /// workspace.
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
+    }
+
     pub fn examples(&self) -> Result<Vec<Artifact>> {
         let mut artifacts = vec![];
         for file in utils::list_rust_files(&self.package_root().join("examples"))? {
@@ -237,6 +259,9 @@ pub struct CargoBuild {
     c_flags: String,
     cxx_flags: String,
     rust_flags: String,
+    sccache: bool,
+    container: Option<Container>,
+    print_env: bool,
 }
 
 impl CargoBuild {
@@ -268,18 +293,26 @@ impl CargoBuild {
         for features in features {
             cmd.arg("--features").arg(features);
         }
+        // lets crates gate code with `#[cfg(xbuild_platform = "android")]`
+        let rust_flags = format!("--cfg xbuild_platform=\"{}\" ", target.platform());
         Ok(Self {
             cmd,
             target,
             triple,
             c_flags: Default::default(),
             cxx_flags: Default::default(),
-            rust_flags: Default::default(),
+            rust_flags,
+            sccache: false,
+            container: None,
+            print_env: false,
         })
     }
 
-    pub fn use_android_ndk(&mut self, path: &Path, target_sdk_version: u32) -> Result<()> {
-        let path = dunce::canonicalize(path)?;
+    /// Links against the libraries of `api_level`, which should be the min sdk
+    /// version so newer APIs aren't made available inadvertently.
+    pub fn use_android_ndk(&mut self, path: &Path, api_level: u32) -> Result<()> {
+        let path = dunce::canonicalize(path)
+            .with_context(|| format!("ndk sysroot `{}` doesn't exist", path.display()))?;
         let ndk_triple = self.target.ndk_triple();
         self.cfg_tool(Tool::Cc, "clang");
         self.cfg_tool(Tool::Cxx, "clang++");
@@ -288,16 +321,27 @@ impl CargoBuild {
         self.set_sysroot(&path);
         self.add_cxxflag("-stdlib=libc++");
         let lib_dir = path.join("usr").join("lib").join(ndk_triple);
-        let sdk_lib_dir = lib_dir.join(target_sdk_version.to_string());
+        let sdk_lib_dir = lib_dir.join(api_level.to_string());
+        anyhow::ensure!(
+            lib_dir.exists(),
+            "ndk doesn't support {}: `{}` doesn't exist",
+            ndk_triple,
+            lib_dir.display()
+        );
         anyhow::ensure!(
             sdk_lib_dir.exists(),
-            "ndk doesn't support sdk version {}",
-            target_sdk_version
+            "ndk doesn't support sdk version {} for {}: `{}` doesn't exist",
+            api_level,
+            ndk_triple,
+            sdk_lib_dir.display()
         );
         self.use_ld("lld");
-        if let Some(triple) = self.triple {
-            self.add_link_arg(&format!("--target={}", triple));
-        }
+        // the versioned triple selects the api level of the ndk headers. ndks
+        // since r17 only ship armv7 libraries for 32-bit arm, so C code linked
+        // into an armeabi library requires armv7 too
+        let clang_target = format!("--target={}{}", self.target.clang_triple(), api_level);
+        self.add_cflag(&clang_target);
+        self.add_link_arg(&clang_target);
         self.add_link_arg(&format!("-B{}", sdk_lib_dir.display()));
         self.add_link_arg(&format!("-L{}", sdk_lib_dir.display()));
         self.add_link_arg(&format!("-L{}", lib_dir.display()));
@@ -310,13 +354,14 @@ impl CargoBuild {
         self.cfg_tool(Tool::Cxx, "clang-cl");
         self.cfg_tool(Tool::Ar, "llvm-lib");
         self.cfg_tool(Tool::Linker, "rust-lld"); // Rust defaults to link.exe, use its rust-lld binary instead
+        self.add_cflag(&format!("--target={}", self.target.rust_triple()?));
         self.add_msvc_include_dir(&path.join("crt").join("include"));
         self.add_msvc_include_dir(&path.join("sdk").join("include").join("um"));
         self.add_msvc_include_dir(&path.join("sdk").join("include").join("ucrt"));
         self.add_msvc_include_dir(&path.join("sdk").join("include").join("shared"));
-        self.add_lib_dir(&path.join("crt").join("lib").join("x86_64"));
-        self.add_lib_dir(&path.join("sdk").join("lib").join("um").join("x86_64"));
-        self.add_lib_dir(&path.join("sdk").join("lib").join("ucrt").join("x86_64"));
+        for lib_dir in windows_sdk_lib_dirs(&path, self.target.arch()) {
+            self.add_lib_dir(&lib_dir);
+        }
         Ok(())
     }
 
@@ -369,8 +414,25 @@ impl CargoBuild {
         Ok(())
     }
 
+    /// Caches rustc and C/C++ compiler invocations with `sccache`. Must be
+    /// called before [`CargoBuild::cfg_tool`] to wrap the configured compilers.
+    pub fn use_sccache(&mut self) {
+        self.sccache = true;
+        self.cmd.env("RUSTC_WRAPPER", "sccache");
+    }
+
+    /// Runs cargo inside `container` instead of on the host.
+    pub fn use_container(&mut self, container: Container) {
+        self.container = Some(container);
+    }
+
     pub fn cfg_tool<P: AsRef<Path>>(&mut self, tool: Tool, path: P) {
         match tool {
+            Tool::Cc | Tool::Cxx if self.sccache => {
+                // the cc crate detects the wrapper as the first word of `CC`
+                let cc = format!("sccache {}", path.as_ref().to_str().unwrap());
+                self.cc_triple_env(&tool.to_string(), &cc);
+            }
             Tool::Cc | Tool::Cxx | Tool::Ar => {
                 self.cc_triple_env(&tool.to_string(), path.as_ref().to_str().unwrap());
             }
@@ -423,6 +485,31 @@ impl CargoBuild {
             .push_str(&format!("-Ctarget-feature={} ", target_feature));
     }
 
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.cmd.arg("--jobs").arg(jobs.to_string());
+    }
+
+    pub fn set_target_cpu(&mut self, target_cpu: &str) {
+        self.rust_flags
+            .push_str(&format!("-Ctarget-cpu={} ", target_cpu));
+    }
+
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo) {
+        let (level, cflag) = match debug_info {
+            DebugInfo::None => (0, "-g0"),
+            DebugInfo::LineTablesOnly => (1, "-gline-tables-only"),
+            DebugInfo::Full => (2, "-g"),
+        };
+        self.rust_flags.push_str(&format!("-Cdebuginfo={} ", level));
+        self.add_cflag(cflag);
+    }
+
+    pub fn set_panic(&mut self, panic: Panic) {
+        if panic == Panic::Abort {
+            self.rust_flags.push_str("-Cpanic=abort ");
+        }
+    }
+
     pub fn add_link_arg(&mut self, link_arg: &str) {
         self.rust_flags
             .push_str(&format!("-Clink-arg={} ", link_arg));
@@ -464,12 +551,56 @@ impl CargoBuild {
         self.cmd.arg(arg);
     }
 
-    pub fn exec(mut self) -> Result<()> {
+    pub fn env(&mut self, name: &str, value: &str) {
+        self.cmd.env(name, value);
+    }
+
+    /// Prints the environment variables set for cargo before running it, to
+    /// debug build scripts of `-sys` crates.
+    pub fn print_env(&mut self) {
+        self.print_env = true;
+    }
+
+    fn apply_flags(&mut self) {
         self.cargo_target_env("RUSTFLAGS", &self.rust_flags.clone());
         self.cc_triple_env("CFLAGS", &self.c_flags.clone());
         // These strings already end with a space if they're non-empty:
         self.cc_triple_env("CXXFLAGS", &format!("{}{}", self.c_flags, self.cxx_flags));
-        if !self.cmd.status()?.success() {
+        // bindgen parses headers with libclang, which needs the same flags
+        self.cc_triple_env("BINDGEN_EXTRA_CLANG_ARGS", &self.c_flags.clone());
+    }
+
+    fn wrap(self) -> Command {
+        if let Some(container) = self.container.as_ref() {
+            container.wrap(&self.cmd)
+        } else {
+            self.cmd
+        }
+    }
+
+    /// Returns the `cargo` invocation with all flags applied, without running it.
+    pub fn into_command(mut self) -> Command {
+        self.apply_flags();
+        self.wrap()
+    }
+
+    pub fn exec(mut self) -> Result<()> {
+        self.apply_flags();
+        if self.print_env {
+            let mut envs = self.cmd.get_envs().collect::<Vec<_>>();
+            envs.sort();
+            println!("cargo environment:");
+            for (key, value) in envs {
+                if let Some(value) = value {
+                    println!("  {}={}", key.to_string_lossy(), value.to_string_lossy());
+                } else {
+                    println!("  {} (unset)", key.to_string_lossy());
+                }
+            }
+        }
+        let mut cmd = self.wrap();
+        tracing::info!("running {:?}", cmd);
+        if !cmd.status()?.success() {
             std::process::exit(1);
         }
         Ok(())
@@ -494,3 +625,35 @@ impl std::fmt::Display for Tool {
         }
     }
 }
+
+/// Arch directory name used by the splatted windows sdk.
+fn windows_sdk_arch(arch: Arch) -> &'static str {
+    match arch {
+        // `rust_triple` rejects arm on windows before the sdk is used
+        Arch::Arm => unreachable!(),
+        Arch::Arm64 => "aarch64",
+        Arch::X64 => "x86_64",
+    }
+}
+
+fn windows_sdk_lib_dirs(sdk: &Path, arch: Arch) -> [PathBuf; 3] {
+    let arch = windows_sdk_arch(arch);
+    [
+        sdk.join("crt").join("lib").join(arch),
+        sdk.join("sdk").join("lib").join("um").join(arch),
+        sdk.join("sdk").join("lib").join("ucrt").join(arch),
+    ]
+}
+
+#[test]
+fn test_windows_sdk_lib_dirs() {
+    let sdk = Path::new("Windows.sdk");
+    let [crt, um, ucrt] = windows_sdk_lib_dirs(sdk, Arch::X64);
+    assert_eq!(crt, Path::new("Windows.sdk/crt/lib/x86_64"));
+    assert_eq!(um, Path::new("Windows.sdk/sdk/lib/um/x86_64"));
+    assert_eq!(ucrt, Path::new("Windows.sdk/sdk/lib/ucrt/x86_64"));
+    let [crt, um, ucrt] = windows_sdk_lib_dirs(sdk, Arch::Arm64);
+    assert_eq!(crt, Path::new("Windows.sdk/crt/lib/aarch64"));
+    assert_eq!(um, Path::new("Windows.sdk/sdk/lib/um/aarch64"));
+    assert_eq!(ucrt, Path::new("Windows.sdk/sdk/lib/ucrt/aarch64"));
+}
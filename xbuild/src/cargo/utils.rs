@@ -27,6 +27,19 @@ pub fn canonicalize(mut path: &Path) -> Result<PathBuf> {
         .with_context(|| format!("Failed to canonicalize `{}`", path.display()))
 }
 
+/// Resolves `-p` to the `Cargo.toml` of a package when it names an existing
+/// package directory or manifest instead of a package name.
+pub fn package_manifest_path(package: &str) -> Option<PathBuf> {
+    let path = Path::new(package);
+    let manifest_path = if path.is_dir() {
+        path.join("Cargo.toml")
+    } else {
+        path.to_path_buf()
+    };
+    let is_manifest = manifest_path.file_name() == Some(OsStr::new("Cargo.toml"));
+    (is_manifest && manifest_path.is_file()).then_some(manifest_path)
+}
+
 /// Tries to find a package by the given `name` in the [workspace root] or member
 /// of the given [workspace] [`Manifest`], and possibly falls back to a potential
 /// manifest based on the working directory or `--manifest-path` as found by
@@ -148,3 +161,22 @@ pub fn get_target_dir_name(config: Option<&Config>) -> Result<String> {
     }
     Ok("target".to_string())
 }
+
+#[test]
+fn test_package_manifest_path() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let manifest_path = dir.join("Cargo.toml");
+    assert_eq!(
+        package_manifest_path(dir.to_str().unwrap()),
+        Some(manifest_path.clone())
+    );
+    assert_eq!(
+        package_manifest_path(manifest_path.to_str().unwrap()),
+        Some(manifest_path)
+    );
+    assert_eq!(
+        package_manifest_path(dir.join("src").to_str().unwrap()),
+        None
+    );
+    assert_eq!(package_manifest_path("xbuild-does-not-exist"), None);
+}
@@ -1,29 +1,32 @@
+use crate::app_store;
 use crate::cargo::CrateType;
-use crate::download::DownloadManager;
+use crate::compress;
+use crate::download::{self, DownloadManager};
 use crate::task::TaskRunner;
-use crate::{BuildEnv, Format, Opt, Platform};
+use crate::{BuildEnv, CompileTarget, Format, Opt, Platform, Store, XbuildError};
 use anyhow::{ensure, Context, Result};
 use apk::Apk;
 use appbundle::AppBundle;
 use appimage::AppImage;
 use msix::Msix;
+use mvn::{Maven, Package};
 use std::collections::HashSet;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
 use xcommon::{Zip, ZipFileOptions};
 
 pub fn build(env: &BuildEnv) -> Result<()> {
     let platform_dir = env.platform_dir();
     std::fs::create_dir_all(&platform_dir)?;
 
-    let mut runner = TaskRunner::new(3, env.verbose());
+    let mut runner = TaskRunner::new(3, env.verbose(), env.ci());
 
     runner.start_task("Fetch precompiled artifacts");
     let manager = DownloadManager::new(env)?;
-    if !env.offline() {
-        manager.prefetch()?;
-        runner.end_verbose_task();
-    }
+    manager.prefetch()?;
+    runner.end_verbose_task();
 
     runner.start_task(format!("Build rust `{}`", env.name));
     let bin_target = env.target().platform() != Platform::Android;
@@ -39,6 +42,23 @@ pub fn build(env: &BuildEnv) -> Result<()> {
                 cargo.arg("--lib");
             }
             cargo.exec()?;
+            if env.strip_symbols_to().is_some() || env.split_debug() {
+                let cargo_dir = arch_dir.join("cargo");
+                let mut artifacts = vec![];
+                if bin_target {
+                    artifacts.push(env.cargo_artefact(&cargo_dir, target, CrateType::Bin)?);
+                }
+                if has_lib {
+                    artifacts.push(env.cargo_artefact(&cargo_dir, target, CrateType::Cdylib)?);
+                }
+                for artifact in artifacts {
+                    if let Some(dir) = env.strip_symbols_to() {
+                        strip_symbols(target, &artifact, dir)?;
+                    } else {
+                        split_debug(target, &artifact)?;
+                    }
+                }
+            }
         }
         runner.end_verbose_task();
     }
@@ -49,8 +69,29 @@ pub fn build(env: &BuildEnv) -> Result<()> {
             let target = env.target().compile_targets().next().unwrap();
             let arch_dir = platform_dir.join(target.arch().to_string());
 
-            let appimage = AppImage::new(&arch_dir, env.name().to_string())?;
-            appimage.add_apprun()?;
+            let mut appimage = if env.keep_intermediates() {
+                AppImage::reuse(&arch_dir, env.name().to_string())?
+            } else {
+                AppImage::new(&arch_dir, env.name().to_string())?
+            };
+            print_intermediates(env, appimage.appdir());
+            let options = &env.config().linux().appimage;
+            appimage.set_squashfs_options(options.squashfs.clone());
+            if let Some(info) = options.update_info.as_ref() {
+                appimage.set_update_info(info.clone())?;
+            }
+            appimage.set_zsync(options.zsync);
+            appimage.set_exclude(env.exclude().clone());
+            match (options.apprun.as_ref(), options.apprun_env) {
+                (Some(_), true) => {
+                    anyhow::bail!("linux.appimage.apprun and apprun_env are mutually exclusive")
+                }
+                (Some(script), false) => {
+                    appimage.add_apprun_script(&env.root_dir().join(script))?
+                }
+                (None, true) => appimage.add_apprun_env()?,
+                (None, false) => appimage.add_apprun()?,
+            }
             appimage.add_desktop()?;
             if let Some(icon) = env.icon() {
                 appimage.add_icon(icon)?;
@@ -58,20 +99,29 @@ pub fn build(env: &BuildEnv) -> Result<()> {
 
             let main = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Bin)?;
             appimage.add_file(&main, Path::new(env.name()))?;
+            if env.split_debug() {
+                let debug = debug_file(Platform::Linux, &main);
+                appimage.add_file(&debug, Path::new(debug.file_name().unwrap()))?;
+            }
 
             if has_lib {
                 let lib = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Cdylib)?;
                 appimage.add_file(&lib, &Path::new("lib").join(lib.file_name().unwrap()))?;
+                if env.split_debug() {
+                    let debug = debug_file(Platform::Linux, &lib);
+                    appimage
+                        .add_file(&debug, &Path::new("lib").join(debug.file_name().unwrap()))?;
+                }
             }
 
             if env.target().format() == Format::Appimage {
-                let out = arch_dir.join(format!("{}.AppImage", env.name()));
-                appimage.build(&out, env.target().signer().cloned())?;
+                appimage.build(&env.output(), env.target().signer().cloned())?;
             }
         }
         Platform::Android => {
-            let out = platform_dir.join(format!("{}.{}", env.name(), env.target().format()));
+            let out = env.output();
             ensure!(has_lib, "Android APKs/AABs require a library");
+            crate::target_sdk::warn_behavior_changes(env)?;
 
             let mut libraries = vec![];
 
@@ -103,6 +153,14 @@ pub fn build(env: &BuildEnv) -> Result<()> {
                         )
                     })?;
                 search_paths.push(deps_dir);
+                let native_libs_dir = env
+                    .config()
+                    .android()
+                    .native_libs_dir(target.android_abi())
+                    .map(|dir| env.root_dir().join(dir));
+                if let Some(dir) = native_libs_dir.as_ref() {
+                    search_paths.push(dir.clone());
+                }
                 let search_paths = search_paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
 
                 let ndk_sysroot_libs = ndk.join("usr/lib").join(target.ndk_triple());
@@ -112,25 +170,24 @@ pub fn build(env: &BuildEnv) -> Result<()> {
                         // Use libraries (symbols) from the lowest NDK that is supported by the application,
                         // to prevent inadvertently making newer APIs available:
                         // https://developer.android.com/ndk/guides/sdk-versions
-                        env.config()
-                            .android()
-                            .manifest
-                            .sdk
-                            .min_sdk_version
-                            .unwrap()
-                            .to_string(),
+                        env.min_sdk_version().to_string(),
                     ),
                 ];
 
                 let mut explicit_libs = vec![lib];
 
                 // Collect the libraries the user wants to include
-                for runtime_lib_path in env.config().runtime_libs(env.target().platform()) {
-                    let abi_dir = env
-                        .cargo()
-                        .package_root()
-                        .join(runtime_lib_path)
-                        .join(target.android_abi().as_str());
+                let runtime_lib_dirs = env
+                    .config()
+                    .runtime_libs(env.target().platform())
+                    .into_iter()
+                    .map(|path| {
+                        env.cargo()
+                            .package_root()
+                            .join(path)
+                            .join(target.android_abi().as_str())
+                    });
+                for abi_dir in runtime_lib_dirs.chain(native_libs_dir) {
                     let entries = std::fs::read_dir(&abi_dir).with_context(|| {
                         format!(
                             "Runtime libraries for current ABI not found at `{}`",
@@ -164,6 +221,7 @@ pub fn build(env: &BuildEnv) -> Result<()> {
                 let mut needs_cpp_shared = false;
 
                 for lib in explicit_libs {
+                    warn_symbols_above_min_sdk(&lib, &ndk_sysroot_libs, env.min_sdk_version());
                     libraries.push((target.android_abi(), lib.clone()));
 
                     let (extra_libs, cpp_shared) = xcommon::llvm::list_needed_libs_recursively(
@@ -191,22 +249,78 @@ pub fn build(env: &BuildEnv) -> Result<()> {
             }
 
             if env.config().android().gradle {
+                ensure!(
+                    !env.incremental(),
+                    "--incremental is not supported with `gradle: true`"
+                );
                 crate::gradle::build(env, libraries, &out)?;
                 runner.end_verbose_task();
                 return Ok(());
             } else {
-                let mut apk = Apk::new(
-                    out,
-                    env.config().android().manifest.clone(),
-                    env.target().opt() != Opt::Debug,
-                )?;
-                apk.add_res(env.icon(), &env.android_jar())?;
-
-                for asset in &env.config().android().assets {
-                    let path = env.cargo().package_root().join(asset.path());
+                ensure!(
+                    env.config().android().asset_packs.is_empty(),
+                    "Asset packs require `gradle: true`"
+                );
+                let artifacts = if env.config().android().dependencies.is_empty() {
+                    vec![]
+                } else {
+                    resolve_android_dependencies(env, &manager)?
+                };
+                // resources and assets are reused while their inputs are unchanged,
+                // merged libraries add entries the update doesn't know about
+                let inputs = apk_inputs(env)?;
+                let inputs_stamp = out.with_extension("apk.inputs");
+                let reuse = artifacts.is_empty()
+                    && out.exists()
+                    && std::fs::read_to_string(&inputs_stamp).ok().as_deref() == Some(&*inputs);
+                let manifest = env.config().android().manifest.for_apk()?;
+                let mut apk = if reuse {
+                    Apk::update(out.clone(), manifest, env.compression())?
+                } else {
+                    std::fs::remove_file(&inputs_stamp).ok();
+                    Apk::new(out.clone(), manifest, env.compression())?
+                };
+                apk.set_exclude(env.exclude().clone());
+                apk.set_icon_densities(env.config().android().icon_densities.clone());
+                if let Some(dir) = env.android_resources() {
+                    apk.add_resources(dir);
+                }
+                if let Some(theme) = env.config().android().theme.as_ref() {
+                    let dir = platform_dir.join("theme");
+                    std::fs::remove_dir_all(&dir).ok();
+                    theme.write_resources(&dir)?;
+                    apk.add_resources(dir);
+                }
+                if !reuse {
+                    apk.add_res(env.icon(), &env.android_jar())?;
+
+                    let staged_assets = platform_dir.join("assets");
+                    std::fs::remove_dir_all(&staged_assets).ok();
+                    for asset in &env.config().android().assets {
+                        let mut path = env.assets_dir().join(asset.path());
+
+                        if !asset.optional() || path.exists() {
+                            if env.compress_assets() {
+                                path = compress::stage_asset(&path, &staged_assets, env.exclude())?;
+                            }
+                            apk.add_asset(&path, asset.alignment().to_zip_file_options())?
+                        }
+                    }
 
-                    if !asset.optional() || path.exists() {
-                        apk.add_asset(&path, asset.alignment().to_zip_file_options())?
+                    let mut jars = vec![];
+                    for artifact in &artifacts {
+                        match artifact.extension().and_then(|ext| ext.to_str()) {
+                            Some("aar") => {
+                                jars.extend(apk.add_aar(artifact, &platform_dir.join("classes"))?)
+                            }
+                            Some("jar") => jars.push(artifact.clone()),
+                            _ => {}
+                        }
+                    }
+                    if !jars.is_empty() {
+                        let dex_dir = platform_dir.join("dex");
+                        dex(env, &jars, &dex_dir)?;
+                        apk.add_dex(&dex_dir.join("classes.dex"))?;
                     }
                 }
 
@@ -215,37 +329,71 @@ pub fn build(env: &BuildEnv) -> Result<()> {
                 }
 
                 apk.finish(env.target().signer().cloned())?;
+                std::fs::write(&inputs_stamp, inputs)?;
+                if env.incremental() {
+                    Apk::sign_v4(&out, env.target().signer().cloned())?;
+                }
             }
         }
         Platform::Macos => {
             let target = env.target().compile_targets().next().unwrap();
             let arch_dir = platform_dir.join(target.arch().to_string());
 
-            let mut app = AppBundle::new(&arch_dir, env.config().macos().info.clone())?;
-            if let Some(icon) = env.icon() {
-                app.add_icon(icon)?;
-            }
+            let info = env.config().macos().info.clone();
+            let mut app = if env.keep_intermediates() {
+                AppBundle::reuse(&arch_dir, info)?
+            } else {
+                AppBundle::new(&arch_dir, info)?
+            };
+            print_intermediates(env, app.appdir());
+            let xcassets = env.config().macos().xcassets.as_ref();
+            let xcassets = xcassets.map(|path| env.root_dir().join(path));
+            app.set_exclude(env.exclude().clone());
+            app.add_assets(env.icon(), xcassets.as_deref())?;
 
             let main = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Bin)?;
             app.add_executable(&main)?;
+            if env.split_debug() {
+                copy_debug_file(&debug_file(Platform::Macos, &main), &arch_dir)?;
+            }
 
             if has_lib {
                 let lib = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Cdylib)?;
                 app.add_lib(&lib)?;
+                if env.split_debug() {
+                    copy_debug_file(&debug_file(Platform::Macos, &lib), &arch_dir)?;
+                }
             }
 
+            app.set_signing_identity(env.target().signing_identity().map(Into::into));
             app.finish(env.target().signer().cloned())?;
             if let Some(api_key) = env.target().api_key() {
-                appbundle::notarize(app.appdir(), api_key)?;
+                appbundle::notarize(
+                    app.appdir(),
+                    api_key,
+                    env.target().notarize_timeout(),
+                    env.target().notarize_poll_interval(),
+                )?;
             }
             if env.target().format() == Format::Dmg {
-                let out = arch_dir.join(format!("{}.dmg", env.name()));
+                let out = env.output();
                 apple_dmg::create_dmg(app.appdir(), &out, env.name(), 0x40000)?;
-                if let Some(signer) = env.target().signer() {
+                let signed = if env.target().signing_identity().is_some() {
+                    app.sign_dmg_with_identity(&out)?;
+                    true
+                } else if let Some(signer) = env.target().signer() {
                     app.sign_dmg(&out, signer)?;
-                    if let Some(api_key) = env.target().api_key() {
-                        appbundle::notarize(&out, api_key)?;
-                    }
+                    true
+                } else {
+                    false
+                };
+                if let Some(api_key) = env.target().api_key().filter(|_| signed) {
+                    appbundle::notarize(
+                        &out,
+                        api_key,
+                        env.target().notarize_timeout(),
+                        env.target().notarize_poll_interval(),
+                    )?;
                 }
             }
         }
@@ -253,37 +401,64 @@ pub fn build(env: &BuildEnv) -> Result<()> {
             let target = env.target().compile_targets().next().unwrap();
             let arch_dir = platform_dir.join(target.arch().to_string());
             std::fs::create_dir_all(&arch_dir)?;
-            let mut app = AppBundle::new(&arch_dir, env.config().ios().info.clone())?;
-            if let Some(icon) = env.icon() {
-                app.add_icon(icon)?;
-            }
+            let info = env.config().ios().info.clone();
+            let mut app = if env.keep_intermediates() {
+                AppBundle::reuse(&arch_dir, info)?
+            } else {
+                AppBundle::new(&arch_dir, info)?
+            };
+            print_intermediates(env, app.appdir());
+            let xcassets = env.config().ios().xcassets.as_ref();
+            let xcassets = xcassets.map(|path| env.root_dir().join(path));
+            app.set_exclude(env.exclude().clone());
+            app.add_assets(env.icon(), xcassets.as_deref())?;
             let main = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Bin)?;
             app.add_executable(&main)?;
-            if let Some(provisioning_profile) = env.target().provisioning_profile() {
-                app.add_provisioning_profile(provisioning_profile)?;
+            let provisioning_profiles = env.target().provisioning_profiles();
+            if !provisioning_profiles.is_empty() {
+                app.add_provisioning_profiles(provisioning_profiles)?;
             }
             if let Some(assets_car) = env.config().ios().assets_car.as_ref() {
                 app.add_file(assets_car, "Assets.car".as_ref())?;
             }
+            app.set_signing_identity(env.target().signing_identity().map(Into::into));
             app.finish(env.target().signer().cloned())?;
+            let app_dir = app.appdir().to_path_buf();
+            let store_info = if env.target().store() == Some(Store::Apple) {
+                Some(app_store::validate_app(&app_dir)?)
+            } else {
+                None
+            };
             if env.target().format() == Format::Ipa {
                 let app = arch_dir.join(format!("{}.app", env.name()));
-                let out = arch_dir.join(format!("{}.ipa", env.name()));
-                let mut ipa = Zip::new(&out, false)?;
+                let out = env.output();
+                let mut ipa = Zip::new(&out, env.compression())?;
                 ipa.add_directory(
                     &app,
                     &Path::new("Payload").join(format!("{}.app", env.name())),
                     ZipFileOptions::Compressed,
                 )?;
                 ipa.finish()?;
+                if let (Some(info), Some(api_key)) = (&store_info, env.target().api_key()) {
+                    let client = download::http_client(env.config().network())?;
+                    app_store::upload_ipa(&out, info, api_key, client)?;
+                }
             }
         }
         Platform::Windows => {
             let target = env.target().compile_targets().next().unwrap();
             let arch_dir = platform_dir.join(target.arch().to_string());
             std::fs::create_dir_all(&arch_dir)?;
-            let out = arch_dir.join(format!("{}.{}", env.name(), env.target().format()));
+            let out = env.output();
             let main = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Bin)?;
+            if env.split_debug() {
+                copy_debug_file(&debug_file(Platform::Windows, &main), &arch_dir)?;
+                if has_lib {
+                    let lib =
+                        env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Cdylib)?;
+                    copy_debug_file(&debug_file(Platform::Windows, &lib), &arch_dir)?;
+                }
+            }
             match env.target().format() {
                 Format::Exe => {
                     std::fs::copy(&main, &out)?;
@@ -292,12 +467,20 @@ pub fn build(env: &BuildEnv) -> Result<()> {
                     let mut msix = Msix::new(
                         out,
                         env.config().windows().manifest.clone(),
-                        target.opt() != Opt::Debug,
+                        env.compression(),
                     )?;
+                    msix.set_exclude(env.exclude().clone());
+                    if let Some(jobs) = env.max_jobs() {
+                        msix.set_jobs(jobs);
+                    }
                     if let Some(icon) = env.icon() {
                         msix.add_icon(icon)?;
                     }
                     // TODO: *.pri
+                    let strings = &env.config().windows().strings;
+                    if !strings.is_empty() {
+                        msix.add_strings(strings)?;
+                    }
 
                     msix.add_file(
                         &main,
@@ -327,3 +510,452 @@ pub fn build(env: &BuildEnv) -> Result<()> {
 
     Ok(())
 }
+
+/// Resolves `android.dependencies` with maven, returning the paths of the
+/// downloaded `aar` and `jar` artifacts.
+fn resolve_android_dependencies(env: &BuildEnv, manager: &DownloadManager) -> Result<Vec<PathBuf>> {
+    let mut maven = Maven::new(env.cache_dir().join("maven"), manager)?;
+    maven.add_repository("https://maven.google.com");
+    maven.add_repository("https://repo1.maven.org/maven2");
+    let mut artifacts = vec![];
+    for dep in &env.config().android().dependencies {
+        let parts = dep.split(':').collect::<Vec<_>>();
+        let [group, name, version] = parts[..] else {
+            anyhow::bail!(
+                "invalid dependency `{}`, expected `group:name:version`",
+                dep
+            );
+        };
+        let version = version
+            .parse()
+            .with_context(|| format!("invalid version of dependency `{}`", dep))?;
+        for artifact in maven.resolve(Package::new(group, name), version)? {
+            if !artifacts.contains(&artifact) {
+                artifacts.push(artifact);
+            }
+        }
+    }
+    Ok(artifacts)
+}
+
+/// Compiles `jars` to `out/classes.dex` with `d8` from the android build tools.
+fn dex(env: &BuildEnv, jars: &[PathBuf], out: &Path) -> Result<()> {
+    std::fs::create_dir_all(out)?;
+    let mut cmd = Command::new(find_d8()?);
+    cmd.arg(if env.target().opt() == Opt::Release {
+        "--release"
+    } else {
+        "--debug"
+    })
+    .arg("--min-api")
+    .arg(env.min_sdk_version().to_string())
+    .arg("--lib")
+    .arg(env.android_jar())
+    .arg("--output")
+    .arg(out)
+    .args(jars);
+    anyhow::ensure!(cmd.status()?.success(), "failed to run d8");
+    anyhow::ensure!(
+        !out.join("classes2.dex").exists(),
+        "dependencies need multidex, which is only supported by gradle builds"
+    );
+    Ok(())
+}
+
+fn find_d8() -> Result<PathBuf> {
+    let d8 = if cfg!(windows) { "d8.bat" } else { "d8" };
+    if let Ok(path) = which::which(d8) {
+        return Ok(path);
+    }
+    let home = std::env::var("ANDROID_HOME")
+        .or_else(|_| std::env::var("ANDROID_SDK_ROOT"))
+        .map_err(|_| XbuildError::MissingTool(d8.into()))?;
+    let mut candidates = std::fs::read_dir(Path::new(&home).join("build-tools"))?
+        .filter_map(|entry| Some(entry.ok()?.path().join(d8)))
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates
+        .pop()
+        .ok_or_else(|| XbuildError::MissingTool(d8.into()).into())
+}
+
+/// Prints a generated directory kept by `--keep-intermediates`.
+pub(crate) fn print_intermediates(env: &BuildEnv, dir: &Path) {
+    if env.keep_intermediates() {
+        println!("intermediates: {}", dir.display());
+    }
+}
+
+/// Describes everything packaged into an apk except for the libraries, an
+/// apk built from the same inputs only needs its libraries replaced.
+fn apk_inputs(env: &BuildEnv) -> Result<String> {
+    let mut inputs = format!(
+        "{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{}\n",
+        env.config().android().manifest,
+        env.config().android().theme,
+        env.config().android().icon_densities,
+        env.compression(),
+        env.compress_assets(),
+        env.exclude(),
+        env.android_jar().display(),
+    );
+    let mut paths = env
+        .icon()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .collect::<Vec<_>>();
+    paths.extend(env.android_resources());
+    for asset in &env.config().android().assets {
+        inputs.push_str(&format!("{:?}\n", asset));
+        paths.push(env.assets_dir().join(asset.path()));
+    }
+    for path in paths {
+        let modified = if path.exists() {
+            Some(newest_modification(&path)?)
+        } else {
+            None
+        };
+        inputs.push_str(&format!("{} {:?}\n", path.display(), modified));
+    }
+    Ok(inputs)
+}
+
+fn newest_modification(path: &Path) -> Result<SystemTime> {
+    let mut newest = std::fs::metadata(path)?.modified()?;
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            newest = newest.max(newest_modification(&entry?.path())?);
+        }
+    }
+    Ok(newest)
+}
+
+/// Prints what [`build`] would do for `env` without building anything.
+pub fn dry_run(env: &BuildEnv) -> Result<()> {
+    let target = env.target();
+    println!("platform: {}", target.platform());
+    println!("format: {}", target.format());
+    println!("opt: {}", target.opt());
+    match target.platform() {
+        Platform::Android => {
+            println!("android ndk: {}", env.android_ndk().display());
+            println!("android jar: {}", env.android_jar().display());
+        }
+        Platform::Ios => println!("ios sdk: {}", env.ios_sdk().display()),
+        Platform::Macos => println!("macos sdk: {}", env.macos_sdk().display()),
+        Platform::Windows => println!("windows sdk: {}", env.windows_sdk().display()),
+        Platform::Linux => {}
+    }
+    let bin_target = target.platform() != Platform::Android;
+    for target in target.compile_targets() {
+        let triple = target.rust_triple()?;
+        println!("target {} {}", target.arch(), triple);
+        let arch_dir = env.platform_dir().join(target.arch().to_string());
+        match env.cargo_build(target, &arch_dir.join("cargo")) {
+            Ok(mut cargo) => {
+                if !bin_target {
+                    cargo.arg("--lib");
+                }
+                println!("  {}", format_command(&cargo.into_command()));
+            }
+            Err(err) => println!("  cargo: {:#}", err),
+        }
+    }
+    println!("output: {}", env.output().display());
+    Ok(())
+}
+
+/// Checks that the build can run without compiling or packaging anything.
+/// The manifest and the signing material were already validated by
+/// [`BuildEnv::new`], this checks the rust targets, sdks and tools.
+pub fn check(env: &BuildEnv) -> Result<()> {
+    let mut failed = 0;
+    let mut report = |name: &str, result: Result<String>| match result {
+        Ok(status) => println!("{:20} {}", name, status),
+        Err(err) => {
+            println!("{:20} error: {:#}", name, err);
+            failed += 1;
+        }
+    };
+    let target = env.target();
+    report("manifest", Ok("valid".into()));
+
+    let installed = Command::new("rustup")
+        .arg("target")
+        .arg("list")
+        .arg("--installed")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+    for compile_target in target.compile_targets() {
+        let result = (|| {
+            let triple = compile_target.rust_triple()?;
+            if compile_target.is_host()? {
+                return Ok(format!("{} (host)", triple));
+            }
+            let installed = installed
+                .as_ref()
+                .map_err(|err| anyhow::anyhow!("rustup: {}", err))?;
+            anyhow::ensure!(
+                installed.lines().any(|line| line.trim() == triple),
+                "{} is not installed, run `rustup target add {}`",
+                triple,
+                triple
+            );
+            Ok(triple.to_string())
+        })();
+        report(&format!("target {}", compile_target.arch()), result);
+    }
+
+    let mut sdks = vec![];
+    match target.platform() {
+        Platform::Android => {
+            if let Some((ndk, var)) = env.android_ndk_override() {
+                let result = if ndk.exists() {
+                    Ok(format!("{} ({})", ndk.display(), var))
+                } else {
+                    Err(anyhow::anyhow!(
+                        "{} set by {} doesn't exist",
+                        ndk.display(),
+                        var
+                    ))
+                };
+                report("android ndk", result);
+            } else {
+                sdks.push(("android ndk", env.android_ndk()));
+            }
+            sdks.push(("android jar", env.android_jar()));
+        }
+        Platform::Ios => sdks.push(("ios sdk", env.ios_sdk())),
+        Platform::Macos if Platform::host()? != Platform::Macos => {
+            sdks.push(("macos sdk", env.macos_sdk()))
+        }
+        Platform::Windows if Platform::host()? != Platform::Windows => {
+            sdks.push(("windows sdk", env.windows_sdk()))
+        }
+        _ => {}
+    }
+    for (name, path) in sdks {
+        let result = if path.exists() {
+            Ok(path.display().to_string())
+        } else if env.offline() {
+            Err(anyhow::anyhow!(
+                "{} not in cache; run once online",
+                path.display()
+            ))
+        } else {
+            Ok("downloaded by x build".into())
+        };
+        report(name, result);
+    }
+
+    let mut tools = vec![];
+    let android = env.config().android();
+    if target.platform() == Platform::Android && android.gradle {
+        tools.push("gradle");
+    } else if target.platform() == Platform::Android && !android.dependencies.is_empty() {
+        report("d8", find_d8().map(|path| path.display().to_string()));
+    }
+    if target.format() == Format::Appimage {
+        tools.push("mksquashfs");
+    }
+    if env.uses_container() {
+        tools.push("docker");
+    }
+    for tool in tools {
+        let result = which::which(tool)
+            .map(|path| path.display().to_string())
+            .map_err(|_| anyhow::anyhow!("{} not found", tool));
+        report(tool, result);
+    }
+
+    if target.format().is_signed() {
+        let status = if target.signer().is_some() {
+            "signing key loaded".to_string()
+        } else if let Some(identity) = target.signing_identity() {
+            format!("keychain identity {}", identity)
+        } else if target.opt() == Opt::Release {
+            "warning: signing with the debug key".to_string()
+        } else {
+            "debug key".to_string()
+        };
+        report("signing", Ok(status));
+    }
+    anyhow::ensure!(failed == 0, "{} check(s) failed", failed);
+    Ok(())
+}
+
+/// Writes the platform manifest as resolved from `manifest.yaml` and `Cargo.toml`
+/// to `out` as yaml.
+pub fn write_manifest(env: &BuildEnv, out: &Path) -> Result<()> {
+    let config = env.config();
+    let manifest = match env.target().platform() {
+        Platform::Android => serde_yaml::to_string(&config.android().manifest)?,
+        Platform::Ios => serde_yaml::to_string(&config.ios().info)?,
+        Platform::Macos => serde_yaml::to_string(&config.macos().info)?,
+        Platform::Windows => serde_yaml::to_string(&config.windows().manifest)?,
+        Platform::Linux => anyhow::bail!("linux builds have no platform manifest"),
+    };
+    std::fs::write(out, manifest)?;
+    Ok(())
+}
+
+fn format_command(cmd: &Command) -> String {
+    let mut line = String::new();
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            line.push_str(&format!(
+                "{}={:?} ",
+                key.to_string_lossy(),
+                value.to_string_lossy()
+            ));
+        }
+    }
+    line.push_str(&cmd.get_program().to_string_lossy());
+    for arg in cmd.get_args() {
+        line.push(' ');
+        line.push_str(&arg.to_string_lossy());
+    }
+    line
+}
+
+/// Saves the unstripped `artifact` into `dir` by arch and build id, then
+/// strips it. Unchanged artifacts were already saved by a previous build.
+fn strip_symbols(target: CompileTarget, artifact: &Path, dir: &Path) -> Result<()> {
+    let platform = target.platform();
+    if platform == Platform::Windows {
+        tracing::warn!(
+            "--strip-symbols-to is not supported for windows, which keeps debug info in a pdb"
+        );
+        return Ok(());
+    }
+    let name = artifact.file_name().unwrap().to_str().unwrap();
+    let build_id = xcommon::llvm::build_id(artifact)?
+        .with_context(|| format!("{} has no build id", artifact.display()))?;
+    let out_dir = dir.join(target.arch().to_string()).join(build_id);
+    let saved = out_dir.join(name);
+    let debug_file = out_dir.join(format!("{}.debug", name));
+    if !saved.exists() {
+        std::fs::create_dir_all(&out_dir)?;
+        std::fs::copy(artifact, &saved)?;
+        if platform == Platform::Android {
+            xcommon::llvm::only_keep_debug(artifact, &debug_file)?;
+        }
+    }
+    let macho = matches!(platform, Platform::Macos | Platform::Ios);
+    let debug_file = (platform == Platform::Android).then_some(debug_file.as_path());
+    xcommon::llvm::strip(artifact, macho, debug_file)
+}
+
+/// Debug info of `artifact` split off by `--split-debug`, next to it.
+fn debug_file(platform: Platform, artifact: &Path) -> PathBuf {
+    let name = artifact.file_name().unwrap().to_str().unwrap();
+    match platform {
+        Platform::Macos => artifact.with_file_name(format!("{}.dSYM", name)),
+        Platform::Windows => {
+            // rustc names the pdb after the crate
+            let stem = artifact.file_stem().unwrap().to_str().unwrap();
+            artifact.with_file_name(format!("{}.pdb", stem.replace('-', "_")))
+        }
+        _ => artifact.with_file_name(format!("{}.debug", name)),
+    }
+}
+
+/// Moves the debug info of `artifact` into its [`debug_file`] and strips it.
+/// Artifacts cargo didn't relink were already split.
+fn split_debug(target: CompileTarget, artifact: &Path) -> Result<()> {
+    let platform = target.platform();
+    let debug = debug_file(platform, artifact);
+    if platform == Platform::Windows {
+        // the msvc linker already writes the debug info into the pdb
+        return Ok(());
+    }
+    let build_id = xcommon::llvm::build_id(artifact)?;
+    if build_id.is_some() && debug.exists() && xcommon::llvm::build_id(&debug)? == build_id {
+        return Ok(());
+    }
+    if platform == Platform::Macos {
+        xcommon::llvm::dsymutil(artifact, &debug)?;
+        xcommon::llvm::strip(artifact, true, None)
+    } else {
+        xcommon::llvm::only_keep_debug(artifact, &debug)?;
+        xcommon::llvm::strip(artifact, false, Some(&debug))
+    }
+}
+
+/// Copies a `.pdb` file or `.dSYM` bundle into `dir`, next to the artifact.
+fn copy_debug_file(debug: &Path, dir: &Path) -> Result<()> {
+    ensure!(
+        debug.exists(),
+        "{} not found, was it built with debug info?",
+        debug.display()
+    );
+    let dest = dir.join(debug.file_name().unwrap());
+    if debug.is_dir() {
+        if dest.exists() {
+            std::fs::remove_dir_all(&dest)?;
+        }
+        std::fs::create_dir_all(&dest)?;
+        xcommon::copy_dir_all(debug, &dest)
+    } else {
+        std::fs::copy(debug, dest)?;
+        Ok(())
+    }
+}
+
+/// Warns about NDK symbols imported by `lib` that don't exist on the oldest
+/// supported android version. The library links fine but fails to load on
+/// those devices.
+fn warn_symbols_above_min_sdk(lib: &Path, sysroot_libs: &Path, min_sdk: u32) {
+    let name = lib.file_name().unwrap().to_string_lossy();
+    match crate::ndk::symbols_above_min_sdk(lib, sysroot_libs, min_sdk) {
+        Ok(symbols) => {
+            for (symbol, level) in symbols {
+                tracing::warn!(
+                    "{} uses `{}` from android api {}, but min_sdk_version is {}",
+                    name,
+                    symbol,
+                    level,
+                    min_sdk
+                );
+            }
+        }
+        Err(err) => tracing::warn!("Failed to check the NDK symbols of {}: {:?}", name, err),
+    }
+}
+
+#[test]
+fn test_symbol_warnings_shown_by_default() {
+    use clap::Parser;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("xbuild-ndk-symbols-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("21")).unwrap();
+    let args = crate::BuildArgs::try_parse_from(["x"]).unwrap();
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(args.log_filter()))
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::subscriber::with_default(subscriber, || {
+        warn_symbols_above_min_sdk(&dir.join("libmissing.so"), &dir, 21);
+    });
+    let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(output.contains("Failed to check the NDK symbols of libmissing.so"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
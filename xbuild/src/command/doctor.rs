@@ -1,6 +1,8 @@
 use anyhow::Result;
-use std::path::PathBuf;
+use reqwest::blocking::Client;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub struct Doctor {
@@ -162,7 +164,131 @@ impl Check {
     }
 }
 
-pub fn doctor() {
+fn android_ndk() -> String {
+    let (ndk, var) = if let Some(ndk) = crate::ndk::env_ndk() {
+        ndk
+    } else {
+        return "not set, the ndk is downloaded by x build".into();
+    };
+    let (version, error) = match crate::ndk::NdkVersion::detect(&ndk) {
+        Ok(Some(version)) => (version.to_string(), version.check().err()),
+        Ok(None) => ("unknown".into(), None),
+        Err(err) => ("unknown".into(), Some(err)),
+    };
+    let mut line = format!("{:20}{} (${})", version, ndk.display(), var);
+    if let Some(err) = error {
+        line.push_str(&format!("\n{:21}{}", "", err));
+    }
+    line
+}
+
+/// Hosts `x build` downloads sdks, ndks, rust targets and maven packages from.
+const ENDPOINTS: &[(&str, &str)] = &[
+    (
+        "xbuild releases",
+        "https://github.com/rust-mobile/xbuild/releases",
+    ),
+    (
+        "android sdk",
+        "https://dl.google.com/android/repository/repository2-1.xml",
+    ),
+    ("google maven", "https://maven.google.com/web/index.html"),
+    ("maven central", "https://repo.maven.apache.org/maven2/"),
+    (
+        "rustup",
+        "https://static.rust-lang.org/rustup/release-stable.toml",
+    ),
+];
+
+/// Sends a `HEAD` request to every endpoint in parallel. The client picks up
+/// the same proxy environment variables as the downloads of `x build`.
+fn network() -> Vec<(&'static str, String)> {
+    let client = match Client::builder().timeout(Duration::from_secs(5)).build() {
+        Ok(client) => client,
+        Err(err) => return vec![("client", format!("failed to create: {}", err))],
+    };
+    std::thread::scope(|scope| {
+        let checks = ENDPOINTS
+            .iter()
+            .map(|(name, url)| {
+                let client = &client;
+                let check = scope.spawn(move || match client.head(*url).send() {
+                    Ok(resp) if resp.status().is_success() => "reachable".to_string(),
+                    Ok(resp) => format!("blocked, HEAD {} returned {}", url, resp.status()),
+                    Err(err) => {
+                        let mut cause: &dyn std::error::Error = &err;
+                        while let Some(source) = cause.source() {
+                            cause = source;
+                        }
+                        format!("unreachable, {} ({})", url, cause)
+                    }
+                });
+                (*name, check)
+            })
+            .collect::<Vec<_>>();
+        checks
+            .into_iter()
+            .map(|(name, check)| (name, check.join().unwrap()))
+            .collect()
+    })
+}
+
+/// Artifacts `x build` requires in the cache when building with `--offline`.
+fn cache(cache_dir: &Path) -> Vec<(String, String)> {
+    let mut artifacts = vec![];
+    for sdk in ["Android.ndk", "Windows.sdk", "MacOSX.sdk", "iPhoneOS.sdk"] {
+        artifacts.push((sdk.to_string(), cache_dir.join(sdk)));
+    }
+    let platforms = cache_dir.join("Android.sdk").join("platforms");
+    let mut platforms = std::fs::read_dir(&platforms)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|platform| platform.join("android.jar").exists())
+        .collect::<Vec<_>>();
+    platforms.sort();
+    if platforms.is_empty() {
+        artifacts.push(("android.jar".into(), PathBuf::new()));
+    }
+    for platform in platforms {
+        let name = platform.file_name().unwrap().to_string_lossy();
+        artifacts.push((format!("{}.jar", name), platform.join("android.jar")));
+    }
+    artifacts
+        .into_iter()
+        .map(|(name, path)| {
+            let status = if !path.as_os_str().is_empty() && path.exists() {
+                format!("{:20}{}", "cached", path.display())
+            } else {
+                "missing, run once online".into()
+            };
+            (name, status)
+        })
+        .collect()
+}
+
+pub fn doctor(offline: bool) {
     let doctor = Doctor::default();
     print!("{}", doctor);
+    println!("{:-^1$}", "android ndk", 60);
+    println!("{:20} {}", "ndk", android_ndk());
+    println!();
+    if offline {
+        let cache_dir = dirs::cache_dir().unwrap().join("x");
+        println!("{:-^1$}", "cache", 60);
+        for (name, status) in cache(&cache_dir) {
+            println!("{:20} {}", name, status);
+        }
+    } else {
+        println!("{:-^1$}", "network", 60);
+        for var in ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"] {
+            if let Some(proxy) = std::env::var_os(var) {
+                println!("{:20} {} (${})", "proxy", proxy.to_string_lossy(), var);
+                break;
+            }
+        }
+        for (name, status) in network() {
+            println!("{:20} {}", name, status);
+        }
+    }
 }
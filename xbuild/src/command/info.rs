@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use apk::Apk;
+use quick_xml::events::Event;
+use std::io::Read;
+use std::path::Path;
+use xcommon::{CertInfo, Signer};
+
+fn print_field(name: &str, value: Option<&str>) {
+    println!("{:20}{}", format!("{}:", name), value.unwrap_or("-"));
+}
+
+fn print_list(name: &str, values: &[String]) {
+    if values.is_empty() {
+        print_field(name, None);
+    } else {
+        print_field(name, Some(&values.join(", ")));
+    }
+}
+
+fn apk_info(path: &Path) -> Result<()> {
+    let info = Apk::info(path)?;
+    print_field("package", info.package.as_deref());
+    print_field("version code", info.version_code.as_deref());
+    print_field("version name", info.version_name.as_deref());
+    print_field("min sdk", info.min_sdk_version.as_deref());
+    print_field("target sdk", info.target_sdk_version.as_deref());
+    print_list("abis", &info.abis);
+    print_list("permissions", &info.permissions);
+    match Apk::verify(path) {
+        Ok(certs) => {
+            let fingerprints = certs
+                .iter()
+                .map(xcommon::cert_fingerprint)
+                .collect::<Result<Vec<_>>>()?;
+            print_list("sha256 fingerprint", &fingerprints);
+        }
+        Err(err) => print_field("signature", Some(&format!("invalid ({})", err))),
+    }
+    Ok(())
+}
+
+fn plist_info(info: plist::Value) -> Result<()> {
+    let info = info.as_dictionary().context("invalid Info.plist")?;
+    let field = |key: &str| info.get(key).and_then(|value| value.as_string());
+    print_field("bundle identifier", field("CFBundleIdentifier"));
+    print_field("name", field("CFBundleName"));
+    print_field("version", field("CFBundleShortVersionString"));
+    print_field("build", field("CFBundleVersion"));
+    let minimum = field("MinimumOSVersion").or_else(|| field("LSMinimumSystemVersion"));
+    print_field("minimum os", minimum);
+    Ok(())
+}
+
+fn app_info(path: &Path) -> Result<()> {
+    let plist = if path.join("Contents").exists() {
+        path.join("Contents").join("Info.plist")
+    } else {
+        path.join("Info.plist")
+    };
+    plist_info(plist::Value::from_file(plist)?)
+}
+
+fn ipa_info(path: &Path) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let name = archive
+        .file_names()
+        .find(|name| {
+            let mut parts = name.split('/');
+            parts.next() == Some("Payload")
+                && parts.next().map(|app| app.ends_with(".app")) == Some(true)
+                && parts.next() == Some("Info.plist")
+                && parts.next().is_none()
+        })
+        .context("ipa contains no Payload/*.app/Info.plist")?
+        .to_string();
+    let mut buf = vec![];
+    archive.by_name(&name)?.read_to_end(&mut buf)?;
+    plist_info(plist::Value::from_reader(std::io::Cursor::new(buf))?)
+}
+
+fn msix_info(path: &Path) -> Result<()> {
+    let manifest = xcommon::extract_zip_file(path, "AppxManifest.xml")?;
+    let mut reader = quick_xml::Reader::from_reader(&manifest[..]);
+    let mut buf = vec![];
+    let mut capabilities = vec![];
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(el) | Event::Empty(el) => {
+                let name = el.local_name();
+                let attr = |key: &str| -> Result<Option<String>> {
+                    let attr = el.try_get_attribute(key)?;
+                    Ok(attr.map(|attr| String::from_utf8_lossy(&attr.value).into_owned()))
+                };
+                match name.as_ref() {
+                    b"Identity" => {
+                        print_field("name", attr("Name")?.as_deref());
+                        print_field("version", attr("Version")?.as_deref());
+                        print_field("publisher", attr("Publisher")?.as_deref());
+                        print_field("architecture", attr("ProcessorArchitecture")?.as_deref());
+                    }
+                    b"Capability" | b"DeviceCapability" => capabilities.extend(attr("Name")?),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    print_list("capabilities", &capabilities);
+    Ok(())
+}
+
+/// Prints metadata of a built artifact without installing it.
+pub fn info(path: &Path) -> Result<()> {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    match ext {
+        "apk" => apk_info(path),
+        "app" => app_info(path),
+        "ipa" => ipa_info(path),
+        "msix" => msix_info(path),
+        _ => anyhow::bail!(
+            "unsupported artifact `{}`, expected an apk, app, ipa or msix",
+            path.display()
+        ),
+    }
+}
+
+/// Prints the certificate of the key artifacts are signed with.
+pub fn cert_info(signer: Option<&Signer>, signing_identity: Option<&str>) -> Result<()> {
+    if let Some(signer) = signer {
+        print!("{}", CertInfo::new(signer.cert())?);
+    } else if let Some(identity) = signing_identity {
+        print_field("keychain identity", Some(identity));
+    } else {
+        println!("no signing key, apks and msix packages are signed with the debug key");
+    }
+    Ok(())
+}
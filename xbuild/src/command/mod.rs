@@ -1,17 +1,31 @@
 use crate::cargo::CrateType;
+use crate::config::{self, Config};
 use crate::devices::Device;
 use crate::{BuildEnv, CompileTarget, Platform};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use app_store_connect::UnifiedApiKey;
+use serde_json::json;
 use std::path::Path;
+use std::process::ExitStatus;
+use std::time::Duration;
+use xcommon::Signer;
 
 mod build;
 mod doctor;
+mod info;
 mod new;
+mod resign;
+mod targets;
+mod watch;
 
-pub use build::build;
+pub(crate) use build::print_intermediates;
+pub use build::{build, check, dry_run, write_manifest};
 pub use doctor::doctor;
+pub use info::{cert_info, info};
 pub use new::new;
+pub use resign::resign;
+pub use targets::list_targets;
+pub use watch::watch;
 
 pub fn devices() -> Result<()> {
     for device in Device::list()? {
@@ -26,14 +40,20 @@ pub fn devices() -> Result<()> {
     Ok(())
 }
 
-pub fn run(env: &BuildEnv) -> Result<()> {
+/// Runs the app on the target device. Returns the exit status of the app
+/// when it ran on the host, other devices don't report it.
+pub fn run(
+    env: &BuildEnv,
+    boot_timeout: Duration,
+    url: Option<&str>,
+    profile_startup: bool,
+) -> Result<Option<ExitStatus>> {
     let out = env.executable();
     if let Some(device) = env.target().device() {
-        device.run(env, &out)?;
+        device.run(env, &out, boot_timeout, url, profile_startup)
     } else {
         anyhow::bail!("no device specified");
     }
-    Ok(())
 }
 
 pub fn lldb(env: &BuildEnv) -> Result<()> {
@@ -63,6 +83,45 @@ pub fn lldb(env: &BuildEnv) -> Result<()> {
     Ok(())
 }
 
+/// Writes the JSON schema of `manifest.yaml` to `output` or stdout.
+pub fn schema(output: Option<&Path>) -> Result<()> {
+    let schema = serde_json::to_string_pretty(&Config::json_schema())?;
+    if let Some(output) = output {
+        std::fs::write(output, schema)?;
+    } else {
+        println!("{}", schema);
+    }
+    Ok(())
+}
+
+/// Digital asset links statement verifying the android app links of
+/// `package` for the certificates with the SHA-256 `fingerprints`.
+fn assetlinks_json(package: &str, fingerprints: &[String]) -> serde_json::Value {
+    json!([{
+        "relation": ["delegate_permission/common.handle_all_urls"],
+        "target": {
+            "namespace": "android_app",
+            "package_name": package,
+            "sha256_cert_fingerprints": fingerprints,
+        },
+    }])
+}
+
+/// Writes the `.well-known/assetlinks.json` of `package` signed with `signer`
+/// to `output` or stdout.
+pub fn assetlinks(signer: Option<&Signer>, package: &str, output: Option<&Path>) -> Result<()> {
+    config::validate_app_id(Platform::Android, package)?;
+    let signer = signer.context("assetlinks requires --pem or X_PEM")?;
+    let fingerprint = xcommon::cert_fingerprint(signer.cert())?;
+    let json = serde_json::to_string_pretty(&assetlinks_json(package, &[fingerprint]))?;
+    if let Some(output) = output {
+        std::fs::write(output, json)?;
+    } else {
+        println!("{}", json);
+    }
+    Ok(())
+}
+
 pub fn create_apple_api_key(
     issuer_id: &str,
     key_id: &str,
@@ -72,3 +131,12 @@ pub fn create_apple_api_key(
     UnifiedApiKey::from_ecdsa_pem_path(issuer_id, key_id, private_key)?.write_json_file(api_key)?;
     Ok(())
 }
+
+#[test]
+fn test_assetlinks_json() {
+    let json = assetlinks_json("com.example.app", &["AB:CD".into()]);
+    assert_eq!(
+        json.to_string(),
+        r#"[{"relation":["delegate_permission/common.handle_all_urls"],"target":{"namespace":"android_app","package_name":"com.example.app","sha256_cert_fingerprints":["AB:CD"]}}]"#
+    );
+}
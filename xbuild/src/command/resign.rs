@@ -0,0 +1,54 @@
+use anyhow::{Context, Result};
+use apk::Apk;
+use msix::Msix;
+use std::path::Path;
+use xcommon::ZipCompression;
+
+/// Replaces the signature of a built artifact without rebuilding it. Without
+/// a signer apks and msix packages are signed with the debug key.
+pub fn resign(path: &Path, pem: Option<&Path>, signing_identity: Option<&str>) -> Result<()> {
+    anyhow::ensure!(path.exists(), "{} doesn't exist", path.display());
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    anyhow::ensure!(
+        signing_identity.is_none() || matches!(ext, "app" | "dmg"),
+        "--signing-identity is only supported for apple artifacts"
+    );
+    let signer = crate::load_signer(pem)?;
+    match ext {
+        "apk" => {
+            println!("signing {}", path.display());
+            let idsig = path.with_extension("apk.idsig");
+            Apk::sign(path, signer.clone())?;
+            // a v4 signature of the old signer would fail the incremental install
+            if idsig.exists() {
+                Apk::sign_v4(path, signer)?;
+            }
+            let certs = Apk::verify(path)?;
+            let fingerprint =
+                xcommon::cert_fingerprint(certs.first().context("apk is not signed")?)?;
+            println!("sha256 fingerprint: {}", fingerprint);
+        }
+        "msix" => {
+            println!("signing {}", path.display());
+            Msix::resign(path, signer, ZipCompression::Best)?;
+        }
+        "app" | "dmg" => {
+            if let Some(identity) = signing_identity {
+                let identity = appbundle::keychain_identity(identity)?;
+                appbundle::resign_with_identity(path, &identity)?;
+            } else {
+                let signer = signer
+                    .context("re-signing apple artifacts requires --pem or --signing-identity")?;
+                appbundle::resign(path, &signer)?;
+            }
+        }
+        _ => anyhow::bail!(
+            "unsupported artifact `{}`, expected an apk, msix, app or dmg",
+            path.display()
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use crate::{Arch, CompileTarget, Format, Opt, Platform};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct Target {
+    platform: String,
+    arch: String,
+    format: String,
+    triple: &'static str,
+    /// Tools from `x doctor` that are needed but not installed
+    missing_tools: Vec<&'static str>,
+    /// Reason the target can't be built on this host
+    unsupported: Option<&'static str>,
+}
+
+/// Tools needed on the host to build `format` for `platform`.
+fn required_tools(host: Platform, platform: Platform, format: Format) -> Vec<&'static str> {
+    let mut tools = vec![];
+    match platform {
+        Platform::Android => tools.extend(["clang", "llvm-ar", "lld"]),
+        Platform::Ios | Platform::Macos if host != Platform::Macos => {
+            tools.extend(["clang", "llvm-ar", "lld"])
+        }
+        Platform::Windows if host != Platform::Windows => {
+            tools.extend(["clang", "llvm-lib", "lld-link"])
+        }
+        _ => {}
+    }
+    if format == Format::Appimage && !cfg!(feature = "squashfs") {
+        tools.push("mksquashfs");
+    }
+    tools
+}
+
+fn targets() -> Result<Vec<Target>> {
+    let host = Platform::host()?;
+    let mut targets = vec![];
+    for platform in Platform::value_variants() {
+        for arch in Arch::value_variants() {
+            let target = CompileTarget::new(*platform, *arch, Opt::Debug);
+            let triple = if let Ok(triple) = target.rust_triple() {
+                triple
+            } else {
+                continue;
+            };
+            let unsupported = if *platform == Platform::Linux && host != Platform::Linux {
+                Some("cross compiling to linux is not yet supported")
+            } else {
+                None
+            };
+            for format in Format::valid_for(*platform) {
+                let missing_tools = required_tools(host, *platform, *format)
+                    .into_iter()
+                    .filter(|tool| which::which(tool).is_err())
+                    .collect();
+                targets.push(Target {
+                    platform: platform.to_string(),
+                    arch: arch.to_string(),
+                    format: format.to_string(),
+                    triple,
+                    missing_tools,
+                    unsupported,
+                });
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// Prints every platform, arch and format combination `x build` accepts.
+pub fn list_targets(json: bool) -> Result<()> {
+    let targets = targets()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&targets)?);
+        return Ok(());
+    }
+    println!(
+        "{:10}{:8}{:12}{:30}notes",
+        "platform", "arch", "format", "triple"
+    );
+    for target in targets {
+        let notes = if let Some(reason) = target.unsupported {
+            reason.to_string()
+        } else if !target.missing_tools.is_empty() {
+            format!("missing {}", target.missing_tools.join(", "))
+        } else {
+            String::new()
+        };
+        println!(
+            "{:10}{:8}{:12}{:30}{}",
+            target.platform, target.arch, target.format, target.triple, notes
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use crate::{BuildEnv, Platform};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::time::{Duration, SystemTime};
+
+/// How often the sources are scanned for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Changes are only acted on once the sources stopped changing for this long,
+/// so saving several files at once triggers a single rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Modification times of all files in the package, except for build outputs
+/// and hidden directories like `.git`.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Snapshot(BTreeMap<PathBuf, SystemTime>);
+
+impl Snapshot {
+    fn new(root: &Path, ignore: &Path) -> Result<Self> {
+        let mut snapshot = Self::default();
+        snapshot.scan(root, ignore)?;
+        Ok(snapshot)
+    }
+
+    /// Files removed while scanning, like the temporary files editors write
+    /// when saving, are skipped. Symlinks aren't followed so links to parent
+    /// directories don't recurse forever.
+    fn scan(&mut self, dir: &Path, ignore: &Path) -> Result<()> {
+        let entries = match std::fs::read_dir(dir) {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            entries => entries?,
+        };
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if path == ignore || entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = match std::fs::symlink_metadata(&path) {
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                metadata => metadata?,
+            };
+            if metadata.is_dir() {
+                self.scan(&path, ignore)?;
+            } else {
+                self.0.insert(path, metadata.modified()?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds and runs the app on the host, rebuilding and restarting it whenever
+/// the package sources change.
+///
+/// Ctrl-C is delivered to the whole process group, which stops the running
+/// app together with xbuild.
+pub fn watch(env: &BuildEnv, url: Option<&str>) -> Result<()> {
+    let device = if let Some(device) = env.target().device() {
+        device
+    } else {
+        anyhow::bail!("no device specified");
+    };
+    anyhow::ensure!(
+        device.is_host()
+            && matches!(
+                env.target().platform(),
+                Platform::Linux | Platform::Macos | Platform::Windows
+            ),
+        "--watch is only supported when running on the host"
+    );
+    let root = env.root_dir();
+    let target_dir = env.cargo().target_dir();
+    let mut snapshot = Snapshot::new(root, target_dir)?;
+    loop {
+        let mut child = match super::build(env) {
+            Ok(()) => Some(device.spawn(&env.executable(), url)?),
+            Err(err) => {
+                eprintln!("Error: {:?}", err);
+                None
+            }
+        };
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            if let Some(status) = child.as_mut().map(Child::try_wait).transpose()?.flatten() {
+                println!("app exited with {}, waiting for changes", status);
+                child = None;
+            }
+            let mut next = Snapshot::new(root, target_dir)?;
+            if next == snapshot {
+                continue;
+            }
+            loop {
+                std::thread::sleep(DEBOUNCE);
+                let settled = Snapshot::new(root, target_dir)?;
+                if settled == next {
+                    break;
+                }
+                next = settled;
+            }
+            snapshot = next;
+            break;
+        }
+        if let Some(mut child) = child {
+            child.kill().ok();
+            child.wait()?;
+        }
+        println!("sources changed, rebuilding");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_snapshot_skips_symlinks() {
+    let dir = std::env::temp_dir().join(format!("xbuild-watch-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src").join("main.rs"), "").unwrap();
+    xcommon::symlink(&dir, &dir.join("src").join("parent")).unwrap();
+    let snapshot = Snapshot::new(&dir, &dir.join("target")).unwrap();
+    let files = snapshot
+        .0
+        .keys()
+        .map(|path| path.strip_prefix(&dir).unwrap());
+    assert_eq!(
+        files.collect::<Vec<_>>(),
+        [Path::new("src/main.rs"), Path::new("src/parent")]
+    );
+    // entries vanishing during a scan are skipped
+    let mut snapshot = Snapshot::default();
+    snapshot.scan(&dir.join("removed"), &dir).unwrap();
+    assert!(snapshot.0.is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use xcommon::Exclude;
+
+/// Extensions of text assets a webview can be served pre-compressed.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "css", "csv", "htm", "html", "js", "json", "map", "mjs", "svg", "txt", "wasm", "xml",
+];
+
+/// Copies the asset `path` into `dir` and compresses the copy, so the
+/// compressed files don't end up next to the sources.
+pub fn stage_asset(path: &Path, dir: &Path, exclude: &Exclude) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .context("Asset must have file_name component")?;
+    let dest = dir.join(file_name);
+    if path.is_dir() {
+        std::fs::create_dir_all(&dest)?;
+        xcommon::copy_dir_filtered(path, &dest, exclude)?;
+    } else {
+        std::fs::create_dir_all(dir)?;
+        std::fs::copy(path, &dest)?;
+    }
+    compress_assets(&dest)?;
+    Ok(dest)
+}
+
+/// Writes `.gz` and `.br` siblings of the text files in `path`, which is a
+/// file or a directory. Siblings which aren't smaller than the file are
+/// skipped.
+pub fn compress_assets(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        let entries = std::fs::read_dir(path)?.collect::<std::io::Result<Vec<_>>>()?;
+        for entry in entries {
+            compress_assets(&entry.path())?;
+        }
+        return Ok(());
+    }
+    let is_text = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| TEXT_EXTENSIONS.contains(&&*ext.to_ascii_lowercase()))
+        .unwrap_or_default();
+    if is_text {
+        compress_file(path)
+            .with_context(|| format!("While compressing asset `{}`", path.display()))?;
+    }
+    Ok(())
+}
+
+fn compress_file(path: &Path) -> Result<()> {
+    let data = std::fs::read(path)?;
+    let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::best());
+    gz.write_all(&data)?;
+    write_sibling(path, "gz", &gz.finish()?, data.len())?;
+    let mut br = vec![];
+    brotli::BrotliCompress(&mut &data[..], &mut br, &Default::default())?;
+    write_sibling(path, "br", &br, data.len())
+}
+
+fn write_sibling(path: &Path, ext: &str, compressed: &[u8], len: usize) -> Result<()> {
+    let mut sibling = OsString::from(path);
+    sibling.push(".");
+    sibling.push(ext);
+    if compressed.len() < len {
+        std::fs::write(sibling, compressed)?;
+    } else {
+        std::fs::remove_file(sibling).ok();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_compress_assets() {
+    use std::io::Read;
+
+    let dir = std::env::temp_dir().join(format!("xbuild-compress-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(dir.join("web")).unwrap();
+    let html = "<p>hello world</p>\n".repeat(100);
+    std::fs::write(dir.join("web").join("index.html"), &html).unwrap();
+    std::fs::write(dir.join("web").join("main.js"), "x").unwrap();
+    std::fs::write(dir.join("web").join("image.png"), [0; 1000]).unwrap();
+
+    let staged = stage_asset(&dir.join("web"), &dir.join("staged"), &Exclude::default()).unwrap();
+    let gz = std::fs::read(staged.join("index.html.gz")).unwrap();
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&gz[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, html);
+    let br = std::fs::read(staged.join("index.html.br")).unwrap();
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&br[..], 4096)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, html);
+    assert!(!staged.join("main.js.gz").exists());
+    assert!(!staged.join("main.js.br").exists());
+    assert!(!staged.join("image.png.gz").exists());
+    assert!(!dir.join("web").join("index.html.gz").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,18 +1,33 @@
 use crate::cargo::manifest::{Inheritable, Manifest, Package};
-use crate::{Opt, Platform};
+use crate::{Arch, CompileTarget, Format, Opt, Platform};
 use anyhow::{Context, Result};
-use apk::manifest::{Activity, AndroidManifest, IntentFilter, MetaData};
-use apk::VersionCode;
+use apk::manifest::{
+    Activity, AndroidManifest, Application as AndroidApplication, IntentFilter, MetaData,
+    Permission,
+};
+use apk::{Density, VersionCode};
 use appbundle::InfoPlist;
+use appimage::SquashfsOptions;
+use clap::ValueEnum;
+use msix::manifest::{
+    Application, Capability, DefaultTile, Resource, TargetDeviceFamily, FULL_TRUST_ENTRY_POINT,
+};
 use msix::AppxManifest;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::path::{Path, PathBuf};
-use xcommon::ZipFileOptions;
+use xcommon::{ScalerFilter, ZipFileOptions};
 
 #[derive(Clone, Debug, Default)]
 pub struct Config {
     generic: GenericConfig,
+    output: OutputConfig,
+    build: BuildConfig,
+    network: NetworkConfig,
+    target: BTreeMap<String, TargetConfig>,
+    version: String,
     android: AndroidConfig,
     ios: IosConfig,
     linux: LinuxConfig,
@@ -25,18 +40,43 @@ impl Config {
         if !path.as_ref().exists() {
             return Ok(Default::default());
         }
-        let contents = std::fs::read_to_string(path.as_ref())?;
-        let config: RawConfig = serde_yaml::from_str(&contents)?;
+        let config = load_yaml(path.as_ref(), &mut vec![])?;
+        let config: RawConfig = serde_yaml::from_value(config)
+            .with_context(|| format!("invalid {}", path.as_ref().display()))?;
+        let root = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        let mut ios = config.ios.unwrap_or_default();
+        let mut macos = config.macos.unwrap_or_default();
+        let mut android = config.android.unwrap_or_default();
+        android.keystore_properties = match android.keystore_properties.take() {
+            Some(path) => Some(root.join(path)),
+            None => Some(root.join("keystore.properties")).filter(|path| path.exists()),
+        };
+        for template in [&mut ios.info_plist, &mut macos.info_plist]
+            .into_iter()
+            .flatten()
+        {
+            *template = root.join(&template);
+        }
         Ok(Self {
             generic: config.generic.unwrap_or_default(),
-            android: config.android.unwrap_or_default(),
-            ios: config.ios.unwrap_or_default(),
+            output: config.output.unwrap_or_default(),
+            build: config.build.unwrap_or_default(),
+            network: config.network.unwrap_or_default(),
+            target: config.target.unwrap_or_default(),
+            version: Default::default(),
+            android,
+            ios,
             linux: config.linux.unwrap_or_default(),
-            macos: config.macos.unwrap_or_default(),
+            macos,
             windows: config.windows.unwrap_or_default(),
         })
     }
 
+    /// JSON schema of `manifest.yaml`, usable by editors for autocompletion.
+    pub fn json_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(RawConfig)
+    }
+
     /// Selects a generic config value from [`GenericConfig`], platform-specific
     /// overrides first and otherwise falls back to a shared option in the root.
     pub fn select_generic<T: ?Sized>(
@@ -58,6 +98,83 @@ impl Config {
         self.select_generic(platform, |g| g.icon.as_deref())
     }
 
+    pub fn icon_filter(&self, platform: Platform) -> Option<IconFilter> {
+        self.select_generic(platform, |g| g.icon_filter.as_ref())
+            .copied()
+    }
+
+    pub fn env_file(&self, platform: Platform) -> Option<&Path> {
+        self.select_generic(platform, |g| g.env_file.as_deref())
+    }
+
+    pub fn build(&self) -> &BuildConfig {
+        &self.build
+    }
+
+    pub fn network(&self) -> &NetworkConfig {
+        &self.network
+    }
+
+    /// Overrides the android min sdk version.
+    pub fn set_android_min_sdk(&mut self, min_sdk: u32) {
+        self.android.manifest.sdk.min_sdk_version = Some(min_sdk);
+    }
+
+    /// Overrides the android min sdk version of one of several apks built
+    /// with `--min-sdk`, which get distinct version codes.
+    pub fn set_android_min_sdk_variant(&mut self, min_sdk: u32) {
+        self.set_android_min_sdk(min_sdk);
+        self.android.min_sdk_variant = Some(min_sdk);
+    }
+
+    /// Selects the `android.flavors` overlay called `name`.
+    pub fn set_android_flavor(&mut self, name: &str) -> Result<()> {
+        let flavor = self.android.flavors.remove(name).with_context(|| {
+            let names = self.android.flavors.keys().cloned().collect::<Vec<_>>();
+            format!(
+                "unknown flavor `{}`, manifest.yaml defines: {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+        self.android.flavor = Some(flavor);
+        Ok(())
+    }
+
+    /// Codegen and C/C++ options of the `target.<triple>` section.
+    pub fn target(&self, triple: &str) -> Option<&TargetConfig> {
+        self.target.get(triple)
+    }
+
+    /// 32-bit android triples of the `target` section, which 64-bit only
+    /// devices don't need.
+    pub fn android_32bit_targets(&self) -> Vec<&str> {
+        self.target
+            .keys()
+            .map(|triple| triple.as_str())
+            .filter(|triple| {
+                triple.ends_with("-androideabi") || triple.starts_with("i686-linux-android")
+            })
+            .collect()
+    }
+
+    pub fn debug_info(&self, platform: Platform) -> Option<DebugInfo> {
+        self.select_generic(platform, |g| g.debug_info.as_ref())
+            .copied()
+    }
+
+    /// Panic strategy of the platform. Mobile apps can't unwind across the
+    /// FFI boundary anyway, so release builds for android and ios abort.
+    pub fn panic(&self, platform: Platform, opt: Opt) -> Panic {
+        let default = match (platform, opt) {
+            (Platform::Android | Platform::Ios, Opt::Release) => Panic::Abort,
+            _ => Panic::Unwind,
+        };
+        self.select_generic(platform, |g| g.panic.as_ref())
+            .copied()
+            .unwrap_or(default)
+    }
+
     pub fn runtime_libs(&self, platform: Platform) -> Vec<PathBuf> {
         let generic = match platform {
             Platform::Android => &self.android.generic,
@@ -75,13 +192,45 @@ impl Config {
             .collect()
     }
 
+    /// Exclude patterns of the platform and the root combined.
+    pub fn exclude(&self, platform: Platform) -> Vec<&str> {
+        let generic = match platform {
+            Platform::Android => &self.android.generic,
+            Platform::Ios => &self.ios.generic,
+            Platform::Macos => &self.macos.generic,
+            Platform::Linux => &self.linux.generic,
+            Platform::Windows => &self.windows.generic,
+        };
+        generic
+            .exclude
+            .iter()
+            .chain(&self.generic.exclude)
+            .map(|pattern| pattern.as_str())
+            .collect()
+    }
+
     pub fn apply_rust_package(
         &mut self,
         manifest_package: &Package,
         workspace_manifest: Option<&Manifest>,
         opt: Opt,
+        app_id: Option<&str>,
     ) -> Result<()> {
+        if let Some(app_id) = app_id {
+            self.android.manifest.package = Some(app_id.into());
+            self.ios.info.cf_bundle_identifier = Some(app_id.into());
+            self.macos.info.cf_bundle_identifier = Some(app_id.into());
+            self.windows.manifest.identity.name = Some(app_id.into());
+        }
         // android
+        self.apply_android_profile(opt);
+        self.android
+            .application
+            .apply(&mut self.android.manifest.application);
+        apply_android_activities(
+            &self.android.activities,
+            &mut self.android.manifest.application,
+        )?;
         let wry = self.android.wry;
         if wry {
             self.android
@@ -127,10 +276,15 @@ impl Config {
             None => "".into(),
         };
 
+        self.version = package_version.clone();
         manifest
             .version_name
             .get_or_insert_with(|| package_version.clone());
-        if let Ok(code) = VersionCode::from_semver(&package_version) {
+        let manifest = &mut self.android.manifest;
+        if let Some(min_sdk) = self.android.min_sdk_variant {
+            manifest.version_code =
+                min_sdk_variant_code(manifest.version_code, &package_version, min_sdk)?;
+        } else if let Ok(code) = VersionCode::from_semver(&package_version) {
             manifest.version_code.get_or_insert_with(|| code.to_code(1));
         }
         let target_sdk_version = 33;
@@ -163,6 +317,13 @@ impl Config {
                 .theme
                 .get_or_insert_with(|| "@style/Theme.AppCompat.Light.NoActionBar".into());
         }
+        if let Some(theme) = self.android.theme.as_mut() {
+            let parent = application.theme.take();
+            theme.parent.get_or_insert_with(|| {
+                parent.unwrap_or_else(|| "@android:style/Theme.Material.Light.NoActionBar".into())
+            });
+            application.theme = Some(format!("@style/{}", AndroidThemeConfig::NAME));
+        }
         application
             .debuggable
             .get_or_insert_with(|| opt == Opt::Debug);
@@ -210,13 +371,26 @@ impl Config {
                 value: manifest_package.name.replace('-', "_"),
             });
         }
-        activity.intent_filters.push(IntentFilter {
-            actions: vec!["android.intent.action.MAIN".into()],
-            categories: vec!["android.intent.category.LAUNCHER".into()],
-            data: vec![],
+        let has_main = activity.intent_filters.iter().any(|filter| {
+            filter
+                .actions
+                .iter()
+                .any(|action| action == "android.intent.action.MAIN")
         });
+        if !has_main {
+            activity.intent_filters.push(IntentFilter {
+                actions: vec!["android.intent.action.MAIN".into()],
+                categories: vec!["android.intent.category.LAUNCHER".into()],
+                data: vec![],
+            });
+        }
+        self.apply_android_flavor();
 
         // ios
+        if let Some(template) = self.ios.info_plist.as_ref() {
+            let vars = build_settings(&self.ios.info, &manifest_package.name, &package_version);
+            apply_info_plist_template(&mut self.ios.info, template, &vars)?;
+        }
         let info = &mut self.ios.info;
         info.cf_bundle_identifier
             .get_or_insert_with(|| manifest_package.name.clone());
@@ -265,6 +439,10 @@ impl Config {
         iphone_orientations.push("UIInterfaceOrientationLandscapeRight".into());
 
         // macos
+        if let Some(template) = self.macos.info_plist.as_ref() {
+            let vars = build_settings(&self.macos.info, &manifest_package.name, &package_version);
+            apply_info_plist_template(&mut self.macos.info, template, &vars)?;
+        }
         let info = &mut self.macos.info;
         info.cf_bundle_name
             .get_or_insert_with(|| manifest_package.name.clone());
@@ -289,10 +467,268 @@ impl Config {
             .properties
             .description
             .get_or_insert(package_description);
+        self.apply_msix_config(&manifest_package.name);
+
+        Ok(())
+    }
+
+    /// Applies `windows.msix` and fills in the parts of the manifest every
+    /// package needs.
+    fn apply_msix_config(&mut self, name: &str) {
+        let msix = std::mem::take(&mut self.windows.msix);
+        let manifest = &mut self.windows.manifest;
+        manifest
+            .dependencies
+            .target_device_family
+            .extend(msix.device_families);
+        if manifest.dependencies.target_device_family.is_empty() {
+            manifest
+                .dependencies
+                .target_device_family
+                .push(Default::default());
+        }
+        if manifest.resources.resource.is_empty() {
+            manifest.resources.resource.push(Resource {
+                language: "en-US".into(),
+            });
+        }
+        if manifest.applications.application.is_empty() {
+            manifest.applications.application.push(Application {
+                // application ids may only contain ascii letters and digits
+                id: Some(name.chars().filter(char::is_ascii_alphanumeric).collect()),
+                ..Default::default()
+            });
+        }
+        let entry_point = msix
+            .entry_point
+            .as_deref()
+            .unwrap_or(FULL_TRUST_ENTRY_POINT);
+        for app in &mut manifest.applications.application {
+            if let Some(executable) = msix.executable.as_ref() {
+                app.executable = Some(executable.clone());
+            }
+            app.executable
+                .get_or_insert_with(|| format!("{}.exe", name));
+            app.entry_point.get_or_insert_with(|| entry_point.into());
+            let visual = &mut app.visual_elements;
+            visual.display_name.get_or_insert_with(|| {
+                manifest.properties.display_name.clone().unwrap_or_default()
+            });
+            visual
+                .description
+                .get_or_insert_with(|| manifest.properties.description.clone().unwrap_or_default());
+            visual
+                .background_color
+                .get_or_insert_with(|| "transparent".into());
+            visual
+                .logo_150x150
+                .get_or_insert_with(|| "Images\\Square150x150Logo.png".into());
+            visual
+                .logo_44x44
+                .get_or_insert_with(|| "Images\\Square44x44Logo.png".into());
+        }
+        manifest
+            .properties
+            .logo
+            .get_or_insert_with(|| "Images\\StoreLogo.png".into());
+        for name in &msix.capabilities {
+            if !manifest.capabilities.iter().any(|c| c.name() == name) {
+                manifest.capabilities.push(Capability::new(name));
+            }
+        }
+        let full_trust = manifest
+            .applications
+            .application
+            .iter()
+            .any(|app| app.entry_point.as_deref() == Some(FULL_TRUST_ENTRY_POINT));
+        if full_trust
+            && !manifest
+                .capabilities
+                .iter()
+                .any(|c| c.name() == "runFullTrust")
+        {
+            manifest.capabilities.push(Capability::new("runFullTrust"));
+        }
+        manifest.sort_capabilities();
+        self.apply_msix_locales(msix.locales);
+    }
+
+    /// Replaces the display name, description and tile short name with
+    /// `ms-resource:` references to the strings of every language, which
+    /// fall back to the manifest values.
+    fn apply_msix_locales(&mut self, locales: BTreeMap<String, MsixLocale>) {
+        if locales.is_empty() {
+            return;
+        }
+        let manifest = &mut self.windows.manifest;
+        let display_name = manifest.properties.display_name.clone().unwrap_or_default();
+        let description = manifest.properties.description.clone().unwrap_or_default();
+        let app = manifest.applications.application.first();
+        let short_name = app
+            .and_then(|app| app.visual_elements.default_tile.as_ref())
+            .and_then(|tile| tile.short_name.clone())
+            .unwrap_or_else(|| display_name.clone());
+        let default = MsixLocale {
+            display_name: Some(display_name),
+            short_name: Some(short_name),
+            description: Some(description),
+        };
+        let default_language = manifest
+            .resources
+            .resource
+            .first()
+            .map(|resource| resource.language.clone())
+            .unwrap_or_else(|| "en-US".into());
+        let mut strings = BTreeMap::new();
+        strings.insert(default_language.clone(), default.strings(&default));
+        for (language, locale) in &locales {
+            strings.insert(language.clone(), locale.strings(&default));
+        }
+        for language in std::iter::once(&default_language).chain(locales.keys()) {
+            if !manifest
+                .resources
+                .resource
+                .iter()
+                .any(|resource| resource.language.eq_ignore_ascii_case(language))
+            {
+                manifest.resources.resource.push(Resource {
+                    language: language.clone(),
+                });
+            }
+        }
+        manifest.properties.display_name = Some("ms-resource:DisplayName".into());
+        manifest.properties.description = Some("ms-resource:Description".into());
+        for app in &mut manifest.applications.application {
+            let visual = &mut app.visual_elements;
+            visual.display_name = Some("ms-resource:DisplayName".into());
+            visual.description = Some("ms-resource:Description".into());
+            visual
+                .default_tile
+                .get_or_insert_with(DefaultTile::default)
+                .short_name = Some("ms-resource:ShortName".into());
+        }
+        self.windows.strings = strings;
+    }
+
+    /// Merges the `android.profiles` section matching `opt` into the base config.
+    fn apply_android_profile(&mut self, opt: Opt) {
+        let profile = match opt {
+            Opt::Debug => std::mem::take(&mut self.android.profiles.debug),
+            Opt::Release => std::mem::take(&mut self.android.profiles.release),
+        };
+        self.android.assets.extend(profile.assets);
+        self.android
+            .generic
+            .runtime_libs
+            .extend(profile.runtime_libs);
+        self.android
+            .manifest
+            .uses_permission
+            .extend(profile.uses_permission);
+        if let Some(debuggable) = profile.debuggable {
+            self.android.manifest.application.debuggable = Some(debuggable);
+        }
+        if let Some(densities) = profile.icon_densities {
+            self.android.icon_densities = densities;
+        }
+    }
+
+    /// Applies the flavor selected with [`Config::set_android_flavor`] on top of
+    /// the package and version defaults.
+    fn apply_android_flavor(&mut self) {
+        let Some(flavor) = self.android.flavor.take() else {
+            return;
+        };
+        let manifest = &mut self.android.manifest;
+        if let (Some(package), Some(suffix)) = (manifest.package.as_mut(), flavor.app_id_suffix) {
+            package.push_str(&suffix);
+        }
+        if let (Some(version_name), Some(suffix)) =
+            (manifest.version_name.as_mut(), flavor.version_name_suffix)
+        {
+            version_name.push_str(&suffix);
+        }
+        if let Some(label) = flavor.label {
+            manifest.application.label = Some(label);
+        }
+        if let Some(icon) = flavor.icon {
+            self.android.generic.icon = Some(icon);
+        }
+    }
+
+    /// Checks that all fields required to build `format` for `platform` are
+    /// present, reporting every missing field at once. Runs after
+    /// [`Config::apply_rust_package`], so only fields without a default are
+    /// required, like the msix identity name which is also set by
+    /// `--app-id`.
+    pub fn validate(&self, platform: Platform, format: Format) -> Result<()> {
+        let mut validation = ManifestValidation::default();
+        match platform {
+            Platform::Android | Platform::Linux => {}
+            Platform::Ios => {
+                validate_document_types("ios", &self.ios.info)?;
+            }
+            Platform::Macos => {
+                validate_document_types("macos", &self.macos.info)?;
+            }
+            Platform::Windows => {
+                if format == Format::Msix {
+                    validation.require(
+                        "windows.manifest.identity.name",
+                        &self.windows.manifest.identity.name,
+                    );
+                }
+            }
+        }
+        validation.into_result()?;
+        if format == Format::Msix {
+            self.windows.manifest.validate()?;
+        }
+        Ok(())
+    }
 
+    /// Checks that the `target` section only configures rust triples which
+    /// can be built.
+    pub fn validate_targets(&self) -> Result<()> {
+        for triple in self.target.keys() {
+            let supported = Platform::value_variants().iter().any(|platform| {
+                Arch::value_variants().iter().any(|arch| {
+                    let target = CompileTarget::new(*platform, *arch, Opt::Debug);
+                    target.rust_triple().ok() == Some(triple.as_str())
+                })
+            });
+            anyhow::ensure!(
+                supported,
+                "unsupported target `{}` in manifest.yaml",
+                triple
+            );
+        }
         Ok(())
     }
 
+    /// Warns about deprecated fields of the `platform` manifest, which are
+    /// still emitted for legacy apps.
+    pub fn warn_deprecated(&self, platform: Platform) {
+        if platform != Platform::Android {
+            return;
+        }
+        if let Some(shared_user_id) = &self.android.manifest.shared_user_id {
+            tracing::warn!(
+                "android.manifest.shared_user_id `{}` is deprecated since API level 29",
+                shared_user_id
+            );
+        }
+    }
+
+    /// Version of the rust package, available after [`Config::apply_rust_package`].
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn output(&self) -> &OutputConfig {
+        &self.output
+    }
+
     pub fn android(&self) -> &AndroidConfig {
         &self.android
     }
@@ -314,7 +750,112 @@ impl Config {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+/// Checks an application id passed with `--app-id` against the rules of
+/// `platform`.
+pub fn validate_app_id(platform: Platform, app_id: &str) -> Result<()> {
+    let segments = app_id.split('.').collect::<Vec<_>>();
+    match platform {
+        Platform::Android => {
+            anyhow::ensure!(
+                segments.len() >= 2,
+                "android package `{}` needs at least two segments, e.g. `com.example.app`",
+                app_id
+            );
+            for segment in &segments {
+                let valid = segment.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_');
+                anyhow::ensure!(
+                    valid,
+                    "invalid android package `{}`, segments must start with a letter and only contain letters, digits and `_`",
+                    app_id
+                );
+            }
+        }
+        Platform::Ios | Platform::Macos => {
+            let valid = segments.iter().all(|segment| {
+                !segment.is_empty()
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+            anyhow::ensure!(
+                valid,
+                "invalid bundle identifier `{}`, expected reverse-DNS with letters, digits and `-`",
+                app_id
+            );
+        }
+        Platform::Windows => {
+            let valid = (3..=50).contains(&app_id.len())
+                && app_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+            anyhow::ensure!(
+                valid,
+                "invalid msix identity name `{}`, expected 3 to 50 letters, digits, `.` or `-`",
+                app_id
+            );
+        }
+        Platform::Linux => {}
+    }
+    Ok(())
+}
+
+/// Document types are only offered for files matching an extension or a
+/// content type.
+fn validate_document_types(platform: &str, info: &InfoPlist) -> Result<()> {
+    for doc in &info.cf_bundle_document_types {
+        anyhow::ensure!(
+            !doc.cf_bundle_type_extensions.is_empty() || !doc.ls_item_content_types.is_empty(),
+            "{}.info.cf_bundle_document_types: `{}` needs cf_bundle_type_extensions or ls_item_content_types",
+            platform,
+            doc.cf_bundle_type_name
+        );
+    }
+    Ok(())
+}
+
+/// Error listing all required `manifest.yaml` fields that are missing.
+#[derive(Debug, Default)]
+pub struct ManifestValidation {
+    missing: Vec<&'static str>,
+}
+
+impl ManifestValidation {
+    fn require<T>(&mut self, field: &'static str, value: &Option<T>) {
+        if value.is_none() {
+            self.missing.push(field);
+        }
+    }
+
+    /// Paths of the missing fields, e.g. `android.manifest.package`.
+    pub fn missing(&self) -> &[&'static str] {
+        &self.missing
+    }
+
+    fn into_result(self) -> Result<()> {
+        if self.missing.is_empty() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+}
+
+impl fmt::Display for ManifestValidation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "manifest.yaml is missing required fields:")?;
+        for field in &self.missing {
+            write!(f, "\n  {}", field)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ManifestValidation {}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum UnalignedCompressed {
     /// Don't align this file
@@ -324,7 +865,7 @@ pub enum UnalignedCompressed {
     Compressed,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum ZipAlignmentOptions {
     /// Align this file to the given number of bytes
@@ -351,7 +892,7 @@ impl ZipAlignmentOptions {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 #[serde(untagged, deny_unknown_fields)]
 pub enum AssetPath {
     Path(PathBuf),
@@ -390,11 +931,51 @@ impl AssetPath {
     }
 }
 
-#[derive(Deserialize)]
+/// Delivery mode of a Play [asset pack](https://developer.android.com/guide/playcore/asset-delivery).
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssetPackDelivery {
+    #[default]
+    InstallTime,
+    FastFollow,
+    OnDemand,
+}
+
+impl AssetPackDelivery {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InstallTime => "install-time",
+            Self::FastFollow => "fast-follow",
+            Self::OnDemand => "on-demand",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AssetPack {
+    #[serde(default)]
+    pub delivery: AssetPackDelivery,
+    /// Files and directories placed in the root of the asset pack
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct RawConfig {
+    /// Path of a manifest to inherit shared settings from, relative to this
+    /// one. Mappings are merged with this manifest taking precedence, other
+    /// values like lists are replaced. Relative paths of the inherited
+    /// settings are resolved like the ones of this manifest.
+    #[allow(unused)]
+    extends: Option<PathBuf>,
     #[serde(flatten)]
     generic: Option<GenericConfig>,
+    output: Option<OutputConfig>,
+    build: Option<BuildConfig>,
+    network: Option<NetworkConfig>,
+    target: Option<BTreeMap<String, TargetConfig>>,
     android: Option<AndroidConfig>,
     linux: Option<LinuxConfig>,
     ios: Option<IosConfig>,
@@ -402,15 +983,457 @@ struct RawConfig {
     windows: Option<WindowsConfig>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+/// Reads the manifest at `path` merged onto the manifests it `extends`.
+/// `stack` holds the manifests extending it, to detect cycles.
+fn load_yaml(path: &Path, stack: &mut Vec<PathBuf>) -> Result<serde_yaml::Value> {
+    let canonical = dunce::canonicalize(path)
+        .with_context(|| format!("manifest {} not found", path.display()))?;
+    if let Some(pos) = stack.iter().position(|p| *p == canonical) {
+        let cycle = stack[pos..]
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string());
+        anyhow::bail!(
+            "circular extends: {}",
+            cycle.collect::<Vec<_>>().join(" -> ")
+        );
+    }
+    let contents = std::fs::read_to_string(path)?;
+    let mut value: serde_yaml::Value =
+        serde_yaml::from_str(&contents).with_context(|| format!("invalid {}", path.display()))?;
+    let extends = match value.as_mapping_mut() {
+        Some(mapping) => mapping.remove("extends"),
+        None => None,
+    };
+    let Some(extends) = extends else {
+        return Ok(value);
+    };
+    let extends = extends
+        .as_str()
+        .with_context(|| format!("extends of {} must be a path", path.display()))?;
+    let base_path = path.parent().unwrap_or_else(|| Path::new("")).join(extends);
+    stack.push(canonical);
+    let mut base = load_yaml(&base_path, stack)?;
+    stack.pop();
+    merge_yaml(&mut base, value);
+    Ok(base)
+}
+
+/// Merges `value` onto `base`, recursing into mappings.
+fn merge_yaml(base: &mut serde_yaml::Value, value: serde_yaml::Value) {
+    match (base, value) {
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(base) => merge_yaml(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IconFilter {
+    /// Smooth at fractional scales like the msix scale-125 and scale-150 tiles
+    Lanczos3,
+    /// Cheaper and slightly softer than lanczos3
+    Triangle,
+    /// Keeps hard edges, for pixel art icons
+    Nearest,
+}
+
+impl IconFilter {
+    pub fn to_scaler_filter(self) -> ScalerFilter {
+        match self {
+            Self::Lanczos3 => ScalerFilter::Lanczos3,
+            Self::Triangle => ScalerFilter::Triangle,
+            Self::Nearest => ScalerFilter::Nearest,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DebugInfo {
+    /// No debug info (`-C debuginfo=0`)
+    None,
+    /// Line tables only, enough for file:line in backtraces (`-C debuginfo=1`)
+    LineTablesOnly,
+    /// Full debug info (`-C debuginfo=2`)
+    Full,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Panic {
+    /// Unwind the stack on panic (`-C panic=unwind`)
+    Unwind,
+    /// Abort the process on panic (`-C panic=abort`), which shrinks the binary
+    Abort,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct GenericConfig {
     icon: Option<PathBuf>,
+    /// Resampling filter used to resize the icon, defaults to `lanczos3`
+    icon_filter: Option<IconFilter>,
     #[serde(default)]
     runtime_libs: Vec<PathBuf>,
+    /// Overrides the debug info level of the cargo profile.
+    debug_info: Option<DebugInfo>,
+    /// Panic strategy, defaults to `abort` for android and ios release
+    /// builds and to `unwind` otherwise.
+    panic: Option<Panic>,
+    /// Glob patterns of files left out when copying directories into the artifact.
+    /// Copied directories can also contain a `.xbuildignore` in gitignore syntax
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// `.env` file loaded into the environment of cargo, defaults to `.env`
+    env_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Wraps rustc and the C/C++ compilers in `sccache`.
+    #[serde(default)]
+    pub sccache: bool,
+    /// Links linux targets with a faster linker, can be overridden per
+    /// target triple.
+    pub linker: Option<Linker>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Proxy for all downloads, e.g. `http://proxy.example.com:3128`.
+    /// Defaults to `HTTPS_PROXY`, `HTTP_PROXY` or `ALL_PROXY`
+    pub proxy: Option<String>,
+    /// Comma separated hosts which are not proxied, e.g.
+    /// `localhost,.example.com`. Defaults to `NO_PROXY`
+    pub no_proxy: Option<String>,
+}
+
+impl NetworkConfig {
+    /// The configured proxy or the one set in the environment.
+    pub fn proxy(&self) -> Option<String> {
+        self.proxy.clone().or_else(|| {
+            [
+                "HTTPS_PROXY",
+                "https_proxy",
+                "HTTP_PROXY",
+                "http_proxy",
+                "ALL_PROXY",
+                "all_proxy",
+            ]
+            .into_iter()
+            .find_map(|var| std::env::var(var).ok().filter(|proxy| !proxy.is_empty()))
+        })
+    }
+
+    /// The configured hosts bypassing the proxy or the ones set in the
+    /// environment.
+    pub fn no_proxy(&self) -> Option<String> {
+        self.no_proxy.clone().or_else(|| {
+            std::env::var("NO_PROXY")
+                .or_else(|_| std::env::var("no_proxy"))
+                .ok()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Linker {
+    Mold,
+    Lld,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+impl Linker {
+    /// Value of `-fuse-ld`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mold => "mold",
+            Self::Lld => "lld",
+        }
+    }
+
+    /// Binary the compiler driver looks up for `-fuse-ld`.
+    pub fn binary(self) -> &'static str {
+        match self {
+            Self::Mold => "ld.mold",
+            Self::Lld => "ld.lld",
+        }
+    }
+}
+
+/// Xcode build settings available in `Info.plist` templates.
+fn build_settings(info: &InfoPlist, name: &str, version: &str) -> BTreeMap<&'static str, String> {
+    let bundle_identifier = info.cf_bundle_identifier.as_deref().unwrap_or(name);
+    let display_name = info.cf_bundle_display_name.as_deref();
+    let short_version = info.cf_bundle_short_version_string.as_deref();
+    let region = info.cf_bundle_development_region.as_deref();
+    BTreeMap::from([
+        ("PRODUCT_BUNDLE_IDENTIFIER", bundle_identifier.to_string()),
+        ("PRODUCT_NAME", name.to_string()),
+        (
+            "PRODUCT_DISPLAY_NAME",
+            display_name.unwrap_or(name).to_string(),
+        ),
+        ("TARGET_NAME", name.to_string()),
+        ("EXECUTABLE_NAME", name.to_string()),
+        ("PRODUCT_BUNDLE_PACKAGE_TYPE", "APPL".to_string()),
+        (
+            "MARKETING_VERSION",
+            short_version.unwrap_or(version).to_string(),
+        ),
+        (
+            "CURRENT_PROJECT_VERSION",
+            info.cf_bundle_version
+                .as_deref()
+                .unwrap_or(version)
+                .to_string(),
+        ),
+        ("DEVELOPMENT_LANGUAGE", region.unwrap_or("en").to_string()),
+    ])
+}
+
+/// Substitutes `$(VAR)` and `${VAR}` in `value`. Like in Xcode, the
+/// `:rfc1034identifier` modifier replaces characters not allowed in bundle
+/// identifiers with `-`.
+fn substitute_build_settings(value: &str, vars: &BTreeMap<&str, String>) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let close = match rest[start + 1..].chars().next() {
+            Some('(') => ')',
+            Some('{') => '}',
+            _ => {
+                out.push('$');
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        let end = rest[start..]
+            .find(close)
+            .with_context(|| format!("unterminated variable in `{}`", value))?;
+        let var = &rest[start + 2..start + end];
+        let (name, modifier) = var.split_once(':').unwrap_or((var, ""));
+        let resolved = vars
+            .get(name)
+            .with_context(|| format!("unknown variable `{}` in Info.plist template", name))?;
+        match modifier {
+            "" => out.push_str(resolved),
+            "rfc1034identifier" => out.extend(resolved.chars().map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })),
+            _ => anyhow::bail!("unsupported modifier `{}` of `{}`", modifier, name),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn substitute_plist_value(value: &mut plist::Value, vars: &BTreeMap<&str, String>) -> Result<()> {
+    match value {
+        plist::Value::String(s) => *s = substitute_build_settings(s, vars)?,
+        plist::Value::Array(values) => {
+            for value in values {
+                substitute_plist_value(value, vars)?;
+            }
+        }
+        plist::Value::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                substitute_plist_value(value, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Merges an `Info.plist` template into `info`, keeping the keys already set.
+fn apply_info_plist_template(
+    info: &mut InfoPlist,
+    template: &Path,
+    vars: &BTreeMap<&str, String>,
+) -> Result<()> {
+    let mut value = plist::Value::from_file(template)
+        .with_context(|| format!("failed to read {}", template.display()))?;
+    substitute_plist_value(&mut value, vars)?;
+    let mut merged = value
+        .into_dictionary()
+        .context("Info.plist template is not a dictionary")?;
+    let mut buf = vec![];
+    plist::to_writer_xml(&mut buf, &*info)?;
+    let overrides = plist::Value::from_reader_xml(&*buf)?
+        .into_dictionary()
+        .context("invalid Info.plist")?;
+    for (key, value) in overrides {
+        merged.insert(key, value);
+    }
+    let mut buf = vec![];
+    plist::to_writer_xml(&mut buf, &merged)?;
+    *info = plist::from_reader_xml(&*buf)
+        .with_context(|| format!("unsupported key in {}", template.display()))?;
+    Ok(())
+}
+
+/// Codegen and C/C++ options applied to a single rust target triple, so they don't
+/// leak into the other arches of a multiarch build.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+    /// Passed as `-C target-cpu`, e.g. `cortex-a55` or `x86-64-v3`.
+    pub target_cpu: Option<String>,
+    /// Passed as `-C target-feature`, e.g. `+sse4.2`. A missing sign enables
+    /// the feature.
+    #[serde(default)]
+    pub target_features: Vec<String>,
+    /// Include directories for C/C++ compilers and bindgen, relative to the
+    /// package root. Passed after the sdk includes.
+    #[serde(default)]
+    pub include_dirs: Vec<PathBuf>,
+    /// Preprocessor defines for C/C++ compilers and bindgen.
+    #[serde(default)]
+    pub defines: BTreeMap<String, String>,
+    /// Overrides `build.linker` for this target.
+    pub linker: Option<Linker>,
+}
+
+impl TargetConfig {
+    /// Target features with an explicit `+` or `-`, checked against the
+    /// features rustc knows for `arch`.
+    pub fn target_features(&self, arch: Arch) -> Result<Vec<String>> {
+        let known: &[&str] = match arch {
+            Arch::Arm => &[
+                "aclass",
+                "crc",
+                "d32",
+                "dsp",
+                "neon",
+                "thumb-mode",
+                "thumb2",
+                "v5te",
+                "v6",
+                "v7",
+                "vfp2",
+                "vfp3",
+                "vfp4",
+            ],
+            Arch::Arm64 => &[
+                "aes", "crc", "dotprod", "fp16", "fp-armv8", "lse", "neon", "rcpc", "rdm", "sha2",
+                "sha3", "sve", "sve2",
+            ],
+            Arch::X64 => &[
+                "adx",
+                "aes",
+                "avx",
+                "avx2",
+                "avx512bw",
+                "avx512cd",
+                "avx512dq",
+                "avx512f",
+                "avx512vl",
+                "bmi1",
+                "bmi2",
+                "cmpxchg16b",
+                "f16c",
+                "fma",
+                "fxsr",
+                "lzcnt",
+                "movbe",
+                "pclmulqdq",
+                "popcnt",
+                "rdrand",
+                "rdseed",
+                "sha",
+                "sse",
+                "sse2",
+                "sse3",
+                "sse4.1",
+                "sse4.2",
+                "ssse3",
+                "xsave",
+            ],
+        };
+        self.target_features
+            .iter()
+            .map(|feature| {
+                let (sign, name) = match feature.strip_prefix(['+', '-']) {
+                    Some(name) => (&feature[..1], name),
+                    None => ("+", feature.as_str()),
+                };
+                anyhow::ensure!(
+                    known.contains(&name),
+                    "unknown target feature `{}` for {}, expected one of: {}",
+                    name,
+                    arch,
+                    known.join(", ")
+                );
+                Ok(format!("{}{}", sign, name))
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    /// File name of the artifact without extension, e.g. `{name}-{version}-{arch}`.
+    /// Supports `{name}`, `{version}`, `{arch}`, `{platform}`, `{opt}` and `{format}`.
+    pub name_template: Option<String>,
+}
+
+impl OutputConfig {
+    /// Substitutes `vars` into the name template, `None` when no template is set.
+    pub fn file_stem(&self, vars: &[(&str, &str)]) -> Result<Option<String>> {
+        let template = if let Some(template) = self.name_template.as_deref() {
+            template
+        } else {
+            return Ok(None);
+        };
+        let mut name = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            name.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .with_context(|| format!("unclosed `{{` in output.name_template `{}`", template))?;
+            let key = &rest[start + 1..start + end];
+            let value = vars
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+                .with_context(|| {
+                    format!("unknown variable `{{{}}}` in output.name_template", key)
+                })?;
+            name.push_str(value);
+            rest = &rest[start + end + 1..];
+        }
+        name.push_str(rest);
+        anyhow::ensure!(
+            !name.is_empty() && !name.contains(['/', '\\']),
+            "output.name_template must produce a file name, got `{}`",
+            name
+        );
+        Ok(Some(name))
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AndroidDebugConfig {
     /// Forward remote (phone) socket connection to local (host)
@@ -421,54 +1444,1054 @@ pub struct AndroidDebugConfig {
     pub reverse: HashMap<String, String>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+/// Theme generated as `@style/AppTheme`, shown while the app starts.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AndroidThemeConfig {
+    /// Parent theme, defaults to the theme of the application or
+    /// `@android:style/Theme.Material.Light.NoActionBar`
+    pub parent: Option<String>,
+    /// Color or drawable drawn behind the app, e.g. `#101010`. Android 12+
+    /// also uses a color for the splash screen
+    pub window_background: Option<String>,
+    /// Color of the Android 12+ splash screen, when it differs from the
+    /// window background
+    pub splash_screen_background: Option<String>,
+    /// Icon of the Android 12+ splash screen, defaults to the launcher icon
+    pub splash_screen_icon: Option<String>,
+}
+
+impl AndroidThemeConfig {
+    pub const NAME: &'static str = "AppTheme";
+
+    /// Theme resources by `res/` directory. The splash screen attributes
+    /// need api 31, so they go into `values-v31`.
+    pub fn resources(&self) -> Vec<(&'static str, String)> {
+        let mut items = vec![];
+        if let Some(background) = self.window_background.as_ref() {
+            items.push(("android:windowBackground", background));
+        }
+        let mut resources = vec![("values", theme_xml(self.parent.as_deref(), &items))];
+        if let Some(background) = self.splash_screen_background.as_ref() {
+            items.push(("android:windowSplashScreenBackground", background));
+        }
+        if let Some(icon) = self.splash_screen_icon.as_ref() {
+            items.push(("android:windowSplashScreenAnimatedIcon", icon));
+        }
+        if self.splash_screen_background.is_some() || self.splash_screen_icon.is_some() {
+            resources.push(("values-v31", theme_xml(self.parent.as_deref(), &items)));
+        }
+        resources
+    }
+
+    /// Writes the theme resources into a `res/` directory.
+    pub fn write_resources(&self, res: &Path) -> Result<()> {
+        for (dir, xml) in self.resources() {
+            let dir = res.join(dir);
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join("themes.xml"), xml)?;
+        }
+        Ok(())
+    }
+}
+
+fn theme_xml(parent: Option<&str>, items: &[(&str, &String)]) -> String {
+    use quick_xml::escape::escape;
+    let mut xml = format!(
+        "<resources>\n    <style name=\"{}\" parent=\"{}\">\n",
+        AndroidThemeConfig::NAME,
+        escape(parent.unwrap_or_default())
+    );
+    for (name, value) in items {
+        xml.push_str(&format!(
+            "        <item name=\"{}\">{}</item>\n",
+            name,
+            escape(value)
+        ));
+    }
+    xml.push_str("    </style>\n</resources>\n");
+    xml
+}
+
+/// Attributes of the `<application>` element, taking precedence over the ones
+/// in `android.manifest.application`.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct AndroidApplicationConfig {
+    pub large_heap: Option<bool>,
+    pub hardware_accelerated: Option<bool>,
+    pub allow_backup: Option<bool>,
+    pub full_backup_content: Option<String>,
+    /// Resource reference like `@xml/network_security_config`
+    pub network_security_config: Option<String>,
+    pub uses_cleartext_traffic: Option<bool>,
+    pub extract_native_libs: Option<bool>,
+    pub request_legacy_external_storage: Option<bool>,
+    pub resizeable_activity: Option<bool>,
+    pub supports_rtl: Option<bool>,
+    /// Unsupported attributes, which are reported instead of rejected
+    #[serde(flatten)]
+    unknown: BTreeMap<String, serde_json::Value>,
+}
+
+impl AndroidApplicationConfig {
+    fn apply(&self, application: &mut AndroidApplication) {
+        for key in self.unknown.keys() {
+            tracing::warn!("ignoring unknown key android.application.{}", key);
+        }
+        fn set<T: Clone>(value: &Option<T>, field: &mut Option<T>) {
+            if value.is_some() {
+                *field = value.clone();
+            }
+        }
+        set(&self.large_heap, &mut application.large_heap);
+        set(
+            &self.hardware_accelerated,
+            &mut application.hardware_accelerated,
+        );
+        set(&self.allow_backup, &mut application.allow_backup);
+        set(
+            &self.full_backup_content,
+            &mut application.full_backup_content,
+        );
+        set(
+            &self.network_security_config,
+            &mut application.network_security_config,
+        );
+        set(
+            &self.uses_cleartext_traffic,
+            &mut application.uses_cleartext_traffic,
+        );
+        set(
+            &self.extract_native_libs,
+            &mut application.extract_native_libs,
+        );
+        set(
+            &self.request_legacy_external_storage,
+            &mut application.request_legacy_external_storage,
+        );
+        set(
+            &self.resizeable_activity,
+            &mut application.resizeable_activity,
+        );
+        set(&self.supports_rtl, &mut application.supports_rtl);
+    }
+}
+
+/// An `<activity>` element, merged by name into the activities of
+/// `android.manifest.application`.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AndroidActivityConfig {
+    /// Class name of the activity, e.g. `.ShareActivity`
+    pub name: String,
+    pub label: Option<String>,
+    /// `standard`, `singleTop`, `singleTask` or `singleInstance`
+    pub launch_mode: Option<String>,
+    pub orientation: Option<String>,
+    /// Defaults to `true` for activities with intent filters
+    pub exported: Option<bool>,
+    /// Makes this the activity started by the launcher, which receives the
+    /// `MAIN`/`LAUNCHER` intent filter. Defaults to the first activity
+    #[serde(default)]
+    pub launcher: bool,
+    #[serde(default)]
+    pub intent_filters: Vec<IntentFilter>,
+    #[serde(default)]
+    pub meta_data: Vec<MetaData>,
+}
+
+/// Version code of the apk built for the `--min-sdk` variant `min_sdk`. The
+/// min sdk gets its own position, the last two digits of an explicit version
+/// code or the apk id byte of one derived from the version, so variants of
+/// different releases never share a version code.
+fn min_sdk_variant_code(
+    version_code: Option<u32>,
+    version: &str,
+    min_sdk: u32,
+) -> Result<Option<u32>> {
+    if let Some(code) = version_code {
+        anyhow::ensure!(min_sdk < 100, "invalid min sdk version {}", min_sdk);
+        let code = code.checked_mul(100).with_context(|| {
+            format!(
+                "android.manifest.version_code {} is too large for --min-sdk variants",
+                code
+            )
+        })?;
+        return Ok(Some(code + min_sdk));
+    }
+    let apk_id =
+        u8::try_from(min_sdk).with_context(|| format!("invalid min sdk version {}", min_sdk))?;
+    Ok(VersionCode::from_semver(version)
+        .ok()
+        .map(|code| code.to_code(apk_id)))
+}
+
+/// Merges `activities` into `application`, moving the launcher activity to
+/// the front where the defaults and the `MAIN` intent filter are applied.
+fn apply_android_activities(
+    activities: &[AndroidActivityConfig],
+    application: &mut AndroidApplication,
+) -> Result<()> {
+    let launchers = activities.iter().filter(|config| config.launcher).count();
+    anyhow::ensure!(
+        launchers <= 1,
+        "only one of android.activities can be the launcher"
+    );
+    if launchers == 0 && application.activities.is_empty() && !activities.is_empty() {
+        application.activities.push(Activity::default());
+    }
+    for config in activities {
+        let index = application
+            .activities
+            .iter()
+            .position(|activity| activity.name.as_deref() == Some(config.name.as_str()));
+        let index = index.unwrap_or_else(|| {
+            application.activities.push(Activity {
+                name: Some(config.name.clone()),
+                ..Default::default()
+            });
+            application.activities.len() - 1
+        });
+        let activity = &mut application.activities[index];
+        if config.label.is_some() {
+            activity.label = config.label.clone();
+        }
+        if config.launch_mode.is_some() {
+            activity.launch_mode = config.launch_mode.clone();
+        }
+        if config.orientation.is_some() {
+            activity.orientation = config.orientation.clone();
+        }
+        if config.exported.is_some() {
+            activity.exported = config.exported;
+        } else if !config.intent_filters.is_empty() {
+            activity.exported.get_or_insert(true);
+        }
+        activity
+            .intent_filters
+            .extend(config.intent_filters.iter().cloned());
+        activity.meta_data.extend(config.meta_data.iter().cloned());
+        if config.launcher {
+            let activity = application.activities.remove(index);
+            application.activities.insert(0, activity);
+        } else if activity.intent_filters.iter().any(|filter| {
+            filter
+                .categories
+                .iter()
+                .any(|category| category == "android.intent.category.LAUNCHER")
+        }) {
+            tracing::warn!(
+                "activity `{}` has a LAUNCHER intent filter, use `launcher: true` instead",
+                config.name
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AndroidConfig {
     #[serde(flatten)]
     generic: GenericConfig,
     #[serde(default)]
     pub manifest: AndroidManifest,
+    /// Common attributes of the `<application>` element
+    #[serde(default)]
+    pub application: AndroidApplicationConfig,
+    /// Additional activities and their intent filters, e.g. for custom url
+    /// schemes. The launcher activity is the first one unless one is marked
+    /// with `launcher: true`
+    #[serde(default)]
+    pub activities: Vec<AndroidActivityConfig>,
+    /// Maven dependencies as `group:name:version`. Without gradle their
+    /// assets, native libraries and classes are merged into the apk, which
+    /// requires `d8` and doesn't support android resources.
     #[serde(default)]
     pub dependencies: Vec<String>,
     #[serde(default)]
     pub gradle: bool,
     #[serde(default)]
     pub wry: bool,
+    /// Android NDK or NDK sysroot used instead of the downloaded one
+    pub ndk: Option<PathBuf>,
+    /// Directory of prebuilt libraries to link against and package, `{abi}`
+    /// is replaced by the android abi, e.g. `vendor/jni/{abi}`
+    pub native_libs_dir: Option<String>,
+    /// Assets packaged in the `assets/` directory of the APK
     #[serde(default)]
     pub assets: Vec<AssetPath>,
+    /// Android `res/` directory, relative to the package root. Without
+    /// gradle the strings, colors, bools and integers of `values/` and file
+    /// resources like `drawable/` and `layout/` are compiled into the apk
+    pub resources: Option<PathBuf>,
+    /// Launch theme with the splash screen background, replaces the theme of
+    /// the application
+    pub theme: Option<AndroidThemeConfig>,
+    /// Play asset packs by name, only supported by gradle builds
+    #[serde(default)]
+    pub asset_packs: BTreeMap<String, AssetPack>,
+    /// Icon used for the monochrome layer of themed icons (Android 13+).
+    /// Defaults to the launcher icon.
+    pub monochrome_icon: Option<PathBuf>,
+    /// Android Studio style `keystore.properties` with the `storeFile`,
+    /// `storePassword`, `keyAlias` and `keyPassword` of the release key.
+    /// Defaults to `keystore.properties` next to manifest.yaml if it exists
+    pub keystore_properties: Option<PathBuf>,
+    /// Densities of the generated launcher icons, e.g. `[xxhdpi]` for debug
+    /// builds. Defaults to all densities.
+    #[serde(default)]
+    pub icon_densities: Vec<Density>,
     /// Debug configuration for `x run`
     #[serde(default)]
     pub debug: AndroidDebugConfig,
+    /// Overlays applied on top of the base config for debug or release builds
+    #[serde(default)]
+    pub profiles: AndroidProfiles,
+    /// Named overlays selected with `--flavor`, e.g. to install a dev build
+    /// next to the release build
+    #[serde(default)]
+    pub flavors: BTreeMap<String, AndroidFlavorConfig>,
+    /// Min sdk of the apk when building several with `--min-sdk`
+    #[serde(skip)]
+    min_sdk_variant: Option<u32>,
+    /// Flavor selected with `--flavor`
+    #[serde(skip)]
+    flavor: Option<AndroidFlavorConfig>,
+}
+
+impl AndroidConfig {
+    /// [`AndroidConfig::native_libs_dir`] for `abi`.
+    pub fn native_libs_dir(&self, abi: apk::Target) -> Option<PathBuf> {
+        let dir = self.native_libs_dir.as_ref()?;
+        Some(PathBuf::from(dir.replace("{abi}", abi.as_str())))
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AndroidProfiles {
+    #[serde(default)]
+    pub debug: AndroidProfileConfig,
+    #[serde(default)]
+    pub release: AndroidProfileConfig,
+}
+
+/// Additions to the android config which only apply to one build profile.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AndroidProfileConfig {
+    #[serde(default)]
+    pub assets: Vec<AssetPath>,
+    #[serde(default)]
+    pub runtime_libs: Vec<PathBuf>,
+    #[serde(default)]
+    pub uses_permission: Vec<Permission>,
+    /// Overrides `android:debuggable`, which defaults to `true` for debug builds
+    pub debuggable: Option<bool>,
+    /// Overrides `icon_densities`
+    pub icon_densities: Option<Vec<Density>>,
+}
+
+/// Overlay of the android config selected with `--flavor`.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AndroidFlavorConfig {
+    /// Appended to the package, e.g. `.dev`
+    pub app_id_suffix: Option<String>,
+    /// Replaces the application label
+    pub label: Option<String>,
+    /// Replaces the launcher icon
+    pub icon: Option<PathBuf>,
+    /// Appended to the version name, e.g. `-dev`
+    pub version_name_suffix: Option<String>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct IosConfig {
     #[serde(flatten)]
     generic: GenericConfig,
     pub assets_car: Option<PathBuf>,
+    /// Asset catalog compiled into `Assets.car` when `actool` is available
+    pub xcassets: Option<PathBuf>,
+    /// Xcode style `Info.plist` template. `$(VAR)` and `${VAR}` are
+    /// substituted and keys set in `info` take precedence.
+    pub info_plist: Option<PathBuf>,
     pub info: InfoPlist,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct MacosConfig {
     #[serde(flatten)]
     generic: GenericConfig,
+    /// Asset catalog compiled into `Assets.car` when `actool` is available
+    pub xcassets: Option<PathBuf>,
+    /// Xcode style `Info.plist` template. `$(VAR)` and `${VAR}` are
+    /// substituted and keys set in `info` take precedence.
+    pub info_plist: Option<PathBuf>,
     pub info: InfoPlist,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct LinuxConfig {
     #[serde(flatten)]
     generic: GenericConfig,
+    #[serde(default)]
+    pub appimage: AppImageConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AppImageConfig {
+    #[serde(flatten)]
+    pub squashfs: SquashfsOptions,
+    /// Update information for AppImageUpdate, e.g. `zsync|<url>`
+    pub update_info: Option<String>,
+    /// Generates `<name>.AppImage.zsync` with `zsyncmake`
+    #[serde(default)]
+    pub zsync: bool,
+    /// Script installed as `AppRun` instead of a symlink to the executable,
+    /// relative to the package root
+    pub apprun: Option<PathBuf>,
+    /// Generates an `AppRun` script which sets `LD_LIBRARY_PATH`, `PATH` and
+    /// `XDG_DATA_DIRS` relative to `$APPDIR` before running the executable
+    #[serde(default)]
+    pub apprun_env: bool,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct WindowsConfig {
     #[serde(flatten)]
     generic: GenericConfig,
     pub manifest: AppxManifest,
+    #[serde(default)]
+    pub msix: MsixConfig,
+    /// String resources of `windows.msix.locales` by language, indexed into
+    /// `resources.pri`.
+    #[serde(skip)]
+    pub strings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Shorthands for common `AppxManifest` settings, applied on top of
+/// `windows.manifest`.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MsixConfig {
+    /// Capability names like `internetClient` or `runFullTrust`, declared in
+    /// the namespace they are defined in.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Defaults to `Windows.Desktop` if the manifest declares none.
+    #[serde(default)]
+    pub device_families: Vec<TargetDeviceFamily>,
+    /// Defaults to `<name>.exe`.
+    pub executable: Option<String>,
+    /// Defaults to `Windows.FullTrustApplication`, which also requires the
+    /// `runFullTrust` capability.
+    pub entry_point: Option<String>,
+    /// Localized names by language tag like `fr` or `de-DE`, shown in the
+    /// start menu depending on the system language. Requires `makepri` of
+    /// the windows sdk.
+    #[serde(default)]
+    pub locales: BTreeMap<String, MsixLocale>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MsixLocale {
+    /// Defaults to `windows.manifest.properties.display_name`
+    pub display_name: Option<String>,
+    /// Name on the start menu tile, defaults to the display name
+    pub short_name: Option<String>,
+    /// Defaults to `windows.manifest.properties.description`
+    pub description: Option<String>,
+}
+
+impl MsixLocale {
+    /// `Resources.resw` strings, unset ones taken from `default`.
+    fn strings(&self, default: &Self) -> BTreeMap<String, String> {
+        let display_name = self.display_name.as_ref().or(default.display_name.as_ref());
+        let short_name = self
+            .short_name
+            .as_ref()
+            .or(self.display_name.as_ref())
+            .or(default.short_name.as_ref());
+        let description = self.description.as_ref().or(default.description.as_ref());
+        [
+            ("DisplayName", display_name),
+            ("ShortName", short_name),
+            ("Description", description),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.cloned().unwrap_or_default()))
+        .collect()
+    }
+}
+
+/// Config of a package without a manifest.yaml, with the defaults
+/// [`BuildEnv::new`](crate::BuildEnv::new) applies.
+#[cfg(test)]
+pub(crate) fn test_config(app_id: Option<&str>) -> Config {
+    let package = Package {
+        name: "app".into(),
+        version: Inheritable::Value("1.0.0".into()),
+        description: None,
+    };
+    let mut config = Config::default();
+    config
+        .apply_rust_package(&package, None, Opt::Debug, app_id)
+        .unwrap();
+    config
+}
+
+#[test]
+fn test_validate_android_defaults_target_sdk_version() {
+    let config = test_config(None);
+    config.validate(Platform::Android, Format::Apk).unwrap();
+    let manifest = &config.android.manifest;
+    assert_eq!(manifest.package.as_deref(), Some("com.example.app"));
+    assert_eq!(manifest.sdk.target_sdk_version, Some(33));
+}
+
+#[test]
+fn test_validate_macos_defaults_minimum_system_version() {
+    let config = test_config(None);
+    config.validate(Platform::Macos, Format::Appbundle).unwrap();
+    let info = &config.macos.info;
+    assert_eq!(info.ls_minimum_system_version.as_deref(), Some("10.11"));
+}
+
+#[test]
+fn test_validate_msix_without_identity_name() {
+    let config = test_config(None);
+    let err = config
+        .validate(Platform::Windows, Format::Msix)
+        .unwrap_err()
+        .downcast::<ManifestValidation>()
+        .unwrap();
+    assert_eq!(err.missing, ["windows.manifest.identity.name"]);
+    assert_eq!(
+        err.to_string(),
+        "manifest.yaml is missing required fields:\n  windows.manifest.identity.name"
+    );
+    let config = test_config(Some("com.example.app"));
+    config.validate(Platform::Windows, Format::Msix).unwrap();
+}
+
+#[test]
+fn test_android_profiles() {
+    let yaml = r#"
+android:
+  assets:
+    - assets
+  profiles:
+    debug:
+      assets:
+        - fixtures
+      uses_permission:
+        - name: android.permission.INTERNET
+      icon_densities: [xxhdpi]
+    release:
+      debuggable: false
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let android = raw.android.unwrap();
+
+    let mut debug = Config {
+        android: android.clone(),
+        ..Default::default()
+    };
+    debug.apply_android_profile(Opt::Debug);
+    let assets = debug.android.assets.iter().map(|a| a.path());
+    assert_eq!(
+        assets.collect::<Vec<_>>(),
+        [Path::new("assets"), Path::new("fixtures")]
+    );
+    assert_eq!(debug.android.manifest.uses_permission.len(), 1);
+    assert_eq!(debug.android.manifest.application.debuggable, None);
+    assert_eq!(debug.android.icon_densities, [Density::Xxhdpi]);
+
+    let mut release = Config {
+        android,
+        ..Default::default()
+    };
+    release.apply_android_profile(Opt::Release);
+    assert_eq!(release.android.assets.len(), 1);
+    assert!(release.android.manifest.uses_permission.is_empty());
+    assert_eq!(release.android.manifest.application.debuggable, Some(false));
+    assert!(release.android.icon_densities.is_empty());
+}
+
+#[test]
+fn test_android_application() {
+    let yaml = r#"
+android:
+  manifest:
+    application:
+      allow_backup: true
+  application:
+    large_heap: true
+    allow_backup: false
+    large_heep: true
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let mut android = raw.android.unwrap();
+    assert_eq!(
+        android.application.unknown.keys().collect::<Vec<_>>(),
+        ["large_heep"]
+    );
+    android.application.apply(&mut android.manifest.application);
+    let application = &android.manifest.application;
+    assert_eq!(application.large_heap, Some(true));
+    assert_eq!(application.allow_backup, Some(false));
+    assert!(android
+        .manifest
+        .to_string()
+        .contains(r#"android:largeHeap="true""#));
+}
+
+#[test]
+fn test_android_activities() {
+    let yaml = r#"
+android:
+  activities:
+    - name: .ViewActivity
+      launch_mode: singleTask
+      intent_filters:
+        - actions: [android.intent.action.VIEW]
+          categories: [android.intent.category.DEFAULT, android.intent.category.BROWSABLE]
+          data:
+            - scheme: myapp
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let android = raw.android.unwrap();
+    let mut application = AndroidApplication::default();
+    apply_android_activities(&android.activities, &mut application).unwrap();
+    assert_eq!(application.activities.len(), 2);
+    assert!(application.activities[0].name.is_none());
+    let activity = &application.activities[1];
+    assert_eq!(activity.name.as_deref(), Some(".ViewActivity"));
+    assert_eq!(activity.launch_mode.as_deref(), Some("singleTask"));
+    assert_eq!(activity.exported, Some(true));
+
+    let activities = [
+        AndroidActivityConfig {
+            name: ".ViewActivity".into(),
+            launcher: true,
+            ..Default::default()
+        },
+        AndroidActivityConfig {
+            name: ".Other".into(),
+            launcher: true,
+            ..Default::default()
+        },
+    ];
+    assert!(apply_android_activities(&activities, &mut application).is_err());
+    apply_android_activities(&activities[..1], &mut application).unwrap();
+    assert_eq!(application.activities.len(), 2);
+    assert_eq!(
+        application.activities[0].name.as_deref(),
+        Some(".ViewActivity")
+    );
+}
+
+#[test]
+fn test_document_types() {
+    let yaml = r#"
+macos:
+  info:
+    cf_bundle_name: app
+    ls_minimum_system_version: "10.13"
+    cf_bundle_document_types:
+      - cf_bundle_type_name: My Project
+        cf_bundle_type_role: Editor
+        ls_item_content_types: [com.example.myproj]
+        ls_handler_rank: Owner
+    ut_exported_type_declarations:
+      - ut_type_identifier: com.example.myproj
+        ut_type_conforms_to: [public.data]
+        ut_type_tag_specification:
+          public_filename_extension: [myproj]
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let mut config = Config {
+        macos: raw.macos.unwrap(),
+        ..Default::default()
+    };
+    config.validate(Platform::Macos, Format::Appbundle).unwrap();
+    let mut plist = vec![];
+    plist::to_writer_xml(&mut plist, &config.macos.info).unwrap();
+    let plist = String::from_utf8(plist).unwrap();
+    assert!(plist.contains("<key>CFBundleDocumentTypes</key>"));
+    assert!(plist.contains("<key>public.filename-extension</key>"));
+    assert!(!plist.contains("UTImportedTypeDeclarations"));
+
+    config.macos.info.cf_bundle_document_types[0]
+        .ls_item_content_types
+        .clear();
+    assert!(config.validate(Platform::Macos, Format::Appbundle).is_err());
+}
+
+#[test]
+fn test_validate_app_id() {
+    validate_app_id(Platform::Android, "com.example.app_dev").unwrap();
+    assert!(validate_app_id(Platform::Android, "app").is_err());
+    assert!(validate_app_id(Platform::Android, "com.example.1app").is_err());
+    assert!(validate_app_id(Platform::Android, "com.example-app").is_err());
+    validate_app_id(Platform::Ios, "com.example.my-app").unwrap();
+    assert!(validate_app_id(Platform::Ios, "com.example..app").is_err());
+    assert!(validate_app_id(Platform::Macos, "com.example.my_app").is_err());
+    validate_app_id(Platform::Windows, "Example.App-Dev").unwrap();
+    assert!(validate_app_id(Platform::Windows, "ab").is_err());
+}
+
+#[test]
+fn test_android_flavor() {
+    let yaml = r#"
+android:
+  manifest:
+    package: com.example.app
+    version_name: "1.0.0"
+  flavors:
+    dev:
+      app_id_suffix: .dev
+      label: App Dev
+      version_name_suffix: -dev
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let mut config = Config {
+        android: raw.android.unwrap(),
+        ..Default::default()
+    };
+    assert!(config.set_android_flavor("prod").is_err());
+    config.set_android_flavor("dev").unwrap();
+    config.apply_android_flavor();
+    let manifest = &config.android.manifest;
+    assert_eq!(manifest.package.as_deref(), Some("com.example.app.dev"));
+    assert_eq!(manifest.version_name.as_deref(), Some("1.0.0-dev"));
+    assert_eq!(manifest.application.label.as_deref(), Some("App Dev"));
+}
+
+#[test]
+fn test_output_name_template() {
+    let vars = [("name", "app"), ("version", "1.2.0"), ("arch", "universal")];
+    let mut output = OutputConfig::default();
+    assert_eq!(output.file_stem(&vars).unwrap(), None);
+
+    output.name_template = Some("{name}-{version}-{arch}".into());
+    assert_eq!(
+        output.file_stem(&vars).unwrap().as_deref(),
+        Some("app-1.2.0-universal")
+    );
+
+    output.name_template = Some("{name}-{commit}".into());
+    assert!(output.file_stem(&vars).is_err());
+    output.name_template = Some("{name".into());
+    assert!(output.file_stem(&vars).is_err());
+    output.name_template = Some("{arch}/{name}".into());
+    assert!(output.file_stem(&vars).is_err());
+}
+
+#[test]
+fn test_target_features() {
+    let config: RawConfig = serde_yaml::from_str(
+        r#"
+target:
+  aarch64-linux-android:
+    target_cpu: cortex-a55
+    target_features: [neon, -sve, "+dotprod"]
+  arm-linux-androideabi:
+    target_features: [v7, neon]
+  x86_64-pc-windows-msvc:
+    target_features: [neon]
+"#,
+    )
+    .unwrap();
+    let target = config.target.unwrap();
+    let android = &target["aarch64-linux-android"];
+    assert_eq!(android.target_cpu.as_deref(), Some("cortex-a55"));
+    assert_eq!(
+        android.target_features(Arch::Arm64).unwrap(),
+        ["+neon", "-sve", "+dotprod"]
+    );
+    assert_eq!(
+        target["arm-linux-androideabi"]
+            .target_features(Arch::Arm)
+            .unwrap(),
+        ["+v7", "+neon"]
+    );
+    assert!(target["x86_64-pc-windows-msvc"]
+        .target_features(Arch::X64)
+        .is_err());
+}
+
+#[test]
+fn test_target_c_options() {
+    let config: RawConfig = serde_yaml::from_str(
+        r#"
+target:
+  aarch64-linux-android:
+    include_dirs: [vendor/include]
+    defines:
+      FOO: "1"
+"#,
+    )
+    .unwrap();
+    let target = config.target.unwrap();
+    let android = &target["aarch64-linux-android"];
+    assert_eq!(android.include_dirs, [PathBuf::from("vendor/include")]);
+    assert_eq!(android.defines["FOO"], "1");
+}
+
+#[test]
+fn test_info_plist_template() {
+    let template = std::env::temp_dir().join("xbuild-test-Info.plist");
+    std::fs::write(
+        &template,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>CFBundleIdentifier</key>
+    <string>com.example.$(PRODUCT_NAME:rfc1034identifier)</string>
+    <key>CFBundleShortVersionString</key>
+    <string>${MARKETING_VERSION}</string>
+    <key>CFBundleVersion</key>
+    <string>$(CURRENT_PROJECT_VERSION)</string>
+    <key>CFBundleName</key>
+    <string>template</string>
+</dict>
+</plist>"#,
+    )
+    .unwrap();
+    let mut info = InfoPlist {
+        cf_bundle_name: Some("manifest".into()),
+        cf_bundle_version: Some("42".into()),
+        ..Default::default()
+    };
+    let vars = build_settings(&info, "my_app", "1.2.3");
+    apply_info_plist_template(&mut info, &template, &vars).unwrap();
+    std::fs::remove_file(&template).unwrap();
+    assert_eq!(
+        info.cf_bundle_identifier.as_deref(),
+        Some("com.example.my-app")
+    );
+    assert_eq!(
+        info.cf_bundle_short_version_string.as_deref(),
+        Some("1.2.3")
+    );
+    assert_eq!(info.cf_bundle_version.as_deref(), Some("42"));
+    assert_eq!(info.cf_bundle_name.as_deref(), Some("manifest"));
+    assert!(substitute_build_settings("$(UNKNOWN)", &vars).is_err());
+    assert_eq!(
+        substitute_build_settings("$5 ${TARGET_NAME}", &vars).unwrap(),
+        "$5 my_app"
+    );
+}
+
+#[test]
+fn test_linker() {
+    let config: RawConfig = serde_yaml::from_str(
+        r#"
+build:
+  linker: mold
+target:
+  aarch64-unknown-linux-gnu:
+    linker: lld
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.build.unwrap().linker, Some(Linker::Mold));
+    let target = config.target.unwrap();
+    assert_eq!(
+        target["aarch64-unknown-linux-gnu"].linker,
+        Some(Linker::Lld)
+    );
+}
+
+#[test]
+fn test_panic() {
+    let yaml = r#"
+panic: abort
+android:
+  panic: unwind
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let config = Config {
+        generic: raw.generic.unwrap(),
+        android: raw.android.unwrap(),
+        ..Default::default()
+    };
+    assert_eq!(config.panic(Platform::Linux, Opt::Debug), Panic::Abort);
+    assert_eq!(config.panic(Platform::Android, Opt::Release), Panic::Unwind);
+    let config = Config::default();
+    assert_eq!(config.panic(Platform::Ios, Opt::Release), Panic::Abort);
+    assert_eq!(config.panic(Platform::Android, Opt::Debug), Panic::Unwind);
+    assert_eq!(config.panic(Platform::Macos, Opt::Release), Panic::Unwind);
+}
+
+#[test]
+fn test_extends() {
+    let dir = std::env::temp_dir().join(format!("xbuild-extends-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("app")).unwrap();
+    std::fs::write(
+        dir.join("common.yaml"),
+        r#"
+android:
+  manifest:
+    sdk:
+      min_sdk_version: 24
+      target_sdk_version: 33
+  dependencies:
+    - com.example:common:1.0.0
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("app").join("manifest.yaml"),
+        r#"
+extends: ../common.yaml
+android:
+  manifest:
+    sdk:
+      target_sdk_version: 34
+"#,
+    )
+    .unwrap();
+    let config = Config::parse(dir.join("app").join("manifest.yaml")).unwrap();
+    let sdk = &config.android.manifest.sdk;
+    assert_eq!(sdk.min_sdk_version, Some(24));
+    assert_eq!(sdk.target_sdk_version, Some(34));
+    assert_eq!(config.android.dependencies, ["com.example:common:1.0.0"]);
+
+    std::fs::write(dir.join("common.yaml"), "extends: app/manifest.yaml\n").unwrap();
+    let err = Config::parse(dir.join("app").join("manifest.yaml")).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(err.to_string().starts_with("circular extends"), "{}", err);
+}
+
+#[test]
+fn test_msix_locales() {
+    let yaml = r#"
+locales:
+  fr:
+    display_name: Mon appli
+  de-DE:
+    short_name: App
+"#;
+    let mut config = Config::default();
+    config.windows.msix = serde_yaml::from_str(yaml).unwrap();
+    let properties = &mut config.windows.manifest.properties;
+    properties.display_name = Some("My App".into());
+    properties.description = Some("An app".into());
+    config.apply_msix_config("app");
+    let manifest = &config.windows.manifest;
+    let languages = manifest.resources.resource.iter().map(|r| &r.language);
+    assert_eq!(languages.collect::<Vec<_>>(), ["en-US", "de-DE", "fr"]);
+    assert_eq!(
+        manifest.properties.display_name.as_deref(),
+        Some("ms-resource:DisplayName")
+    );
+    let visual = &manifest.applications.application[0].visual_elements;
+    assert_eq!(
+        visual.default_tile.as_ref().unwrap().short_name.as_deref(),
+        Some("ms-resource:ShortName")
+    );
+    let strings = &config.windows.strings;
+    assert_eq!(strings["en-US"]["DisplayName"], "My App");
+    assert_eq!(strings["fr"]["DisplayName"], "Mon appli");
+    assert_eq!(strings["fr"]["ShortName"], "Mon appli");
+    assert_eq!(strings["fr"]["Description"], "An app");
+    assert_eq!(strings["de-DE"]["DisplayName"], "My App");
+    assert_eq!(strings["de-DE"]["ShortName"], "App");
+
+    let mut config = Config::default();
+    config.windows.msix = serde_yaml::from_str(yaml).unwrap();
+    config.windows.manifest.resources.resource.clear();
+    config.apply_msix_config("app");
+    let manifest = &config.windows.manifest;
+    let languages = manifest.resources.resource.iter().map(|r| &r.language);
+    assert_eq!(languages.collect::<Vec<_>>(), ["en-US", "de-DE", "fr"]);
+    assert!(config.windows.strings.contains_key("en-US"));
+}
+
+#[test]
+fn test_android_32bit_targets() {
+    let yaml = r#"
+target:
+  aarch64-linux-android:
+    target_cpu: cortex-a76
+  armv7-linux-androideabi:
+    target_cpu: cortex-a53
+  i686-linux-android:
+    target_cpu: atom
+"#;
+    let raw: RawConfig = serde_yaml::from_str(yaml).unwrap();
+    let config = Config {
+        target: raw.target.unwrap(),
+        ..Default::default()
+    };
+    assert_eq!(
+        config.android_32bit_targets(),
+        ["armv7-linux-androideabi", "i686-linux-android"]
+    );
+}
+
+#[test]
+fn test_android_theme() {
+    let yaml = r##"
+parent: "@android:style/Theme.Material.NoActionBar"
+window_background: "#101010"
+splash_screen_icon: "@drawable/splash"
+"##;
+    let theme: AndroidThemeConfig = serde_yaml::from_str(yaml).unwrap();
+    let resources = theme.resources();
+    let dirs = resources.iter().map(|(dir, _)| *dir);
+    assert_eq!(dirs.collect::<Vec<_>>(), ["values", "values-v31"]);
+    let (_, values) = &resources[0];
+    assert!(values
+        .contains(r#"<style name="AppTheme" parent="@android:style/Theme.Material.NoActionBar">"#));
+    assert!(values.contains(r#"<item name="android:windowBackground">#101010</item>"#));
+    assert!(!values.contains("windowSplashScreen"));
+    let (_, v31) = &resources[1];
+    assert!(v31.contains("android:windowBackground"));
+    assert!(v31.contains(
+        r#"<item name="android:windowSplashScreenAnimatedIcon">@drawable/splash</item>"#
+    ));
+    let theme = AndroidThemeConfig::default();
+    assert_eq!(theme.resources().len(), 1);
+}
+
+#[test]
+fn test_min_sdk_variant_code() {
+    let version_code = |version: &str, code: Option<u32>, min_sdk: Option<u32>| {
+        let package = Package {
+            name: "app".into(),
+            version: Inheritable::Value(version.into()),
+            description: None,
+        };
+        let mut config = Config::default();
+        config.android.manifest.version_code = code;
+        match min_sdk {
+            Some(min_sdk) => config.set_android_min_sdk_variant(min_sdk),
+            None => config.set_android_min_sdk(26),
+        }
+        config
+            .apply_rust_package(&package, None, Opt::Debug, None)
+            .unwrap();
+        config.android.manifest.version_code.unwrap()
+    };
+    // a single min sdk keeps the version code
+    assert_eq!(version_code("1.2.3", Some(5), None), 5);
+    assert_eq!(version_code("1.2.3", None, None), 0x0101_0203);
+    // variants of different releases don't collide
+    assert_eq!(version_code("1.2.3", Some(5), Some(24)), 524);
+    assert_eq!(version_code("1.2.3", Some(8), Some(21)), 821);
+    assert_eq!(version_code("1.2.3", None, Some(24)), 0x1801_0203);
+    assert!(version_code("1.2.4", None, Some(21)) < version_code("1.2.3", None, Some(24)));
+    assert!(min_sdk_variant_code(Some(u32::MAX), "1.2.3", 21).is_err());
+    assert!(min_sdk_variant_code(None, "1.2.3", 300).is_err());
 }
@@ -0,0 +1,108 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs build commands in a container with `docker run`. Host directories are
+/// mounted at the same path, so the arguments and environment of the wrapped
+/// command stay valid inside the container.
+#[derive(Clone, Debug)]
+pub struct Container {
+    image: String,
+    mounts: Vec<PathBuf>,
+    envs: Vec<(String, OsString)>,
+}
+
+impl Container {
+    pub fn new(image: String) -> Self {
+        Self {
+            image,
+            mounts: vec![],
+            envs: vec![],
+        }
+    }
+
+    /// Makes `path` available inside the container.
+    pub fn mount(&mut self, path: &Path) {
+        if !self.mounts.iter().any(|mount| path.starts_with(mount)) {
+            self.mounts.push(path.to_path_buf());
+        }
+    }
+
+    /// Sets an environment variable for every command run in the container.
+    pub fn env(&mut self, key: &str, value: impl Into<OsString>) {
+        self.envs.push((key.into(), value.into()));
+    }
+
+    /// The `docker run` invocation running `cmd` in the container, with the
+    /// same working directory and environment overrides.
+    pub fn wrap(&self, cmd: &Command) -> Command {
+        let mut docker = Command::new("docker");
+        docker.arg("run").arg("--rm");
+        if let Some(dir) = cmd.get_current_dir() {
+            // files created in mounted directories belong to the user
+            #[cfg(unix)]
+            if let Ok(metadata) = std::fs::metadata(dir) {
+                use std::os::unix::fs::MetadataExt;
+                docker
+                    .arg("--user")
+                    .arg(format!("{}:{}", metadata.uid(), metadata.gid()));
+            }
+            docker.arg("--workdir").arg(dir);
+        }
+        for mount in &self.mounts {
+            let mut volume = mount.as_os_str().to_owned();
+            volume.push(":");
+            volume.push(mount);
+            docker.arg("--volume").arg(volume);
+        }
+        let envs = self
+            .envs
+            .iter()
+            .map(|(key, value)| (OsString::from(key), value.clone()))
+            .chain(
+                cmd.get_envs()
+                    .filter_map(|(key, value)| Some((key.to_owned(), value?.to_owned()))),
+            );
+        for (key, value) in envs {
+            let mut env = key;
+            env.push("=");
+            env.push(value);
+            docker.arg("--env").arg(env);
+        }
+        docker.arg(&self.image);
+        docker.arg(cmd.get_program());
+        docker.args(cmd.get_args());
+        docker
+    }
+}
+
+#[test]
+fn test_wrap() {
+    let mut container = Container::new("rust:latest".into());
+    container.mount(Path::new("/work"));
+    container.mount(Path::new("/work/target"));
+    container.env("CARGO_HOME", "/home/user/.cargo");
+    let mut cmd = Command::new("cargo");
+    cmd.arg("build").env("RUSTFLAGS", "-Copt-level=3");
+    let docker = container.wrap(&cmd);
+    let args = docker
+        .get_args()
+        .map(|arg| arg.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        args,
+        [
+            "run",
+            "--rm",
+            "--volume",
+            "/work:/work",
+            "--env",
+            "CARGO_HOME=/home/user/.cargo",
+            "--env",
+            "RUSTFLAGS=-Copt-level=3",
+            "rust:latest",
+            "cargo",
+            "build",
+        ]
+    );
+}
@@ -1,19 +1,19 @@
 use crate::config::AndroidDebugConfig;
 use crate::devices::{Backend, Device};
-use crate::{Arch, Platform};
+use crate::{Arch, BuildEnv, Platform, XbuildError};
 use anyhow::{Context, Result};
 use apk::Apk;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdout, Command, Stdio};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Debug)]
 pub(crate) struct Adb(PathBuf);
 
 impl Adb {
     pub fn which() -> Result<Self> {
-        Ok(Self(which::which(exe!("adb"))?))
+        Ok(Self(crate::error::which(exe!("adb"))?))
     }
 
     fn adb(&self, device: &str) -> Command {
@@ -61,11 +61,53 @@ impl Adb {
                 });
             }
         }
+        self.mdns_devices(devices).ok();
+        Ok(())
+    }
+
+    /// Connects to a wireless device given as `host:port`, other serials are
+    /// left alone. Connecting to an already connected device is a no-op.
+    pub fn connect(&self, device: &str) -> Result<()> {
+        if !is_tcp_serial(device) {
+            return Ok(());
+        }
+        let output = Command::new(&self.0).arg("connect").arg(device).output()?;
+        // `adb connect` exits with 0 even when it failed to connect
+        let stdout = std::str::from_utf8(&output.stdout)?.trim();
+        anyhow::ensure!(
+            output.status.success() && stdout.contains("connected to"),
+            "adb connect {} failed: {}{}",
+            device,
+            stdout,
+            std::str::from_utf8(&output.stderr)?.trim()
+        );
+        Ok(())
+    }
+
+    /// Wireless devices advertised over mdns which adb didn't connect to
+    /// automatically, e.g. after `adb tcpip`.
+    fn mdns_devices(&self, devices: &mut Vec<Device>) -> Result<()> {
+        let output = Command::new(&self.0).arg("mdns").arg("services").output()?;
+        anyhow::ensure!(output.status.success(), "adb mdns services failed");
+        for address in parse_mdns_services(std::str::from_utf8(&output.stdout)?) {
+            if devices.iter().any(|device| device.id == address) {
+                continue;
+            }
+            devices.push(Device {
+                backend: Backend::Adb(self.clone()),
+                id: address,
+            });
+        }
         Ok(())
     }
 
     fn getprop(&self, device: &str, prop: &str) -> Result<String> {
         let output = self.shell(device, None).arg("getprop").arg(prop).output()?;
+        let stderr = std::str::from_utf8(&output.stderr)?;
+        anyhow::ensure!(
+            !stderr.contains(&format!("device '{}' not found", device)),
+            XbuildError::DeviceNotFound(format!("adb:{}", device))
+        );
         anyhow::ensure!(
             output.status.success(),
             "adb getprop exited with code {:?}: {}",
@@ -75,6 +117,50 @@ impl Adb {
         Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
     }
 
+    /// Waits until the device is online and has finished booting, which takes a
+    /// while after starting an emulator.
+    fn wait_for_boot(&self, device: &str, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            // `adb wait-for-device` can't time out, so poll the state instead.
+            let online = self
+                .adb(device)
+                .arg("get-state")
+                .output()
+                .map(|output| {
+                    output.status.success()
+                        && String::from_utf8_lossy(&output.stdout).trim() == "device"
+                })
+                .unwrap_or_default();
+            if online && self.getprop(device, "sys.boot_completed").ok().as_deref() == Some("1") {
+                return Ok(());
+            }
+            anyhow::ensure!(
+                start.elapsed() < timeout,
+                "device {} did not finish booting within {}s",
+                device,
+                timeout.as_secs()
+            );
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    /// Streams the apk to the device, requires a `<path>.idsig` v4 signature.
+    fn install_incremental(&self, device: &str, path: &Path) -> Result<()> {
+        let status = self
+            .adb(device)
+            .arg("install")
+            .arg("--incremental")
+            .arg(path)
+            .status()?;
+        anyhow::ensure!(
+            status.success(),
+            "adb install --incremental exited with code {:?}",
+            status.code()
+        );
+        Ok(())
+    }
+
     fn install(&self, device: &str, path: &Path) -> Result<()> {
         let file_name = path.file_name().unwrap().to_str().unwrap();
         self.push(device, path)?;
@@ -93,15 +179,61 @@ impl Adb {
     }
 
     /// To run a native activity use "android.app.NativeActivity" as the activity name
-    fn start(&self, device: &str, package: &str, activity: &str) -> Result<()> {
+    fn start(
+        &self,
+        device: &str,
+        package: &str,
+        activity: &str,
+        profile_startup: bool,
+    ) -> Result<()> {
+        let mut cmd = self.shell(device, None);
+        cmd.arg("am").arg("start");
+        if profile_startup {
+            // waits for the launch to complete and reports its timing
+            cmd.arg("-W");
+        }
+        cmd.arg("-a")
+            .arg("android.intent.action.MAIN")
+            .arg("-n")
+            .arg(format!("{}/{}", package, activity));
+        if !profile_startup {
+            let status = cmd.status()?;
+            anyhow::ensure!(
+                status.success(),
+                "adb shell am start exited with code {:?}",
+                status.code()
+            );
+            return Ok(());
+        }
+        let output = cmd.output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "adb shell am start exited with code {:?}: {}",
+            output.status.code(),
+            std::str::from_utf8(&output.stderr)?.trim()
+        );
+        let rows = parse_am_start_timing(std::str::from_utf8(&output.stdout)?);
+        if rows.is_empty() {
+            tracing::warn!("am start -W reported no startup time");
+        } else {
+            super::print_startup(&rows);
+        }
+        Ok(())
+    }
+
+    /// Sends a `VIEW` intent for `url` to `package`, like following a deep link.
+    fn open_url(&self, device: &str, package: &str, url: &str) -> Result<()> {
+        // the arguments are joined into a single command line by the device shell
+        let url = format!("'{}'", url.replace('\'', r"'\''"));
         let status = self
             .shell(device, None)
             .arg("am")
             .arg("start")
             .arg("-a")
-            .arg("android.intent.action.MAIN")
-            .arg("-n")
-            .arg(format!("{}/{}", package, activity))
+            .arg("android.intent.action.VIEW")
+            .arg("-d")
+            .arg(url)
+            .arg(package)
             .status()?;
         anyhow::ensure!(
             status.success(),
@@ -321,14 +453,17 @@ impl Adb {
             .spawn()?;
         std::thread::sleep(Duration::from_millis(100));
         self.forward(device, 10086)?;
+        // lldb reads the serial from the host name unless it is `localhost`,
+        // which doesn't work for `host:port` serials of wireless devices.
         let status = Command::new("lldb")
+            .env("ANDROID_SERIAL", device)
             .arg("-O")
             .arg("platform select remote-android")
             //.arg("-O")
             //.arg(format!("platform settings -w {}", app_dir.display()))
             //.arg("platform settings -w /data/local/tmp")
             .arg("-O")
-            .arg(format!("platform connect connect://{}:10086", device))
+            .arg("platform connect connect://localhost:10086")
             .arg(executable)
             .status()?;
         anyhow::ensure!(status.success(), "lldb exited with nonzero exit code.");
@@ -336,13 +471,18 @@ impl Adb {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn run(
         &self,
+        env: &BuildEnv,
         device: &str,
         path: &Path,
-        debug_config: &AndroidDebugConfig,
         debug: bool,
+        boot_timeout: Duration,
+        url: Option<&str>,
+        profile_startup: bool,
     ) -> Result<()> {
+        self.wait_for_boot(device, boot_timeout)?;
         let entry_point = Apk::entry_point(path)?;
         let package = &entry_point.package;
         let activity = &entry_point.activity;
@@ -352,10 +492,17 @@ impl Adb {
         } else {
             self.clear_debug_app(device)?;
         }
-        self.install(device, path)?;
-        self.forward_reverse(device, debug_config)?;
+        if env.incremental() {
+            self.install_incremental(device, path)?;
+        } else {
+            self.install(device, path)?;
+        }
+        self.forward_reverse(device, &env.config().android().debug)?;
         let last_timestamp = self.logcat_last_timestamp(device)?;
-        self.start(device, package, activity)?;
+        self.start(device, package, activity, profile_startup)?;
+        if let Some(url) = url {
+            self.open_url(device, package, url)?;
+        }
         let uid = self.uidof(device, package)?;
         let logcat = self.logcat(device, uid, &last_timestamp)?;
         for line in logcat {
@@ -375,14 +522,23 @@ impl Adb {
     pub fn arch(&self, device: &str) -> Result<Arch> {
         let arch = match self.getprop(device, "ro.product.cpu.abi")?.as_str() {
             "arm64-v8a" => Arch::Arm64,
-            //"armeabi-v7a" => Arch::Arm,
+            "armeabi" => Arch::Arm,
             "x86_64" => Arch::X64,
             //"x86" => Arch::X86,
-            abi => anyhow::bail!("unrecognized abi {}", abi),
+            abi => anyhow::bail!(
+                "unsupported abi {} of device adb:{}, only arm64-v8a, armeabi and x86_64 are supported",
+                abi,
+                device
+            ),
         };
         Ok(arch)
     }
 
+    /// Devices without 32-bit abis only run 64-bit libraries.
+    pub fn only_64bit(&self, device: &str) -> Result<bool> {
+        Ok(self.getprop(device, "ro.product.cpu.abilist32")?.is_empty())
+    }
+
     pub fn details(&self, device: &str) -> Result<String> {
         let release = self.getprop(device, "ro.build.version.release")?;
         let sdk = self.getprop(device, "ro.build.version.sdk")?;
@@ -390,6 +546,52 @@ impl Adb {
     }
 }
 
+/// Serials of wireless devices are `host:port`.
+fn is_tcp_serial(device: &str) -> bool {
+    device
+        .rsplit_once(':')
+        .map(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        .unwrap_or_default()
+}
+
+/// Addresses of the `_adb._tcp` services in the output of `adb mdns services`.
+/// `_adb-tls-connect._tcp` services are connected automatically once paired.
+fn parse_mdns_services(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let _name = columns.next()?;
+            let service = columns.next()?;
+            let address = columns.next()?;
+            (service.trim_end_matches('.') == "_adb._tcp" && is_tcp_serial(address))
+                .then(|| address.to_string())
+        })
+        .collect()
+}
+
+/// Launch timings reported by `am start -W`, in the order they're printed.
+fn parse_am_start_timing(output: &str) -> Vec<(&str, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim();
+            let name = match key.trim() {
+                "LaunchState" => "launch state",
+                "TotalTime" => "total time",
+                "WaitTime" => "wait time",
+                _ => return None,
+            };
+            if name == "launch state" {
+                Some((name, value.to_lowercase()))
+            } else {
+                Some((name, format!("{} ms", value.parse::<u64>().ok()?)))
+            }
+        })
+        .collect()
+}
+
 pub struct Logcat {
     child: Child,
     reader: BufReader<ChildStdout>,
@@ -438,3 +640,38 @@ impl Drop for Logcat {
         self.child.kill().ok();
     }
 }
+
+#[test]
+fn test_parse_mdns_services() {
+    let output = "List of discovered mdns services
+adb-R5CT1234567-AbCdEf\t_adb-tls-connect._tcp\t192.168.1.20:37123
+Pixel_7\t_adb._tcp.\t192.168.1.21:5555
+";
+    assert_eq!(parse_mdns_services(output), ["192.168.1.21:5555"]);
+    assert!(is_tcp_serial("192.168.1.21:5555"));
+    assert!(!is_tcp_serial("emulator-5554"));
+    assert!(!is_tcp_serial(
+        "adb-R5CT1234567-AbCdEf._adb-tls-connect._tcp"
+    ));
+}
+
+#[test]
+fn test_parse_am_start_timing() {
+    let output = "Starting: Intent { act=android.intent.action.MAIN cmp=com.example.template/android.app.NativeActivity }
+Status: ok
+LaunchState: COLD
+Activity: com.example.template/android.app.NativeActivity
+TotalTime: 812
+WaitTime: 817
+Complete
+";
+    assert_eq!(
+        parse_am_start_timing(output),
+        [
+            ("launch state", "cold".to_string()),
+            ("total time", "812 ms".to_string()),
+            ("wait time", "817 ms".to_string()),
+        ]
+    );
+    assert!(parse_am_start_timing("Status: ok\nComplete\n").is_empty());
+}
@@ -1,7 +1,9 @@
+use crate::devices::PROFILE_STARTUP_ENV;
 use crate::{Arch, Platform};
 use anyhow::Result;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::Instant;
 
 #[derive(Clone, Debug)]
 pub(crate) struct Host;
@@ -46,9 +48,22 @@ impl Host {
         }
     }
 
-    pub fn run(&self, path: &Path) -> Result<()> {
-        Command::new(path).status()?;
-        Ok(())
+    pub fn run(&self, path: &Path, url: Option<&str>, profile_startup: bool) -> Result<ExitStatus> {
+        if !profile_startup {
+            return Ok(Command::new(path).args(url).status()?);
+        }
+        let start = Instant::now();
+        let mut child = Command::new(path)
+            .args(url)
+            .env(PROFILE_STARTUP_ENV, "1")
+            .stdout(Stdio::piped())
+            .spawn()?;
+        super::forward_startup_output(child.stdout.take().unwrap(), start)?;
+        Ok(child.wait()?)
+    }
+
+    pub fn spawn(&self, path: &Path, url: Option<&str>) -> Result<Child> {
+        Ok(Command::new(path).args(url).spawn()?)
     }
 
     pub fn lldb(&self, executable: &Path) -> Result<()> {
@@ -1,8 +1,9 @@
-use crate::devices::{Backend, Device};
-use crate::{Arch, BuildEnv, Platform};
+use crate::devices::{Backend, Device, PROFILE_STARTUP_ENV};
+use crate::{Arch, BuildEnv, Platform, XbuildError};
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::time::Instant;
 
 #[derive(Clone, Debug)]
 pub(crate) struct IMobileDevice {
@@ -17,12 +18,12 @@ pub(crate) struct IMobileDevice {
 impl IMobileDevice {
     pub fn which() -> Result<Self> {
         Ok(Self {
-            idevice_id: which::which(exe!("idevice_id"))?,
-            ideviceinfo: which::which(exe!("ideviceinfo"))?,
-            ideviceimagemounter: which::which(exe!("ideviceimagemounter"))?,
-            ideviceinstaller: which::which(exe!("ideviceinstaller"))?,
-            idevicedebug: which::which(exe!("idevicedebug"))?,
-            idevicedebugserverproxy: which::which(exe!("idevicedebugserverproxy"))?,
+            idevice_id: crate::error::which(exe!("idevice_id"))?,
+            ideviceinfo: crate::error::which(exe!("ideviceinfo"))?,
+            ideviceimagemounter: crate::error::which(exe!("ideviceimagemounter"))?,
+            ideviceinstaller: crate::error::which(exe!("ideviceinstaller"))?,
+            idevicedebug: crate::error::which(exe!("idevicedebug"))?,
+            idevicedebugserverproxy: crate::error::which(exe!("idevicedebugserverproxy"))?,
         })
     }
 
@@ -33,6 +34,10 @@ impl IMobileDevice {
             .arg("--key")
             .arg(key)
             .output()?;
+        anyhow::ensure!(
+            !String::from_utf8_lossy(&output.stderr).contains("No device found"),
+            XbuildError::DeviceNotFound(format!("imd:{}", device))
+        );
         anyhow::ensure!(output.status.success(), "failed to run ideviceinfo");
         Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
     }
@@ -48,13 +53,28 @@ impl IMobileDevice {
         Ok(())
     }
 
-    fn start(&self, device: &str, bundle_identifier: &str) -> Result<()> {
-        let status = Command::new(&self.idevicedebug)
-            .arg("--udid")
-            .arg(device)
-            .arg("run")
-            .arg(bundle_identifier)
-            .status()?;
+    fn start(
+        &self,
+        device: &str,
+        bundle_identifier: &str,
+        url: Option<&str>,
+        profile_startup: bool,
+    ) -> Result<()> {
+        let mut cmd = Command::new(&self.idevicedebug);
+        cmd.arg("--udid").arg(device);
+        if profile_startup {
+            cmd.arg("--env").arg(format!("{}=1", PROFILE_STARTUP_ENV));
+        }
+        cmd.arg("run").arg(bundle_identifier).args(url);
+        let status = if profile_startup {
+            // idevicedebug forwards the output of the app
+            let start = Instant::now();
+            let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+            super::forward_startup_output(child.stdout.take().unwrap(), start)?;
+            child.wait()?
+        } else {
+            cmd.status()?
+        };
         anyhow::ensure!(status.success(), "failed to run idevicedebug");
         Ok(())
     }
@@ -92,11 +112,19 @@ impl IMobileDevice {
         Ok(())
     }
 
-    pub fn run(&self, env: &BuildEnv, device: &str, path: &Path) -> Result<()> {
+    /// `idevicedebug` can't open urls, so `url` is passed as a launch argument instead.
+    pub fn run(
+        &self,
+        env: &BuildEnv,
+        device: &str,
+        path: &Path,
+        url: Option<&str>,
+        profile_startup: bool,
+    ) -> Result<()> {
         let bundle_identifier = appbundle::app_bundle_identifier(path)?;
         self.mount_disk_image(env, device)?;
         self.install(device, path)?;
-        self.start(device, &bundle_identifier)?;
+        self.start(device, &bundle_identifier, url, profile_startup)?;
         Ok(())
     }
 
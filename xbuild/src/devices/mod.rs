@@ -3,12 +3,54 @@ use crate::devices::host::Host;
 use crate::devices::imd::IMobileDevice;
 use crate::{Arch, BuildEnv, Platform};
 use anyhow::Result;
+use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
+use std::process::{Child, ExitStatus};
+use std::time::{Duration, Instant};
 
 mod adb;
 mod host;
 mod imd;
 
+/// Line printed by apps cooperating with `x run --profile-startup` once the
+/// first frame is rendered, like the app of `x new` does.
+const STARTUP_MARKER: &str = "x: startup complete";
+/// Set for apps launched with `--profile-startup`, so they only print the
+/// [`STARTUP_MARKER`] when it is read.
+const PROFILE_STARTUP_ENV: &str = "X_PROFILE_STARTUP";
+
+fn print_startup(rows: &[(&str, String)]) {
+    println!("{:-^1$}", "startup", 40);
+    for (name, value) in rows {
+        println!("{:20}{}", name, value);
+    }
+    println!("{:-^1$}", "", 40);
+}
+
+/// Forwards the output of an app launched at `start`, reporting the time
+/// until it printed the [`STARTUP_MARKER`].
+fn forward_startup_output(output: impl Read, start: Instant) -> Result<()> {
+    let mut marker = false;
+    for line in BufReader::new(output).lines() {
+        let line = line?;
+        if !marker && line.trim() == STARTUP_MARKER {
+            marker = true;
+            let elapsed = start.elapsed().as_millis();
+            print_startup(&[("first frame", format!("{} ms", elapsed))]);
+            continue;
+        }
+        println!("{}", line);
+    }
+    if !marker {
+        tracing::warn!(
+            "the app didn't print `{}` when `{}` is set, no startup time was measured",
+            STARTUP_MARKER,
+            PROFILE_STARTUP_ENV
+        );
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug)]
 enum Backend {
     Adb(Adb),
@@ -31,7 +73,11 @@ impl std::str::FromStr for Device {
         }
         if let Some((backend, id)) = device.split_once(':') {
             let backend = match backend {
-                "adb" => Backend::Adb(Adb::which()?),
+                "adb" => {
+                    let adb = Adb::which()?;
+                    adb.connect(id)?;
+                    Backend::Adb(adb)
+                }
                 "imd" => Backend::Imd(IMobileDevice::which()?),
                 _ => anyhow::bail!("unsupported backend {}", backend),
             };
@@ -102,6 +148,15 @@ impl Device {
         }
     }
 
+    /// Whether the device only runs 64-bit code. Only android devices are
+    /// checked, the other platforms aren't built for 32-bit archs.
+    pub fn only_64bit(&self) -> Result<bool> {
+        match &self.backend {
+            Backend::Adb(adb) => adb.only_64bit(&self.id),
+            Backend::Host(_) | Backend::Imd(_) => Ok(false),
+        }
+    }
+
     pub fn details(&self) -> Result<String> {
         match &self.backend {
             Backend::Adb(adb) => adb.details(&self.id),
@@ -110,13 +165,39 @@ impl Device {
         }
     }
 
-    pub fn run(&self, env: &BuildEnv, path: &Path) -> Result<()> {
+    /// Installs and launches the app, reporting the startup time when
+    /// `profile_startup` is set. `url` is opened as a deep link on android
+    /// and passed as a launch argument everywhere else.
+    pub fn run(
+        &self,
+        env: &BuildEnv,
+        path: &Path,
+        boot_timeout: Duration,
+        url: Option<&str>,
+        profile_startup: bool,
+    ) -> Result<Option<ExitStatus>> {
+        match &self.backend {
+            Backend::Adb(adb) => adb.run(
+                env,
+                &self.id,
+                path,
+                false,
+                boot_timeout,
+                url,
+                profile_startup,
+            )?,
+            Backend::Host(host) => return Ok(Some(host.run(path, url, profile_startup)?)),
+            Backend::Imd(imd) => imd.run(env, &self.id, path, url, profile_startup)?,
+        }
+        Ok(None)
+    }
+
+    /// Launches the app without waiting for it to exit, only supported on the host.
+    pub fn spawn(&self, path: &Path, url: Option<&str>) -> Result<Child> {
         match &self.backend {
-            Backend::Adb(adb) => adb.run(&self.id, path, &env.config.android().debug, false),
-            Backend::Host(host) => host.run(path),
-            Backend::Imd(imd) => imd.run(env, &self.id, path),
-        }?;
-        Ok(())
+            Backend::Host(host) => host.spawn(path, url),
+            _ => anyhow::bail!("only the host device can run an app in the background"),
+        }
     }
 
     pub fn lldb(
@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Sets the variables of the `.env` file at `path` that aren't set yet, so
+/// the process environment and `.cargo/config.toml` take precedence.
+///
+/// Values may hold secrets and are never logged.
+pub fn load(path: &Path) -> Result<()> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file `{}`", path.display()))?;
+    let vars = parse(&contents).with_context(|| format!("Failed to parse `{}`", path.display()))?;
+    let mut count = 0;
+    for (key, value) in vars {
+        if std::env::var_os(&key).is_none() {
+            std::env::set_var(key, value);
+            count += 1;
+        }
+    }
+    tracing::info!("Loaded {} variables from `{}`", count, path.display());
+    Ok(())
+}
+
+/// Parses `KEY=value` lines, optionally prefixed with `export`. Values may be
+/// single quoted (literal) or double quoted (with `\n`, `\"` and `\\` escapes).
+fn parse(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("line {}: expected `KEY=value`", i + 1))?;
+        let key = key.trim();
+        anyhow::ensure!(
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "line {}: invalid variable name `{}`",
+            i + 1,
+            key
+        );
+        let value = value.trim();
+        let value = if let Some(value) = value.strip_prefix('\'') {
+            value
+                .strip_suffix('\'')
+                .with_context(|| format!("line {}: unterminated `'`", i + 1))?
+                .to_string()
+        } else if let Some(value) = value.strip_prefix('"') {
+            let value = value
+                .strip_suffix('"')
+                .with_context(|| format!("line {}: unterminated `\"`", i + 1))?;
+            unescape(value)
+        } else {
+            // unquoted values end at a comment
+            let value = value.split(" #").next().unwrap();
+            value.trim_end().to_string()
+        };
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[test]
+fn test_parse() {
+    let env = r#"
+# endpoints
+API_URL=https://example.com/api # production
+export API_KEY = 'se#cret'
+GREETING="hello\n\"world\""
+EMPTY=
+"#;
+    let vars = parse(env).unwrap();
+    let vars = vars
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vars,
+        [
+            ("API_URL", "https://example.com/api"),
+            ("API_KEY", "se#cret"),
+            ("GREETING", "hello\n\"world\""),
+            ("EMPTY", ""),
+        ]
+    );
+    assert!(parse("API_URL").is_err());
+    assert!(parse("API URL=1").is_err());
+    assert!(parse("API_URL=\"1").is_err());
+}
@@ -1,8 +1,10 @@
-use crate::{BuildEnv, Platform};
-use anyhow::Result;
+use crate::config::NetworkConfig;
+use crate::{BuildEnv, Platform, XbuildError};
+use anyhow::{Context, Result};
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use mvn::Download;
 use reqwest::blocking::Client;
+use reqwest::{NoProxy, Proxy};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
@@ -24,6 +26,10 @@ impl Download for DownloadManager<'_> {
                 .progress_chars("█▇▆▅▄▃▂▁  ")
         );
         let file_name = dest.file_name().unwrap().to_str().unwrap().to_string();
+        anyhow::ensure!(
+            !self.env.offline(),
+            XbuildError::SdkNotFound(file_name.clone())
+        );
         pb.set_prefix(file_name);
         pb.set_message("📥 downloading");
 
@@ -51,9 +57,24 @@ impl<'a> Download for &'a DownloadManager<'a> {
     }
 }
 
+/// HTTP client using the proxy of `network`. Without a configured proxy the
+/// `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY` environment
+/// variables are used.
+pub fn http_client(network: &NetworkConfig) -> Result<Client> {
+    let mut builder = Client::builder();
+    if let Some(proxy) = network.proxy.as_ref() {
+        let no_proxy = network.no_proxy().as_deref().and_then(NoProxy::from_string);
+        let proxy = Proxy::all(proxy)
+            .with_context(|| format!("invalid network.proxy `{}`", proxy))?
+            .no_proxy(no_proxy);
+        builder = builder.proxy(proxy);
+    }
+    Ok(builder.build()?)
+}
+
 impl<'a> DownloadManager<'a> {
     pub fn new(env: &'a BuildEnv) -> Result<Self> {
-        let client = Client::new();
+        let client = http_client(env.config().network())?;
         let download_dir = env.cache_dir().join("download");
         std::fs::create_dir_all(download_dir)?;
         Ok(Self { env, client })
@@ -115,6 +136,23 @@ impl<'a> DownloadManager<'a> {
     }
 
     fn rustup_target(&self, target: &str) -> Result<()> {
+        if self.env().offline() {
+            let output = Command::new("rustup")
+                .arg("target")
+                .arg("list")
+                .arg("--installed")
+                .output()?;
+            anyhow::ensure!(
+                output.status.success(),
+                "failure running rustup target list"
+            );
+            let installed = std::str::from_utf8(&output.stdout)?;
+            anyhow::ensure!(
+                installed.lines().any(|line| line.trim() == target),
+                XbuildError::MissingTarget(target.into())
+            );
+            return Ok(());
+        }
         let status = Command::new("rustup")
             .arg("target")
             .arg("add")
@@ -242,6 +280,10 @@ impl DownloadManager<'_> {
             .join(format!("android-{}", sdk))
             .join("android.jar");
         if !path.exists() {
+            anyhow::ensure!(
+                !self.env.offline(),
+                XbuildError::SdkNotFound(format!("android-{}/android.jar", sdk))
+            );
             let package = format!("platforms;android-{}", sdk);
             android_sdkmanager::download_and_extract_packages(
                 dir.to_str().unwrap(),
@@ -272,6 +314,9 @@ impl DownloadManager<'_> {
     }
 
     pub fn android_ndk(&self) -> Result<()> {
+        if self.env.android_ndk_override().is_some() {
+            return Ok(());
+        }
         let output = self.env.android_ndk();
         let item = WorkItem::xbuild_release(output, "Android.ndk.tar.zst");
         self.fetch(item)
@@ -0,0 +1,80 @@
+use crate::config::ManifestValidation;
+
+/// Failures of [`BuildEnv::build`](crate::BuildEnv::build) a caller can react
+/// to, e.g. by installing a missing rust target. Everything else is returned
+/// as [`XbuildError::Other`].
+#[derive(Debug, thiserror::Error)]
+pub enum XbuildError {
+    /// A required program is not installed or not in `PATH`
+    #[error("{0} not found, install it or add it to PATH")]
+    MissingTool(String),
+    /// A rust target is not installed, `rustup target add` installs it
+    #[error("required rust target `{0}` not installed; run `rustup target add {0}`")]
+    MissingTarget(String),
+    /// An sdk, ndk or platform artifact is not in the cache when building
+    /// offline
+    #[error("required `{0}` not in cache; run once online")]
+    SdkNotFound(String),
+    /// The signing key couldn't be loaded or used
+    #[error("signing failed: {0}")]
+    SigningFailed(String),
+    /// The selected device is not connected
+    #[error("device {0} not found")]
+    DeviceNotFound(String),
+    /// Required fields of `manifest.yaml` are missing
+    #[error("manifest.yaml is missing required fields: {}", .0.join(", "))]
+    ManifestField(Vec<String>),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for XbuildError {
+    /// Recovers the variant of an error raised internally, looking through
+    /// any context added on the way up.
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<Self>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        match err.downcast::<ManifestValidation>() {
+            Ok(validation) => Self::ManifestField(
+                validation
+                    .missing()
+                    .iter()
+                    .map(|field| field.to_string())
+                    .collect(),
+            ),
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+/// Looks up `tool` in `PATH`, failing with [`XbuildError::MissingTool`].
+pub(crate) fn which(tool: &str) -> anyhow::Result<std::path::PathBuf> {
+    which::which(tool).map_err(|_| XbuildError::MissingTool(tool.into()).into())
+}
+
+#[test]
+fn test_from_anyhow() {
+    use anyhow::Context;
+
+    let err: anyhow::Result<()> = Err(XbuildError::MissingTarget("aarch64-linux-android".into()))
+        .context("failed to build rust");
+    match XbuildError::from(err.unwrap_err()) {
+        XbuildError::MissingTarget(target) => assert_eq!(target, "aarch64-linux-android"),
+        err => panic!("unexpected {:?}", err),
+    }
+    let err = crate::config::test_config(None)
+        .validate(crate::Platform::Windows, crate::Format::Msix)
+        .unwrap_err();
+    match XbuildError::from(err) {
+        XbuildError::ManifestField(fields) => {
+            assert!(fields.contains(&"windows.manifest.identity.name".into()))
+        }
+        err => panic!("unexpected {:?}", err),
+    }
+    assert!(matches!(
+        XbuildError::from(anyhow::anyhow!("other")),
+        XbuildError::Other(_)
+    ));
+}
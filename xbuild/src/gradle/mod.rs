@@ -1,8 +1,10 @@
-use crate::{task, BuildEnv, Format, Opt};
+use crate::{compress, task, BuildEnv, Format, Opt};
 use anyhow::{Context, Result};
+use apk::manifest::GradleConfig;
 use apk::Target;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use xcommon::Exclude;
 
 static BUILD_GRADLE: &[u8] = include_bytes!("./build.gradle");
 static GRADLE_PROPERTIES: &[u8] = include_bytes!("./gradle.properties");
@@ -33,6 +35,55 @@ pub fn prepare(env: &BuildEnv) -> Result<()> {
     Ok(())
 }
 
+/// `systemProp` lines of `gradle.properties` routing the http and https
+/// requests of gradle through `proxy`, gradle ignores `HTTPS_PROXY`.
+fn proxy_properties(proxy: &str, no_proxy: Option<&str>) -> Result<String> {
+    let url = if proxy.contains("://") {
+        reqwest::Url::parse(proxy)
+    } else {
+        reqwest::Url::parse(&format!("http://{}", proxy))
+    }
+    .with_context(|| format!("invalid proxy `{}`", proxy))?;
+    let host = url
+        .host_str()
+        .with_context(|| format!("proxy `{}` has no host", proxy))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let non_proxy_hosts = no_proxy.map(|no_proxy| {
+        no_proxy
+            .split(',')
+            .map(|host| host.trim())
+            .filter(|host| !host.is_empty())
+            .map(|host| match host.strip_prefix('.') {
+                Some(domain) => format!("*.{}", domain),
+                None => host.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    });
+    let mut properties = String::new();
+    for scheme in ["http", "https"] {
+        properties.push_str(&format!("systemProp.{}.proxyHost={}\n", scheme, host));
+        properties.push_str(&format!("systemProp.{}.proxyPort={}\n", scheme, port));
+        if !url.username().is_empty() {
+            properties.push_str(&format!(
+                "systemProp.{}.proxyUser={}\n",
+                scheme,
+                url.username()
+            ));
+        }
+        if let Some(password) = url.password() {
+            properties.push_str(&format!(
+                "systemProp.{}.proxyPassword={}\n",
+                scheme, password
+            ));
+        }
+        if let Some(hosts) = non_proxy_hosts.as_ref().filter(|hosts| !hosts.is_empty()) {
+            properties.push_str(&format!("systemProp.{}.nonProxyHosts={}\n", scheme, hosts));
+        }
+    }
+    Ok(properties)
+}
+
 pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> Result<()> {
     let platform_dir = env.platform_dir();
     let gradle = platform_dir.join("gradle");
@@ -44,23 +95,77 @@ pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> R
 
     std::fs::create_dir_all(&kotlin)?;
     std::fs::write(gradle.join("build.gradle"), BUILD_GRADLE)?;
-    std::fs::write(gradle.join("gradle.properties"), GRADLE_PROPERTIES)?;
-    std::fs::write(gradle.join("settings.gradle"), SETTINGS_GRADLE)?;
+    let mut gradle_properties = GRADLE_PROPERTIES.to_vec();
+    let network = env.config().network();
+    if let Some(proxy) = network.proxy() {
+        gradle_properties.extend_from_slice(b"\n");
+        gradle_properties
+            .extend_from_slice(proxy_properties(&proxy, network.no_proxy().as_deref())?.as_bytes());
+    }
+    std::fs::write(gradle.join("gradle.properties"), gradle_properties)?;
 
     let config = env.config().android();
-    let mut manifest = config.manifest.clone();
-
-    let package = manifest.package.take().unwrap_or_default();
-    let target_sdk = manifest.sdk.target_sdk_version.take().unwrap();
-    let min_sdk = manifest.sdk.min_sdk_version.take().unwrap();
-    let version_code = manifest.version_code.take().unwrap();
-    let version_name = manifest.version_name.take().unwrap();
+    let mut settings_gradle = SETTINGS_GRADLE.to_vec();
+    let assets = main.join("assets");
+    std::fs::remove_dir_all(&assets).ok();
+    std::fs::create_dir_all(&assets)?;
+    let mut asset_packs = vec![];
+    for (name, pack) in &config.asset_packs {
+        if env.android_base_only() {
+            // skips the asset pack modules, their assets go into the base module
+            for path in &pack.paths {
+                copy_asset(&env.assets_dir().join(path), &assets, env.exclude())?;
+            }
+            continue;
+        }
+        let pack_dir = gradle.join(name);
+        let pack_assets = pack_dir.join("src").join("main").join("assets");
+        std::fs::remove_dir_all(&pack_assets).ok();
+        std::fs::create_dir_all(&pack_assets)?;
+        for path in &pack.paths {
+            copy_asset(&env.assets_dir().join(path), &pack_assets, env.exclude())?;
+        }
+        if env.compress_assets() {
+            compress::compress_assets(&pack_assets)?;
+        }
+        let pack_build_gradle = format!(
+            r#"
+                plugins {{
+                    id 'com.android.asset-pack'
+                }}
+                assetPack {{
+                    packName = "{name}"
+                    dynamicDelivery {{
+                        deliveryType = "{delivery}"
+                    }}
+                }}
+            "#,
+            name = name,
+            delivery = pack.delivery.as_str(),
+        );
+        std::fs::write(pack_dir.join("build.gradle"), pack_build_gradle)?;
+        settings_gradle.extend_from_slice(format!("include ':{}'\n", name).as_bytes());
+        asset_packs.push(format!("':{}'", name));
+    }
+    std::fs::write(gradle.join("settings.gradle"), settings_gradle)?;
 
-    manifest.compile_sdk_version = None;
-    manifest.compile_sdk_version_codename = None;
-    manifest.platform_build_version_code = None;
-    manifest.platform_build_version_name = None;
-    manifest.application.debuggable = None;
+    for asset in &config.assets {
+        let path = env.assets_dir().join(asset.path());
+        if !asset.optional() || path.exists() {
+            copy_asset(&path, &assets, env.exclude())?;
+        }
+    }
+    if env.compress_assets() {
+        compress::compress_assets(&assets)?;
+    }
+    let (mut manifest, gradle_config) = config.manifest.for_gradle()?;
+    let GradleConfig {
+        package,
+        min_sdk_version: min_sdk,
+        target_sdk_version: target_sdk,
+        version_code,
+        version_name,
+    } = gradle_config;
 
     let mut dependencies = String::new();
     for dep in &config.dependencies {
@@ -83,6 +188,7 @@ pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> R
                     versionCode {version_code}
                     versionName '{version_name}'
                 }}
+                assetPacks = [{asset_packs}]
             }}
             dependencies {{
                 {dependencies}
@@ -93,15 +199,34 @@ pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> R
         min_sdk = min_sdk,
         version_code = version_code,
         version_name = version_name,
+        asset_packs = asset_packs.join(", "),
         dependencies = dependencies,
     );
 
+    std::fs::remove_dir_all(&res).ok();
+    std::fs::create_dir_all(&res)?;
+    if let Some(dir) = env.android_resources() {
+        xcommon::copy_dir_filtered(&dir, &res, env.exclude())
+            .with_context(|| format!("While copying resources `{}`", dir.display()))?;
+    }
+    if let Some(theme) = config.theme.as_ref() {
+        theme.write_resources(&res)?;
+    }
+
     if let Some(icon_path) = env.icon.as_ref() {
         let mut scaler = xcommon::Scaler::open(icon_path)?;
         scaler.optimize();
+        let monochrome = if let Some(path) = config.monochrome_icon.as_ref() {
+            let mut scaler = xcommon::Scaler::open(env.cargo().package_root().join(path))?;
+            scaler.optimize();
+            Some(scaler)
+        } else {
+            None
+        };
         let anydpi = res.join("mipmap-anydpi-v26");
         std::fs::create_dir_all(&anydpi)?;
         std::fs::write(anydpi.join("ic_launcher.xml"), IC_LAUNCHER)?;
+        std::fs::write(anydpi.join("ic_launcher_round.xml"), IC_LAUNCHER)?;
         let dpis = [
             ("m", 48),
             ("h", 72),
@@ -114,16 +239,28 @@ pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> R
             let dir_name = format!("mipmap-{}dpi", name);
             let dir = res.join(dir_name);
             std::fs::create_dir_all(&dir)?;
-            for variant in ["foreground", "monochrome"] {
-                let mut icon =
-                    std::fs::File::create(dir.join(format!("ic_launcher_{}.png", variant)))?;
+            let variants = [
+                ("ic_launcher", &scaler, false),
+                ("ic_launcher_round", &scaler, true),
+                ("ic_launcher_foreground", &scaler, false),
+                (
+                    "ic_launcher_monochrome",
+                    monochrome.as_ref().unwrap_or(&scaler),
+                    false,
+                ),
+            ];
+            for (variant, scaler, round) in variants {
+                let mut icon = std::fs::File::create(dir.join(format!("{}.png", variant)))?;
                 scaler.write(
                     &mut icon,
-                    xcommon::ScalerOptsBuilder::new(size, size).build(),
+                    xcommon::ScalerOptsBuilder::new(size, size)
+                        .round(round)
+                        .build(),
                 )?;
             }
         }
         manifest.application.icon = Some("@mipmap/ic_launcher".into());
+        manifest.application.round_icon = Some("@mipmap/ic_launcher_round".into());
     }
 
     std::fs::write(app.join("build.gradle"), app_build_gradle)?;
@@ -162,7 +299,16 @@ pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> R
         Format::Apk => "assemble",
         _ => unreachable!(),
     });
-    task::run(cmd, true)?;
+    if let Some(jobs) = env.max_jobs() {
+        cmd.arg(format!("--max-workers={}", jobs));
+    }
+    crate::command::print_intermediates(env, &gradle);
+    task::run(env.command(cmd), true).with_context(|| {
+        format!(
+            "gradle failed, the generated project is in {}",
+            gradle.display()
+        )
+    })?;
     let output = gradle
         .join("app")
         .join("build")
@@ -181,5 +327,36 @@ pub fn build(env: &BuildEnv, libraries: Vec<(Target, PathBuf)>, out: &Path) -> R
             _ => unreachable!(),
         });
     std::fs::copy(output, out)?;
+    if (format, opt) == (Format::Apk, Opt::Release) {
+        // gradle leaves release apks unsigned, sign them so they can be installed
+        apk::Apk::sign(out, env.target().signer().cloned())?;
+    }
     Ok(())
 }
+
+fn copy_asset(path: &Path, dest_dir: &Path, exclude: &Exclude) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .context("Asset must have file_name component")?;
+    let dest = dest_dir.join(file_name);
+    if path.is_dir() {
+        std::fs::create_dir_all(&dest)?;
+        xcommon::copy_dir_filtered(path, &dest, exclude)
+    } else {
+        std::fs::copy(path, &dest).map(|_| ()).map_err(Into::into)
+    }
+    .with_context(|| format!("While copying asset `{}`", path.display()))
+}
+
+#[test]
+fn test_proxy_properties() {
+    let properties =
+        proxy_properties("http://user@proxy.corp:3128", Some("localhost, .corp")).unwrap();
+    assert!(properties.contains("systemProp.https.proxyHost=proxy.corp\n"));
+    assert!(properties.contains("systemProp.https.proxyPort=3128\n"));
+    assert!(properties.contains("systemProp.http.proxyUser=user\n"));
+    assert!(properties.contains("systemProp.http.nonProxyHosts=localhost|*.corp\n"));
+    let properties = proxy_properties("proxy.corp", None).unwrap();
+    assert!(properties.contains("systemProp.http.proxyPort=80\n"));
+    assert!(!properties.contains("nonProxyHosts"));
+}
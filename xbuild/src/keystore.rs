@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use xcommon::Signer;
+
+const STORE_PASSWORD_ENV: &str = "XBUILD_KEYSTORE_STORE_PASSWORD";
+const KEY_PASSWORD_ENV: &str = "XBUILD_KEYSTORE_KEY_PASSWORD";
+
+/// Signing config of an Android Studio style `keystore.properties`.
+pub struct KeystoreProperties {
+    store_file: PathBuf,
+    store_password: String,
+    key_alias: String,
+    key_password: String,
+}
+
+impl std::fmt::Debug for KeystoreProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("KeystoreProperties")
+            .field("store_file", &self.store_file)
+            .field("key_alias", &self.key_alias)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeystoreProperties {
+    /// Reads the properties file, a relative `storeFile` is resolved against
+    /// the directory of the properties file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let root = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&contents, root).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    fn parse(contents: &str, root: &Path) -> Result<Self> {
+        let mut properties = parse_properties(contents);
+        let mut take = |key: &str| {
+            properties
+                .remove(key)
+                .with_context(|| format!("missing `{}`", key))
+        };
+        Ok(Self {
+            store_file: root.join(take("storeFile")?),
+            store_password: take("storePassword")?,
+            key_alias: take("keyAlias")?,
+            key_password: take("keyPassword")?,
+        })
+    }
+
+    /// Exports the key with `keytool` and `openssl`. The passwords are passed
+    /// through the environment so they don't show up in process listings.
+    pub fn signer(&self) -> Result<Signer> {
+        anyhow::ensure!(
+            self.store_file.exists(),
+            "keystore doesn't exist {}",
+            self.store_file.display()
+        );
+        let dir = std::env::temp_dir().join(format!("xbuild-keystore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let p12 = dir.join("key.p12");
+        let result = self.export_pem(&p12);
+        std::fs::remove_dir_all(&dir).ok();
+        Signer::new(&result?).context("failed to load the key of the keystore")
+    }
+
+    fn export_pem(&self, p12: &Path) -> Result<String> {
+        let output = Command::new("keytool")
+            .env(STORE_PASSWORD_ENV, &self.store_password)
+            .env(KEY_PASSWORD_ENV, &self.key_password)
+            .arg("-importkeystore")
+            .arg("-noprompt")
+            .arg("-srckeystore")
+            .arg(&self.store_file)
+            .arg("-srcalias")
+            .arg(&self.key_alias)
+            .arg("-srcstorepass:env")
+            .arg(STORE_PASSWORD_ENV)
+            .arg("-srckeypass:env")
+            .arg(KEY_PASSWORD_ENV)
+            .arg("-destkeystore")
+            .arg(p12)
+            .arg("-deststoretype")
+            .arg("PKCS12")
+            .arg("-deststorepass:env")
+            .arg(STORE_PASSWORD_ENV)
+            .arg("-destkeypass:env")
+            .arg(STORE_PASSWORD_ENV)
+            .output()
+            .context("failed to run keytool")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "keytool failed to export `{}` from {}: {}",
+            self.key_alias,
+            self.store_file.display(),
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+        let output = Command::new("openssl")
+            .env(STORE_PASSWORD_ENV, &self.store_password)
+            .arg("pkcs12")
+            .arg("-in")
+            .arg(p12)
+            .arg("-nodes")
+            .arg("-clcerts")
+            .arg("-passin")
+            .arg(format!("env:{}", STORE_PASSWORD_ENV))
+            .output()
+            .context("failed to run openssl")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "openssl failed to read the exported key: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+/// Parses the `key=value` and `key: value` lines of a java properties file.
+fn parse_properties(contents: &str) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let mut key = String::new();
+        let mut value = None;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            let c = match c {
+                '\\' => match chars.next() {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some(c) => c,
+                    None => break,
+                },
+                '=' | ':' if value.is_none() => {
+                    value = Some(String::new());
+                    continue;
+                }
+                c => c,
+            };
+            value.as_mut().unwrap_or(&mut key).push(c);
+        }
+        properties.insert(
+            key.trim().to_string(),
+            value.unwrap_or_default().trim().to_string(),
+        );
+    }
+    properties
+}
+
+#[test]
+fn test_keystore_properties() {
+    let contents = r#"
+# signing config
+storePassword=store pass
+keyPassword = key\=pass
+keyAlias: upload
+storeFile=keys\\release.jks
+"#;
+    let keystore = KeystoreProperties::parse(contents, Path::new("/app")).unwrap();
+    assert_eq!(
+        keystore.store_file,
+        Path::new("/app").join("keys\\release.jks")
+    );
+    assert_eq!(keystore.store_password, "store pass");
+    assert_eq!(keystore.key_password, "key=pass");
+    assert_eq!(keystore.key_alias, "upload");
+    assert!(!format!("{:?}", keystore).contains("pass"));
+    assert!(KeystoreProperties::parse("storeFile=a.jks", Path::new("")).is_err());
+}
@@ -1,10 +1,16 @@
 use crate::cargo::{Cargo, CargoBuild, CrateType};
-use crate::config::Config;
+use crate::config::{Config, DebugInfo};
+use crate::container::Container;
 use crate::devices::Device;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use appbundle::ProvisioningProfile;
 use clap::{Parser, ValueEnum};
 use std::path::{Path, PathBuf};
-use xcommon::Signer;
+use std::process::Command;
+use std::time::Duration;
+use xcommon::{Exclude, Pkcs11Key, Signer, ZipCompression};
+
+pub use crate::error::XbuildError;
 
 #[macro_export]
 macro_rules! exe {
@@ -17,12 +23,20 @@ macro_rules! exe {
     };
 }
 
+mod app_store;
 pub mod cargo;
 pub mod command;
+mod compress;
 mod config;
+mod container;
 mod devices;
+mod dotenv;
 mod download;
+mod error;
 mod gradle;
+mod keystore;
+mod ndk;
+mod target_sdk;
 mod task;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -44,8 +58,12 @@ impl std::fmt::Display for Opt {
 pub enum Platform {
     Android,
     Ios,
+    // `native` selects the host platform
+    #[cfg_attr(target_os = "linux", value(alias = "native"))]
     Linux,
+    #[cfg_attr(target_os = "macos", value(alias = "native"))]
     Macos,
+    #[cfg_attr(target_os = "windows", value(alias = "native"))]
     Windows,
 }
 
@@ -74,13 +92,23 @@ impl std::fmt::Display for Platform {
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
 pub enum Arch {
-    //Arm,
+    /// 32-bit arm, only supported on android
+    Arm,
+    // `native` selects the host arch
+    #[cfg_attr(target_arch = "aarch64", value(alias = "native"))]
     Arm64,
+    #[cfg_attr(target_arch = "x86_64", value(alias = "native"))]
     X64,
     //X86,
 }
 
 impl Arch {
+    /// Archs of a universal android apk, which installs on devices and
+    /// emulators.
+    pub fn universal() -> &'static [Self] {
+        &[Self::Arm64, Self::X64]
+    }
+
     pub fn host() -> Result<Self> {
         if cfg!(target_arch = "x86_64") {
             Ok(Arch::X64)
@@ -153,9 +181,32 @@ impl Format {
         }
     }
 
+    /// Formats that are signed, with the debug key when no signer is given.
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            Self::Aab | Self::Apk | Self::Appbundle | Self::Dmg | Self::Ipa | Self::Msix
+        )
+    }
+
     pub fn supports_multiarch(self) -> bool {
         matches!(self, Self::Aab | Self::Apk)
     }
+
+    /// Formats that can be produced for `platform`.
+    pub fn valid_for(platform: Platform) -> &'static [Self] {
+        match platform {
+            Platform::Android => &[Self::Aab, Self::Apk],
+            Platform::Ios => &[Self::Ipa, Self::Appbundle],
+            Platform::Linux => &[Self::Appdir, Self::Appimage],
+            Platform::Macos => &[Self::Appbundle, Self::Dmg],
+            Platform::Windows => &[Self::Exe, Self::Msix],
+        }
+    }
+
+    pub fn is_valid_for(self, platform: Platform) -> bool {
+        Self::valid_for(platform).contains(&self)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
@@ -206,6 +257,7 @@ impl CompileTarget {
     pub fn android_abi(self) -> apk::Target {
         assert_eq!(self.platform(), Platform::Android);
         match self.arch() {
+            Arch::Arm => apk::Target::Armeabi,
             Arch::Arm64 => apk::Target::Arm64V8a,
             Arch::X64 => apk::Target::X86_64,
         }
@@ -216,14 +268,24 @@ impl CompileTarget {
         assert_eq!(self.platform(), Platform::Android);
         match self.arch() {
             Arch::Arm64 => "aarch64-linux-android",
-            //Arch::Arm => "arm-linux-androideabi",
+            Arch::Arm => "arm-linux-androideabi",
             //Arch::X86 => "i686-linux-android",
             Arch::X64 => "x86_64-linux-android",
         }
     }
 
+    /// Returns the triple clang uses for the NDK, which differs from
+    /// [`CompileTarget::ndk_triple`] for 32-bit arm
+    pub fn clang_triple(self) -> &'static str {
+        match self.arch() {
+            Arch::Arm => "armv7a-linux-androideabi",
+            _ => self.ndk_triple(),
+        }
+    }
+
     pub fn rust_triple(self) -> Result<&'static str> {
         Ok(match (self.arch, self.platform) {
+            (Arch::Arm, Platform::Android) => "arm-linux-androideabi",
             (Arch::Arm64, Platform::Android) => "aarch64-linux-android",
             (Arch::Arm64, Platform::Ios) => "aarch64-apple-ios",
             (Arch::Arm64, Platform::Linux) => "aarch64-unknown-linux-gnu",
@@ -251,20 +313,123 @@ impl std::fmt::Display for CompileTarget {
     }
 }
 
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 pub struct BuildArgs {
     #[clap(flatten)]
     build_target: BuildTargetArgs,
     #[clap(flatten)]
     cargo: CargoArgs,
-    /// Use verbose output
-    #[clap(long, short)]
-    verbose: bool,
+    /// Use verbose output, repeat to also log at warn, info, debug and
+    /// trace level unless `XBUILD_LOG` is set. Warnings of xbuild itself are
+    /// always shown
+    #[clap(long, short, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Write an APK v4 signature and install with `adb install --incremental`
+    #[clap(long)]
+    incremental: bool,
+    /// Compression of apk, ipa and msix archives: none, fast or best.
+    /// Defaults to none for debug and best for release builds
+    #[clap(long)]
+    compress: Option<ZipCompression>,
+    /// Comma separated android min sdk versions, builds one apk per version
+    /// for compatibility testing
+    #[clap(long, value_delimiter = ',')]
+    min_sdk: Vec<u32>,
+    /// Set on the arguments of each build of several `--min-sdk` versions
+    #[clap(skip)]
+    min_sdk_variant: bool,
+    /// Android flavor defined in `android.flavors` of manifest.yaml
+    #[clap(long)]
+    flavor: Option<String>,
+    /// Print the environment variables set for cargo, also enabled by
+    /// `--verbose`
+    #[clap(long)]
+    print_cargo_env: bool,
+    /// Run cargo and gradle in a container of this docker image, which has
+    /// to provide the toolchains. Supported for android and linux
+    #[clap(long)]
+    container: Option<String>,
+    /// Directory the `assets` of manifest.yaml are resolved against instead
+    /// of the package root, e.g. when assets are generated elsewhere
+    #[clap(long)]
+    assets_from: Option<PathBuf>,
+    /// Maximum number of parallel jobs of cargo, gradle and icon encoding.
+    /// Defaults to the number of CPUs
+    #[clap(long = "max-jobs", short = 'j')]
+    jobs: Option<usize>,
+    /// Print plain progress lines without redrawing them, also enabled when
+    /// `CI` is set or stdout isn't a terminal
+    #[clap(long)]
+    ci: bool,
+    /// Package the android asset packs into the base module instead of
+    /// separate gradle modules, for faster debug builds
+    #[clap(long)]
+    android_app_bundle_base_only: bool,
+    /// Save the unstripped libraries and executables into this directory as
+    /// `<arch>/<build id>/<name>` before stripping them. Android builds also
+    /// get a `<name>.debug` file linked with `.gnu_debuglink`
+    #[clap(long)]
+    strip_symbols_to: Option<PathBuf>,
+    /// Strip desktop binaries and place their debug info next to them: a
+    /// `.debug` file on linux, a `.dSYM` on macos and the `.pdb` on windows.
+    /// Builds with full debug info unless `debug_info` is set
+    #[clap(long, conflicts_with = "strip_symbols_to")]
+    split_debug: bool,
+    /// Android `res/` directory compiled into the apk, overrides
+    /// `android.resources`
+    #[clap(long)]
+    android_resources: Option<PathBuf>,
+    /// Keep the AppDir and app bundle staging directories of previous builds
+    /// instead of recreating them, and print where the generated gradle
+    /// project and staging directories are for inspecting failed builds
+    #[clap(long, alias = "no-cleanup")]
+    keep_intermediates: bool,
+    /// Add `.gz` and `.br` copies of text assets like html, css and js to
+    /// the apk, for webviews serving pre-compressed assets. Copies which
+    /// aren't smaller than the asset are skipped
+    #[clap(long)]
+    compress_assets: bool,
+}
+
+/// Log filter without `--verbose`. Warnings of xbuild are always shown, while
+/// dependencies only log errors.
+pub const DEFAULT_LOG_FILTER: &str = "error,xbuild=warn";
+
+impl BuildArgs {
+    /// Log filter selected by repeating `--verbose`.
+    pub fn log_filter(&self) -> &'static str {
+        match self.verbose {
+            0 => DEFAULT_LOG_FILTER,
+            1 => "warn",
+            2 => "info",
+            3 => "debug",
+            _ => "trace",
+        }
+    }
+
+    /// Splits `--min-sdk 21,24` into one set of arguments per min sdk
+    /// version, in ascending order.
+    pub fn min_sdk_variants(mut self) -> Vec<Self> {
+        if self.min_sdk.len() < 2 {
+            return vec![self];
+        }
+        let mut min_sdks = std::mem::take(&mut self.min_sdk);
+        min_sdks.sort_unstable();
+        min_sdks.dedup();
+        min_sdks
+            .into_iter()
+            .map(|min_sdk| Self {
+                min_sdk: vec![min_sdk],
+                min_sdk_variant: true,
+                ..self.clone()
+            })
+            .collect()
+    }
 }
 
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 pub struct CargoArgs {
-    /// Cargo package to build
+    /// Cargo package to build, by name or by the path of its directory
     #[clap(long, short)]
     package: Option<String>,
     /// Path to Cargo.toml
@@ -293,7 +458,7 @@ impl CargoArgs {
     }
 }
 
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 pub struct BuildTargetArgs {
     /// Build artifacts in debug mode, without optimizations
     #[clap(long, conflicts_with = "release")]
@@ -301,12 +466,22 @@ pub struct BuildTargetArgs {
     /// Build artifacts in release mode, with optimizations
     #[clap(long, short, conflicts_with = "debug")]
     release: bool,
-    /// Build artifacts for target platform.
+    /// Build artifacts for target platform, `native` for the host platform.
     #[clap(long, conflicts_with = "device")]
     platform: Option<Platform>,
-    /// Build artifacts for target arch.
+    /// Build artifacts for target arch, `native` for the host arch.
     #[clap(long, requires = "platform")]
     arch: Option<Arch>,
+    /// Build a fat apk for all common android archs, regardless of the
+    /// attached devices.
+    #[clap(
+        long,
+        requires = "platform",
+        conflicts_with = "arch",
+        conflicts_with = "store",
+        conflicts_with = "device"
+    )]
+    universal: bool,
     /// Build artifacts for target device. To find the device
     /// identifier of a connected device run `x devices`.
     #[clap(long, conflicts_with = "store")]
@@ -317,28 +492,72 @@ pub struct BuildTargetArgs {
     /// Build artifacts for target app store.
     #[clap(long, conflicts_with = "device", conflicts_with = "format")]
     store: Option<Store>,
+    /// Remove an arch from the archs built for the store, can be repeated.
+    #[clap(long, requires = "store", conflicts_with = "arch")]
+    exclude_arch: Vec<Arch>,
+    /// Add an arch to the archs built for the store, can be repeated.
+    #[clap(long, requires = "store", conflicts_with = "arch")]
+    include_arch: Vec<Arch>,
     /// Path to a PEM encoded RSA2048 signing key and certificate
-    /// used to sign artifacts.
+    /// used to sign artifacts. When `X_PKCS11_MODULE` is set, only the
+    /// certificate is read and the key is used from the PKCS#11 token.
     #[clap(long)]
     pem: Option<PathBuf>,
-    /// Path to an apple provisioning profile.
+    /// Path to an apple provisioning profile. Can be repeated for apps with
+    /// multiple bundle identifiers, the profile matching the bundle
+    /// identifier is selected when signing.
+    #[clap(long)]
+    provisioning_profile: Vec<PathBuf>,
+    /// Name or SHA-1 hash of a code signing identity in the macos keychain,
+    /// used instead of `--pem` for apple platforms.
+    #[clap(long, conflicts_with = "pem")]
+    signing_identity: Option<String>,
+    /// Fail release builds that would be signed with the debug key because
+    /// no `--pem` or `--signing-identity` is given. Implied by `--store`.
     #[clap(long)]
-    provisioning_profile: Option<PathBuf>,
-    /// Path to an api key.
+    require_signing: bool,
+    /// Path to an App Store Connect api key. Used to notarize macos builds
+    /// and to upload ios builds to TestFlight with `--store apple`.
     #[clap(long)]
     api_key: Option<PathBuf>,
+    /// Minutes to wait for the notarization of macos builds before failing
+    /// with the submission id
+    #[clap(long, default_value = "60")]
+    notarize_timeout: u64,
+    /// Seconds between the first polls of the notarization status, the
+    /// interval doubles up to a minute
+    #[clap(long, default_value = "3", value_parser = clap::value_parser!(u64).range(1..))]
+    notarize_poll_interval: u64,
+    /// Overrides the android package, the apple bundle identifier and the
+    /// msix identity name, e.g. to build `com.example.app.dev`
+    #[clap(long, alias = "bundle-id")]
+    app_id: Option<String>,
+}
+
+/// Creates a signer from `pem`, falling back to `X_PEM`. The key is used from
+/// a PKCS#11 token when `X_PKCS11_MODULE` is set.
+pub fn load_signer(pem: Option<&Path>) -> Result<Option<Signer>> {
+    let pem = if let Some(pem) = pem {
+        anyhow::ensure!(pem.exists(), "pem file doesn't exist {}", pem.display());
+        Some(std::fs::read_to_string(pem)?)
+    } else {
+        std::env::var("X_PEM").ok()
+    };
+    Ok(match (pem, Pkcs11Key::from_env()) {
+        (Some(pem), Some(key)) => {
+            Some(Signer::with_pkcs11(&pem, key).context("Failed to create PKCS#11 signer")?)
+        }
+        (Some(pem), None) => Some(Signer::new(&pem)?),
+        (None, Some(_)) => {
+            anyhow::bail!("X_PKCS11_MODULE requires a certificate passed with --pem or X_PEM")
+        }
+        (None, None) => None,
+    })
 }
 
 impl BuildTargetArgs {
     pub fn build_target(self, config: &Config) -> Result<BuildTarget> {
-        let signer = if let Some(pem) = self.pem.as_ref() {
-            anyhow::ensure!(pem.exists(), "pem file doesn't exist {}", pem.display());
-            Some(Signer::from_path(pem)?)
-        } else if let Ok(pem) = std::env::var("X_PEM") {
-            Some(Signer::new(&pem)?)
-        } else {
-            None
-        };
+        let signer = load_signer(self.pem.as_deref())?;
         let store = self.store;
         let device = if self.platform.is_none() && store.is_none() && self.device.is_none() {
             Some(Device::host())
@@ -355,7 +574,7 @@ impl BuildTargetArgs {
                 Store::Apple => anyhow::bail!("apple store requires platform arg"),
                 Store::Microsoft => Platform::Windows,
                 Store::Play => Platform::Android,
-                Store::Sideload => anyhow::bail!("sideload store requires platform arg"),
+                Store::Sideload => Platform::host()?,
             }
         } else if let Some(device) = device.as_ref() {
             device.platform()?
@@ -364,15 +583,56 @@ impl BuildTargetArgs {
         };
         let archs = if let Some(arch) = self.arch {
             vec![arch]
+        } else if self.universal {
+            anyhow::ensure!(
+                platform == Platform::Android,
+                "--universal is only supported for android"
+            );
+            Arch::universal().to_vec()
         } else if let Some(store) = store {
-            match store {
+            let mut archs = match store {
+                Store::Apple if platform == Platform::Ios => vec![Arch::Arm64],
                 Store::Apple => vec![Arch::X64, Arch::Arm64],
                 Store::Microsoft => vec![Arch::X64],
                 Store::Play => vec![Arch::Arm64],
-                Store::Sideload => anyhow::bail!("sideload store requires arch arg"),
+                // sideloaded apks should install on both devices and emulators
+                Store::Sideload => match platform {
+                    Platform::Android => vec![Arch::Arm64, Arch::X64],
+                    Platform::Ios => vec![Arch::Arm64],
+                    _ => vec![Arch::host()?],
+                },
+            };
+            for arch in &self.include_arch {
+                if !archs.contains(arch) {
+                    archs.push(*arch);
+                }
+            }
+            archs.retain(|arch| !self.exclude_arch.contains(arch));
+            anyhow::ensure!(
+                !archs.is_empty(),
+                "--exclude-arch removed all archs of the {} store",
+                store
+            );
+            for arch in &archs {
+                CompileTarget::new(platform, *arch, Opt::Debug).rust_triple()?;
             }
+            archs
         } else if let Some(device) = device.as_ref() {
-            vec![device.arch()?]
+            // only the arch of the device is built
+            let arch = device.arch()?;
+            CompileTarget::new(platform, arch, Opt::Debug)
+                .rust_triple()
+                .with_context(|| format!("can't build for device {} ({})", device, arch))?;
+            if platform == Platform::Android && device.only_64bit()? {
+                for triple in config.android_32bit_targets() {
+                    tracing::warn!(
+                        "device {} only runs 64-bit apps, target.{} is not used",
+                        device,
+                        triple
+                    );
+                }
+            }
+            vec![arch]
         } else {
             anyhow::bail!("--arch, --store or --device must be provided");
         };
@@ -381,27 +641,93 @@ impl BuildTargetArgs {
         } else {
             Opt::Debug
         };
+        anyhow::ensure!(
+            !self.universal || self.format != Some(Format::Aab),
+            "--universal builds an apk, use --store play for app bundles"
+        );
         let format = if let Some(format) = self.format {
             format
         } else if store == Some(Store::Play) {
             Format::Aab
+        } else if self.universal
+            || (store == Some(Store::Sideload) && platform == Platform::Android)
+        {
+            Format::Apk
         } else {
             Format::platform_default(platform, opt, config.android().gradle)
         };
-        let provisioning_profile = if let Some(profile) = self.provisioning_profile {
+        if !format.is_valid_for(platform) {
+            let valid = Format::valid_for(platform)
+                .iter()
+                .map(|format| format.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            anyhow::bail!(
+                "format {} is not supported on {}, expected one of: {}",
+                format,
+                platform,
+                valid
+            );
+        }
+        // release apks and app bundles are signed with the keystore of an
+        // Android Studio project unless a pem is passed
+        let signer = match (signer, &config.android().keystore_properties) {
+            (None, Some(path)) if platform == Platform::Android && opt == Opt::Release => {
+                let signer = keystore::KeystoreProperties::load(path)
+                    .and_then(|keystore| keystore.signer())
+                    .map_err(|err| XbuildError::SigningFailed(format!("{:#}", err)))?;
+                Some(signer)
+            }
+            (signer, _) => signer,
+        };
+        let mut raw_profiles = vec![];
+        for profile in &self.provisioning_profile {
             anyhow::ensure!(
                 profile.exists(),
                 "provisioning profile doesn't exist {}",
                 profile.display()
             );
-            Some(std::fs::read(profile)?)
-        } else if let Ok(mut profile) = std::env::var("X_PROVISIONING_PROFILE") {
-            profile.retain(|c| !c.is_whitespace());
-            Some(base64::decode(&profile)?)
-        } else {
-            None
-        };
+            raw_profiles.push(std::fs::read(profile)?);
+        }
+        if raw_profiles.is_empty() {
+            if let Ok(mut profiles) = std::env::var("X_PROVISIONING_PROFILE") {
+                // multiple base64 encoded profiles are separated by commas
+                profiles.retain(|c| !c.is_whitespace());
+                for profile in profiles.split(',') {
+                    raw_profiles.push(base64::decode(profile)?);
+                }
+            }
+        }
+        let mut provisioning_profiles: Vec<ProvisioningProfile> = vec![];
+        for raw in raw_profiles {
+            let profile = ProvisioningProfile::parse(raw)?;
+            anyhow::ensure!(
+                provisioning_profiles
+                    .iter()
+                    .all(|other| other.app_id() != profile.app_id()),
+                "multiple provisioning profiles for app id {}",
+                profile.app_id()
+            );
+            provisioning_profiles.push(profile);
+        }
+        let signing_identity = self
+            .signing_identity
+            .as_deref()
+            .map(appbundle::keychain_identity)
+            .transpose()?;
+        if (self.require_signing || store.is_some()) && opt == Opt::Release && format.is_signed() {
+            anyhow::ensure!(
+                signer.is_some() || signing_identity.is_some(),
+                XbuildError::SigningFailed(format!(
+                    "release {} requires a --pem or --signing-identity, refusing to sign with the debug key",
+                    format
+                ))
+            );
+        }
         let api_key = self.api_key;
+        if let Some(app_id) = self.app_id.as_deref() {
+            config::validate_app_id(platform, app_id)?;
+        }
         Ok(BuildTarget {
             opt,
             platform,
@@ -410,8 +736,12 @@ impl BuildTargetArgs {
             device,
             store,
             signer,
-            provisioning_profile,
+            provisioning_profiles,
+            signing_identity,
             api_key,
+            notarize_timeout: Duration::from_secs(self.notarize_timeout * 60),
+            notarize_poll_interval: Duration::from_secs(self.notarize_poll_interval),
+            app_id: self.app_id,
         })
     }
 }
@@ -425,8 +755,12 @@ pub struct BuildTarget {
     device: Option<Device>,
     store: Option<Store>,
     signer: Option<Signer>,
-    provisioning_profile: Option<Vec<u8>>,
+    provisioning_profiles: Vec<ProvisioningProfile>,
+    signing_identity: Option<String>,
     api_key: Option<PathBuf>,
+    notarize_timeout: Duration,
+    notarize_poll_interval: Duration,
+    app_id: Option<String>,
 }
 
 impl BuildTarget {
@@ -471,53 +805,281 @@ impl BuildTarget {
         self.signer.as_ref()
     }
 
-    pub fn provisioning_profile(&self) -> Option<&[u8]> {
-        self.provisioning_profile.as_deref()
+    /// SHA-1 hash of the keychain identity selected with `--signing-identity`.
+    pub fn signing_identity(&self) -> Option<&str> {
+        self.signing_identity.as_deref()
+    }
+
+    pub fn provisioning_profiles(&self) -> &[ProvisioningProfile] {
+        &self.provisioning_profiles
     }
 
     pub fn api_key(&self) -> Option<&Path> {
         self.api_key.as_deref()
     }
+
+    pub fn notarize_timeout(&self) -> Duration {
+        self.notarize_timeout
+    }
+
+    pub fn notarize_poll_interval(&self) -> Duration {
+        self.notarize_poll_interval
+    }
+
+    /// Application id set with `--app-id`.
+    pub fn app_id(&self) -> Option<&str> {
+        self.app_id.as_deref()
+    }
 }
 
 pub struct BuildEnv {
     name: String,
+    output_name: String,
     build_target: BuildTarget,
     build_dir: PathBuf,
     cache_dir: PathBuf,
     icon: Option<PathBuf>,
+    assets_dir: PathBuf,
+    exclude: Exclude,
     cargo: Cargo,
     config: Config,
     verbose: bool,
     offline: bool,
+    incremental: bool,
+    compression: ZipCompression,
+    container: Option<Container>,
+    print_cargo_env: bool,
+    jobs: Option<usize>,
+    ci: bool,
+    android_base_only: bool,
+    strip_symbols_to: Option<PathBuf>,
+    split_debug: bool,
+    android_resources: Option<PathBuf>,
+    keep_intermediates: bool,
+    compress_assets: bool,
 }
 
 impl BuildEnv {
+    /// Builds the artifact of [`BuildEnv::target`] like `x build`. Failures
+    /// a caller can react to are returned as the variants of [`XbuildError`],
+    /// errors of [`BuildEnv::new`] convert with [`XbuildError::from`].
+    pub fn build(&self) -> std::result::Result<(), XbuildError> {
+        command::build(self).map_err(XbuildError::from)
+    }
+
     pub fn new(args: BuildArgs) -> Result<Self> {
-        let verbose = args.verbose;
+        let verbose = args.verbose > 0;
+        let print_cargo_env = args.print_cargo_env || verbose;
         let offline = args.cargo.offline;
+        let incremental = args.incremental;
+        let compress = args.compress;
+        anyhow::ensure!(
+            args.min_sdk.len() < 2,
+            "multiple min sdk versions are only supported by `x build`"
+        );
+        let min_sdk = args.min_sdk.first().copied();
+        let min_sdk_variant = args.min_sdk_variant;
+        let container = args.container;
+        let flavor = args.flavor;
+        let jobs = args.jobs;
+        let ci = args.ci;
+        let android_base_only = args.android_app_bundle_base_only;
+        let strip_symbols_to = args.strip_symbols_to;
+        let split_debug = args.split_debug;
+        let android_resources = args.android_resources;
+        let keep_intermediates = args.keep_intermediates;
+        let compress_assets = args.compress_assets;
+        anyhow::ensure!(jobs != Some(0), "--max-jobs must be at least 1");
         let cargo = args.cargo.cargo()?;
+        let assets_dir = if let Some(dir) = args.assets_from {
+            anyhow::ensure!(
+                dir.is_dir(),
+                "--assets-from {} is not a directory",
+                dir.display()
+            );
+            dunce::canonicalize(dir)?
+        } else {
+            cargo.package_root().to_path_buf()
+        };
         let build_dir = cargo.target_dir().join("x");
         let cache_dir = dirs::cache_dir().unwrap().join("x");
+        xcommon::Scaler::set_cache_dir(cache_dir.join("icons"));
         let package = cargo.manifest().package.as_ref().unwrap(); // Caller should guarantee that this is a valid package
         let manifest = cargo.package_root().join("manifest.yaml");
         let mut config = Config::parse(manifest)?;
+        // rustup, cargo and the sdk manager read the proxy from the environment
+        let network = config.network();
+        if let Some(proxy) = &network.proxy {
+            std::env::set_var("HTTPS_PROXY", proxy);
+            std::env::set_var("HTTP_PROXY", proxy);
+        }
+        if let Some(no_proxy) = &network.no_proxy {
+            std::env::set_var("NO_PROXY", no_proxy);
+        }
         let build_target = args.build_target.build_target(&config)?;
-        config.apply_rust_package(package, cargo.workspace_manifest(), build_target.opt())?;
+        if let Some(min_sdk) = min_sdk {
+            anyhow::ensure!(
+                build_target.platform() == Platform::Android,
+                "--min-sdk requires --platform android"
+            );
+            if min_sdk_variant {
+                config.set_android_min_sdk_variant(min_sdk);
+            } else {
+                config.set_android_min_sdk(min_sdk);
+            }
+        }
+        anyhow::ensure!(
+            !android_base_only || build_target.platform() == Platform::Android,
+            "--android-app-bundle-base-only requires --platform android"
+        );
+        if let Some(flavor) = flavor.as_deref() {
+            anyhow::ensure!(
+                build_target.platform() == Platform::Android,
+                "--flavor requires --platform android"
+            );
+            config.set_android_flavor(flavor)?;
+        }
+        config.apply_rust_package(
+            package,
+            cargo.workspace_manifest(),
+            build_target.opt(),
+            build_target.app_id(),
+        )?;
+        config.validate(build_target.platform(), build_target.format())?;
+        config.validate_targets()?;
+        config.warn_deprecated(build_target.platform());
+        if let Some(env_file) = config.env_file(build_target.platform()) {
+            dotenv::load(&cargo.package_root().join(env_file))?;
+        } else {
+            let env_file = cargo.package_root().join(".env");
+            if env_file.exists() {
+                dotenv::load(&env_file)?;
+            }
+        }
         let icon = config
             .icon(build_target.platform())
             .map(|icon| cargo.package_root().join(icon));
-        Ok(Self {
+        if let Some(filter) = config.icon_filter(build_target.platform()) {
+            xcommon::Scaler::set_default_filter(filter.to_scaler_filter());
+        }
+        let exclude = Exclude::new(&config.exclude(build_target.platform()))?;
+        let compression = compress.unwrap_or(match build_target.opt() {
+            Opt::Debug => ZipCompression::None,
+            Opt::Release => ZipCompression::Best,
+        });
+        let arch = if build_target.format().supports_multiarch() {
+            "universal".to_string()
+        } else {
+            build_target
+                .compile_targets()
+                .next()
+                .unwrap()
+                .arch()
+                .to_string()
+        };
+        let output_name = config
+            .output()
+            .file_stem(&[
+                ("name", &package.name),
+                ("version", config.version()),
+                ("arch", &arch),
+                ("platform", &build_target.platform().to_string()),
+                ("opt", &build_target.opt().to_string()),
+                ("format", &build_target.format().to_string()),
+            ])?
+            .unwrap_or_else(|| package.name.clone());
+        let output_name = if let Some(flavor) = flavor.as_deref() {
+            format!("{}-{}", output_name, flavor)
+        } else {
+            output_name
+        };
+        let output_name = if let Some(min_sdk) = min_sdk {
+            format!("{}-sdk{}", output_name, min_sdk)
+        } else {
+            output_name
+        };
+        let mut env = Self {
             name: package.name.clone(),
+            output_name,
             build_target,
             icon,
+            assets_dir,
+            exclude,
             cargo,
             config,
             build_dir,
             cache_dir,
             verbose,
             offline,
-        })
+            incremental,
+            compression,
+            container: None,
+            print_cargo_env,
+            jobs,
+            ci,
+            android_base_only,
+            strip_symbols_to,
+            split_debug,
+            android_resources,
+            keep_intermediates,
+            compress_assets,
+        };
+        anyhow::ensure!(
+            !split_debug
+                || matches!(
+                    env.target().platform(),
+                    Platform::Linux | Platform::Macos | Platform::Windows
+                ),
+            "--split-debug is only supported for linux, macos and windows, use --strip-symbols-to"
+        );
+        if let Some(image) = container {
+            anyhow::ensure!(
+                matches!(env.target().platform(), Platform::Android | Platform::Linux),
+                "--container is only supported for android and linux"
+            );
+            env.container = Some(env.container(image));
+        }
+        tracing::info!("package root: {}", env.root_dir().display());
+        tracing::info!("build dir: {}", env.build_dir().display());
+        tracing::info!("cache dir: {}", env.cache_dir().display());
+        tracing::info!("output: {}", env.output().display());
+        Ok(env)
+    }
+
+    /// Container mounting the workspace, the target and cache directories and
+    /// the cargo home. Path dependencies outside of the workspace are not
+    /// available.
+    fn container(&self, image: String) -> Container {
+        let mut container = Container::new(image);
+        container.mount(self.cargo.workspace_root());
+        container.mount(self.cargo.target_dir());
+        container.mount(self.cache_dir());
+        if self.target().platform() == Platform::Android {
+            if let Some((ndk, _)) = self.android_ndk_override() {
+                container.mount(&ndk);
+            }
+        }
+        let home = dirs::home_dir().unwrap();
+        let cargo_home = std::env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".cargo"));
+        container.mount(&cargo_home);
+        container.env("CARGO_HOME", cargo_home);
+        if self.config().android().gradle {
+            let gradle_home = home.join(".gradle");
+            container.mount(&gradle_home);
+            container.env("GRADLE_USER_HOME", gradle_home);
+        }
+        container
+    }
+
+    /// Wraps `cmd` to run in the container selected with `--container`.
+    pub fn command(&self, cmd: Command) -> Command {
+        if let Some(container) = self.container.as_ref() {
+            container.wrap(&cmd)
+        } else {
+            cmd
+        }
     }
 
     pub fn name(&self) -> &str {
@@ -532,14 +1094,78 @@ impl BuildEnv {
         self.verbose
     }
 
+    /// Plain progress output requested with `--ci`.
+    pub fn ci(&self) -> bool {
+        self.ci
+    }
+
+    /// Asset packs are packaged into the base module, set with
+    /// `--android-app-bundle-base-only`.
+    pub fn android_base_only(&self) -> bool {
+        self.android_base_only
+    }
+
+    /// Directory the unstripped binaries are saved to before stripping.
+    pub fn strip_symbols_to(&self) -> Option<&Path> {
+        self.strip_symbols_to.as_deref()
+    }
+
+    pub fn split_debug(&self) -> bool {
+        self.split_debug
+    }
+
+    /// Intermediates of previous builds are kept, set with
+    /// `--keep-intermediates`.
+    pub fn keep_intermediates(&self) -> bool {
+        self.keep_intermediates
+    }
+
+    /// Text assets get compressed copies, set with `--compress-assets`.
+    pub fn compress_assets(&self) -> bool {
+        self.compress_assets
+    }
+
+    /// Android `res/` directory set with `--android-resources` or
+    /// `android.resources`.
+    pub fn android_resources(&self) -> Option<PathBuf> {
+        self.android_resources.clone().or_else(|| {
+            let dir = self.config.android().resources.as_ref()?;
+            Some(self.cargo.package_root().join(dir))
+        })
+    }
+
     pub fn offline(&self) -> bool {
         self.offline
     }
 
+    /// Cargo and gradle run in the container set with `--container`.
+    pub fn uses_container(&self) -> bool {
+        self.container.is_some()
+    }
+
+    pub fn incremental(&self) -> bool {
+        self.incremental
+    }
+
+    pub fn compression(&self) -> ZipCompression {
+        self.compression
+    }
+
     pub fn root_dir(&self) -> &Path {
         self.cargo.package_root()
     }
 
+    /// Directory `assets` are resolved against, set with `--assets-from`.
+    pub fn assets_dir(&self) -> &Path {
+        &self.assets_dir
+    }
+
+    /// Maximum number of parallel jobs set with `--max-jobs`, otherwise cargo,
+    /// gradle and the icon encoding use their own defaults.
+    pub fn max_jobs(&self) -> Option<usize> {
+        self.jobs
+    }
+
     pub fn build_dir(&self) -> &Path {
         &self.build_dir
     }
@@ -567,8 +1193,12 @@ impl BuildEnv {
             let target = self.target().compile_targets().next().unwrap();
             self.arch_dir(target.arch())
         };
-        let output_name = format!("{}.{}", self.name(), self.target().format().extension());
-        output_dir.join(output_name)
+        // bundle directories are named after the app, only archives are renamed
+        let stem = match self.target().format() {
+            Format::Appbundle | Format::Appdir => self.name(),
+            _ => &self.output_name,
+        };
+        output_dir.join(format!("{}.{}", stem, self.target().format().extension()))
     }
 
     pub fn executable(&self) -> PathBuf {
@@ -586,6 +1216,10 @@ impl BuildEnv {
         self.icon.as_deref()
     }
 
+    pub fn exclude(&self) -> &Exclude {
+        &self.exclude
+    }
+
     pub fn cargo(&self) -> &Cargo {
         &self.cargo
     }
@@ -594,13 +1228,24 @@ impl BuildEnv {
         &self.config
     }
 
+    /// The sdk versions are defaulted by [`Config::apply_rust_package`]
+    /// when the [`BuildEnv`] is created.
+    pub fn min_sdk_version(&self) -> u32 {
+        self.config()
+            .android()
+            .manifest
+            .sdk
+            .min_sdk_version
+            .expect("defaulted by apply_rust_package")
+    }
+
     pub fn target_sdk_version(&self) -> u32 {
         self.config()
             .android()
             .manifest
             .sdk
             .target_sdk_version
-            .unwrap()
+            .expect("defaulted by apply_rust_package")
     }
 
     pub fn android_jar(&self) -> PathBuf {
@@ -623,8 +1268,21 @@ impl BuildEnv {
         self.cache_dir().join("Android.sdk")
     }
 
+    /// NDK set by `android.ndk` or the environment and what it was set by.
+    pub fn android_ndk_override(&self) -> Option<(PathBuf, String)> {
+        if let Some(ndk) = self.config().android().ndk.as_ref() {
+            return Some((self.root_dir().join(ndk), "android.ndk".into()));
+        }
+        ndk::env_ndk().map(|(ndk, var)| (ndk, format!("${}", var)))
+    }
+
+    /// Sysroot of the android NDK, the downloaded one unless overridden.
     pub fn android_ndk(&self) -> PathBuf {
-        self.cache_dir().join("Android.ndk")
+        if let Some((ndk, _)) = self.android_ndk_override() {
+            ndk::sysroot(&ndk)
+        } else {
+            self.cache_dir().join("Android.ndk")
+        }
     }
 
     pub fn ios_sdk(&self) -> PathBuf {
@@ -655,20 +1313,96 @@ impl BuildEnv {
 
     pub fn cargo_build(&self, target: CompileTarget, target_dir: &Path) -> Result<CargoBuild> {
         let mut cargo = self.cargo.build(target, target_dir)?;
+        if let Some(jobs) = self.jobs {
+            cargo.set_jobs(jobs);
+        }
+        if let Some(container) = self.container.as_ref() {
+            cargo.use_container(container.clone());
+        }
+        if self.print_cargo_env {
+            cargo.print_env();
+        }
+        if self.config().build().sccache {
+            if which::which("sccache").is_ok() {
+                cargo.use_sccache();
+            } else {
+                tracing::warn!("build.sccache is enabled but sccache is not installed");
+            }
+        }
+        if let Some(debug_info) = self.config().debug_info(target.platform()) {
+            cargo.set_debug_info(debug_info);
+        } else if self.split_debug {
+            cargo.set_debug_info(DebugInfo::Full);
+        }
+        for (name, value) in self.build_info(target)? {
+            cargo.env(name, &value);
+        }
+        cargo.set_panic(self.config().panic(target.platform(), target.opt()));
+        if let Some(config) = self.config().target(target.rust_triple()?) {
+            if let Some(target_cpu) = config.target_cpu.as_ref() {
+                cargo.set_target_cpu(target_cpu);
+            }
+            for feature in config.target_features(target.arch())? {
+                cargo.add_target_feature(&feature);
+            }
+        }
         if target.platform() == Platform::Linux {
             cargo.add_link_arg("-Wl,-rpath");
             cargo.add_link_arg("-Wl,$ORIGIN/lib");
         }
+        if (self.strip_symbols_to.is_some() || self.split_debug)
+            && matches!(target.platform(), Platform::Android | Platform::Linux)
+        {
+            // names the saved unstripped binaries
+            cargo.add_link_arg("-Wl,--build-id");
+        }
+        let target_config = self.config().target(target.rust_triple()?);
+        let linker = target_config
+            .and_then(|config| config.linker)
+            .or(self.config().build().linker);
+        if let Some(linker) = linker {
+            // cross targets already link with the lld of their sdk
+            if target.platform() != Platform::Linux {
+                if target_config.and_then(|config| config.linker).is_some() {
+                    tracing::warn!(
+                        "linker is only supported for linux targets, ignoring it for {}",
+                        target.rust_triple()?
+                    );
+                }
+            } else if which::which(linker.binary()).is_ok() {
+                cargo.use_ld(linker.name());
+            } else {
+                tracing::warn!(
+                    "linker {} is configured but {} is not installed",
+                    linker.name(),
+                    linker.binary()
+                );
+            }
+        }
         if target.platform() == Platform::Android {
             let ndk = self.android_ndk();
-            let target_sdk_version = self
-                .config()
-                .android()
-                .manifest
-                .sdk
-                .target_sdk_version
-                .unwrap();
-            cargo.use_android_ndk(&ndk, target_sdk_version)?;
+            let source = if let Some((root, source)) = self.android_ndk_override() {
+                if let Some(version) = ndk::NdkVersion::detect(&root)? {
+                    version
+                        .check()
+                        .with_context(|| format!("Android NDK set by {}", source))?;
+                }
+                format!("set by {}", source)
+            } else {
+                format!(
+                    "downloaded, android.ndk, ${} are unset",
+                    ndk::ANDROID_NDK_VARS.join(", $")
+                )
+            };
+            cargo
+                .use_android_ndk(&ndk, self.min_sdk_version())
+                .with_context(|| {
+                    format!("Invalid android ndk at `{}` ({})", ndk.display(), source)
+                })?;
+            let android = self.config().android();
+            if let Some(dir) = android.native_libs_dir(target.android_abi()) {
+                cargo.add_lib_dir(&self.root_dir().join(dir));
+            }
         }
         if target.platform() == Platform::Windows {
             let sdk = self.windows_sdk();
@@ -685,7 +1419,7 @@ impl BuildEnv {
                     .info
                     .ls_minimum_system_version
                     .as_ref()
-                    .unwrap();
+                    .context("missing macos.info.ls_minimum_system_version")?;
                 cargo.use_macos_sdk(&sdk, minimum_version)?;
             } else {
                 cargo.add_link_arg("-rpath");
@@ -701,13 +1435,67 @@ impl BuildEnv {
                     .info
                     .minimum_os_version
                     .as_ref()
-                    .unwrap();
+                    .context("missing ios.info.minimum_os_version")?;
                 cargo.use_ios_sdk(&sdk, minimum_version)?;
             }
         }
+        if let Some(config) = self.config().target(target.rust_triple()?) {
+            for dir in &config.include_dirs {
+                cargo.add_include_dir(&self.root_dir().join(dir));
+            }
+            for (name, value) in &config.defines {
+                cargo.add_define(name, value);
+            }
+        }
         Ok(cargo)
     }
 
+    /// Build provenance set for cargo, which the app reads with `env!`.
+    fn build_info(&self, target: CompileTarget) -> Result<[(&'static str, String); 5]> {
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(self.root_dir())
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let git_hash = match git(&["rev-parse", "HEAD"]) {
+            Some(hash) => match git(&["status", "--porcelain", "--untracked-files=no"]) {
+                Some(status) if !status.is_empty() => format!("{}-dirty", hash),
+                _ => hash,
+            },
+            None => "unknown".to_string(),
+        };
+        // reproducible builds set the time of the last commit
+        let build_time = match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(epoch) => epoch,
+            Err(_) => std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs()
+                .to_string(),
+        };
+        let version = self.config().version().to_string();
+        let triple = target.rust_triple()?.to_string();
+        let build_info = format!(
+            "{} ({}, {}, {})",
+            version,
+            &git_hash[..git_hash.len().min(12)],
+            triple,
+            target.opt()
+        );
+        Ok([
+            ("XBUILD_GIT_HASH", git_hash),
+            ("XBUILD_BUILD_TIME", build_time),
+            ("XBUILD_VERSION", version),
+            ("XBUILD_TARGET", triple),
+            ("XBUILD_BUILD_INFO", build_info),
+        ])
+    }
+
     pub fn cargo_artefact(
         &self,
         target_dir: &Path,
@@ -2,6 +2,7 @@ use anyhow::Result;
 use app_store_connect::certs_api::CertificateType;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Duration;
 use xbuild::{cargo::config::LocalizedConfig, command, BuildArgs, BuildEnv};
 
 #[derive(Parser)]
@@ -13,8 +14,9 @@ struct Args {
 
 fn main() -> Result<()> {
     use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+    let args = Args::parse();
     tracing_log::LogTracer::init().ok();
-    let env = std::env::var("XBUILD_LOG").unwrap_or_else(|_| "error".into());
+    let env = std::env::var("XBUILD_LOG").unwrap_or_else(|_| args.command.log_filter().into());
     let subscriber = tracing_subscriber::FmtSubscriber::builder()
         .with_span_events(FmtSpan::ACTIVE | FmtSpan::CLOSE)
         .with_env_filter(EnvFilter::new(env))
@@ -22,7 +24,6 @@ fn main() -> Result<()> {
         .finish();
     tracing::subscriber::set_global_default(subscriber).ok();
     log_panics::init();
-    let args = Args::parse();
     args.command.run()
 }
 
@@ -33,19 +34,101 @@ enum Commands {
         /// Project name
         name: String,
     },
-    /// Show information about the installed tooling
-    Doctor,
+    /// Show information about the installed tooling and the reachability of
+    /// the download servers
+    Doctor {
+        /// Report the artifacts in the cache instead of checking the network
+        #[clap(long)]
+        offline: bool,
+    },
     /// List all connected devices
     Devices,
+    /// Print a JSON schema of manifest.yaml for editor autocompletion
+    Schema {
+        /// Write the schema to a file instead of stdout
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Re-sign a built apk, msix, app or dmg without rebuilding it
+    Resign {
+        /// Path to the artifact
+        path: PathBuf,
+        /// Path to a PEM encoded signing key and certificate, defaults to
+        /// `X_PEM` or the debug key
+        #[clap(long)]
+        pem: Option<PathBuf>,
+        /// Name or SHA-1 hash of a code signing identity in the macos keychain
+        #[clap(long, conflicts_with = "pem")]
+        signing_identity: Option<String>,
+    },
+    /// Print metadata of a built apk, app, ipa or msix
+    Info {
+        /// Path to the artifact
+        path: PathBuf,
+    },
+    /// Print the subject, validity, key and fingerprints of a signing
+    /// certificate
+    CertInfo {
+        /// Path to a PEM encoded signing key and certificate, defaults to
+        /// `X_PEM`
+        #[clap(long)]
+        pem: Option<PathBuf>,
+    },
+    /// Generate the `.well-known/assetlinks.json` verifying the android app
+    /// links of a package signed with a key
+    Assetlinks {
+        /// Path to a PEM encoded signing key and certificate, defaults to
+        /// `X_PEM`
+        #[clap(long)]
+        pem: Option<PathBuf>,
+        /// Android package name, e.g. `com.example.app`
+        #[clap(long)]
+        package: String,
+        /// Write the json to a file instead of stdout
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
     /// Build an executable app or install bundle
     Build {
         #[clap(flatten)]
         args: BuildArgs,
+        /// Print the build plan without building anything
+        #[clap(long)]
+        dry_run: bool,
+        /// Check the manifest, signing material, rust targets, sdks and tools
+        /// without building anything
+        #[clap(long, conflicts_with = "dry_run")]
+        check: bool,
+        /// Print the certificate of the signing key before building
+        #[clap(long)]
+        cert_info: bool,
+        /// Write the resolved platform manifest as yaml to path
+        #[clap(long)]
+        manifest_out: Option<PathBuf>,
+        /// List the platform, arch and format combinations that can be built
+        #[clap(long)]
+        list_targets: bool,
+        /// Print --list-targets as json
+        #[clap(long, requires = "list_targets")]
+        json: bool,
     },
     /// Run app on an attached device
     Run {
         #[clap(flatten)]
         args: BuildArgs,
+        /// Seconds to wait for an android device or emulator to finish booting
+        #[clap(long, default_value = "120")]
+        boot_timeout: u64,
+        /// Url or file to open in the app after launching it, e.g. a deep link
+        #[clap(long)]
+        url: Option<String>,
+        /// Rebuild and restart the app when the sources change, host only
+        #[clap(long, conflicts_with = "profile_startup")]
+        watch: bool,
+        /// Report the time until the app is launched and its first frame
+        /// is rendered
+        #[clap(long)]
+        profile_startup: bool,
     },
     /// Launch app in a debugger on an attached device
     Lldb {
@@ -89,25 +172,98 @@ fn partial_build_env() -> Result<()> {
 }
 
 impl Commands {
+    fn log_filter(&self) -> &'static str {
+        match self {
+            Self::Build { args, .. } | Self::Run { args, .. } | Self::Lldb { args } => {
+                args.log_filter()
+            }
+            _ => xbuild::DEFAULT_LOG_FILTER,
+        }
+    }
+
     pub fn run(self) -> Result<()> {
         match self {
             Self::New { name } => command::new(&name)?,
-            Self::Doctor => {
+            Self::Doctor { offline } => {
                 partial_build_env()?;
-                command::doctor()
+                command::doctor(offline)
             }
             Self::Devices => {
                 partial_build_env()?;
                 command::devices()?
             }
-            Self::Build { args } => {
-                let env = BuildEnv::new(args)?;
-                command::build(&env)?;
+            Self::Schema { output } => command::schema(output.as_deref())?,
+            Self::Resign {
+                path,
+                pem,
+                signing_identity,
+            } => command::resign(&path, pem.as_deref(), signing_identity.as_deref())?,
+            Self::Info { path } => command::info(&path)?,
+            Self::Assetlinks {
+                pem,
+                package,
+                output,
+            } => command::assetlinks(
+                xbuild::load_signer(pem.as_deref())?.as_ref(),
+                &package,
+                output.as_deref(),
+            )?,
+            Self::CertInfo { pem } => {
+                command::cert_info(xbuild::load_signer(pem.as_deref())?.as_ref(), None)?
+            }
+            Self::Build {
+                args,
+                dry_run,
+                check,
+                cert_info,
+                manifest_out,
+                list_targets,
+                json,
+            } => {
+                if list_targets {
+                    partial_build_env()?;
+                    return command::list_targets(json);
+                }
+                for args in args.min_sdk_variants() {
+                    let env = BuildEnv::new(args)?;
+                    if cert_info {
+                        let target = env.target();
+                        command::cert_info(target.signer(), target.signing_identity())?;
+                    }
+                    if let Some(out) = manifest_out.as_ref() {
+                        command::write_manifest(&env, out)?;
+                    }
+                    if dry_run {
+                        command::dry_run(&env)?;
+                    } else if check {
+                        command::check(&env)?;
+                    } else {
+                        command::build(&env)?;
+                    }
+                }
             }
-            Self::Run { args } => {
+            Self::Run {
+                args,
+                boot_timeout,
+                url,
+                watch,
+                profile_startup,
+            } => {
                 let env = BuildEnv::new(args)?;
+                if watch {
+                    return command::watch(&env, url.as_deref());
+                }
                 command::build(&env)?;
-                command::run(&env)?;
+                let status = command::run(
+                    &env,
+                    Duration::from_secs(boot_timeout),
+                    url.as_deref(),
+                    profile_startup,
+                )?;
+                if let Some(status) = status.filter(|status| !status.success()) {
+                    // like `cargo run`, exit with the code of the app
+                    std::process::exit(status.code().unwrap_or(1));
+                }
             }
             Self::Lldb { args } => {
                 let env = BuildEnv::new(args)?;
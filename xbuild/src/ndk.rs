@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use xcommon::llvm;
+
+/// Environment variables consulted for an android NDK, in order.
+pub const ANDROID_NDK_VARS: [&str; 2] = ["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT"];
+
+/// Oldest NDK major version with the unified `usr/lib/<triple>/<sdk>` sysroot
+/// and the libunwind required by rust since 1.68.
+const MIN_NDK_MAJOR: u32 = 25;
+
+/// NDK release recommended when the detected one is unsupported.
+const RECOMMENDED_NDK: &str = "r25c (25.2.9519653)";
+
+/// NDK set in the environment.
+pub fn env_ndk() -> Option<(PathBuf, &'static str)> {
+    ANDROID_NDK_VARS.iter().find_map(|var| {
+        let ndk = std::env::var_os(var).filter(|ndk| !ndk.is_empty())?;
+        Some((PathBuf::from(ndk), *var))
+    })
+}
+
+/// A full NDK keeps its sysroot in `toolchains/llvm/prebuilt/<host>/sysroot`,
+/// anything else is assumed to be a sysroot already.
+pub fn sysroot(ndk: &Path) -> PathBuf {
+    let prebuilt = ndk.join("toolchains").join("llvm").join("prebuilt");
+    std::fs::read_dir(prebuilt)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path().join("sysroot")))
+        .find(|sysroot| sysroot.exists())
+        .unwrap_or_else(|| ndk.to_path_buf())
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct NdkVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub build: u32,
+}
+
+impl NdkVersion {
+    /// Reads `Pkg.Revision` from `source.properties` in the NDK root. Returns
+    /// `None` for a bare sysroot, which doesn't ship the file.
+    pub fn detect(ndk: &Path) -> Result<Option<Self>> {
+        let path = ndk.join("source.properties");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let properties = std::fs::read_to_string(&path)?;
+        let version = Self::from_properties(&properties)
+            .with_context(|| format!("Failed to read NDK version from `{}`", path.display()))?;
+        Ok(Some(version))
+    }
+
+    fn from_properties(properties: &str) -> Result<Self> {
+        let revision = properties
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == "Pkg.Revision")
+            .map(|(_, value)| value.trim())
+            .context("missing Pkg.Revision")?;
+        revision.parse()
+    }
+
+    /// Errors with a recommendation when the NDK layout isn't supported.
+    pub fn check(self) -> Result<()> {
+        anyhow::ensure!(
+            self.major >= MIN_NDK_MAJOR,
+            "NDK {} is not supported, at least r{} is required. Install NDK {}",
+            self,
+            MIN_NDK_MAJOR,
+            RECOMMENDED_NDK
+        );
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for NdkVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(revision: &str) -> Result<Self> {
+        // prereleases look like `25.0.8151533-beta1`
+        let revision = revision.split('-').next().unwrap();
+        let mut parts = revision.split('.').map(|part| part.parse::<u32>());
+        let mut next = || -> Result<u32> {
+            parts
+                .next()
+                .with_context(|| format!("invalid NDK revision `{}`", revision))?
+                .with_context(|| format!("invalid NDK revision `{}`", revision))
+        };
+        Ok(Self {
+            major: next()?,
+            minor: next()?,
+            build: next()?,
+        })
+    }
+}
+
+impl std::fmt::Display for NdkVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.build)
+    }
+}
+
+/// Symbols `lib` imports from NDK libraries that are only available above api
+/// level `min_sdk`, with the level that introduced them. `sysroot_libs` is the
+/// `usr/lib/<triple>` directory holding a directory of stub libraries per
+/// level.
+pub fn symbols_above_min_sdk(
+    lib: &Path,
+    sysroot_libs: &Path,
+    min_sdk: u32,
+) -> Result<Vec<(String, u32)>> {
+    if !sysroot_libs.join(min_sdk.to_string()).exists() {
+        return Ok(vec![]);
+    }
+    let needed = llvm::list_needed_libs(lib)?;
+    let defined_at = |level: u32| -> Result<HashSet<String>> {
+        let mut defined = HashSet::new();
+        for name in &needed {
+            let stub = sysroot_libs.join(level.to_string()).join(name);
+            if stub.exists() {
+                defined.extend(llvm::dynamic_symbols(&stub)?.defined);
+            }
+        }
+        Ok(defined)
+    };
+    let available = defined_at(min_sdk)?;
+    let mut missing = llvm::dynamic_symbols(lib)?.imported;
+    missing.retain(|symbol| !available.contains(symbol));
+    let mut levels = std::fs::read_dir(sysroot_libs)?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|level| *level > min_sdk)
+        .collect::<Vec<_>>();
+    levels.sort_unstable();
+    // symbols of libraries outside of the NDK are never found
+    let mut above = vec![];
+    for level in levels {
+        if missing.is_empty() {
+            break;
+        }
+        let defined = defined_at(level)?;
+        missing.retain(|symbol| {
+            if defined.contains(symbol) {
+                above.push((symbol.clone(), level));
+                false
+            } else {
+                true
+            }
+        });
+    }
+    above.sort();
+    Ok(above)
+}
+
+#[test]
+fn test_ndk_version() {
+    let properties = "Pkg.Desc = Android NDK\nPkg.Revision = 25.2.9519653\n";
+    let version = NdkVersion::from_properties(properties).unwrap();
+    assert_eq!(
+        version,
+        NdkVersion {
+            major: 25,
+            minor: 2,
+            build: 9519653
+        }
+    );
+    version.check().unwrap();
+
+    let beta: NdkVersion = "26.0.10404224-beta1".parse().unwrap();
+    assert_eq!(beta.major, 26);
+
+    let old: NdkVersion = "21.4.7075529".parse().unwrap();
+    assert!(old.check().is_err());
+    assert!("21".parse::<NdkVersion>().is_err());
+    assert!(NdkVersion::from_properties("Pkg.Desc = Android NDK").is_err());
+}
@@ -0,0 +1,123 @@
+use crate::BuildEnv;
+use anyhow::Result;
+
+/// Default behavior that changes for apps targeting an android api level.
+struct BehaviorChange {
+    level: u32,
+    summary: &'static str,
+    /// Permissions the app has to declare or request at runtime because of
+    /// the change.
+    permissions: &'static [&'static str],
+}
+
+const BEHAVIOR_CHANGES: &[BehaviorChange] = &[
+    BehaviorChange {
+        level: 23,
+        summary: "dangerous permissions are granted at runtime instead of on install",
+        permissions: &[],
+    },
+    BehaviorChange {
+        level: 26,
+        summary: "background services are limited and implicit broadcasts are no longer delivered to manifest receivers",
+        permissions: &[],
+    },
+    BehaviorChange {
+        level: 28,
+        summary: "foreground services require a permission and cleartext http is disabled by default",
+        permissions: &["android.permission.FOREGROUND_SERVICE"],
+    },
+    BehaviorChange {
+        level: 29,
+        summary: "scoped storage is enabled and location access in the background requires a separate permission",
+        permissions: &["android.permission.ACCESS_BACKGROUND_LOCATION"],
+    },
+    BehaviorChange {
+        level: 30,
+        summary: "scoped storage is enforced and other packages are only visible when declared in `<queries>`",
+        permissions: &[],
+    },
+    BehaviorChange {
+        level: 31,
+        summary: "exact alarms require a permission, pending intents must set their mutability and components with intent filters must set `exported`",
+        permissions: &["android.permission.SCHEDULE_EXACT_ALARM"],
+    },
+    BehaviorChange {
+        level: 33,
+        summary: "notifications require a runtime permission and media access is split by media type",
+        permissions: &[
+            "android.permission.POST_NOTIFICATIONS",
+            "android.permission.READ_MEDIA_IMAGES",
+            "android.permission.READ_MEDIA_VIDEO",
+            "android.permission.READ_MEDIA_AUDIO",
+        ],
+    },
+    BehaviorChange {
+        level: 34,
+        summary: "foreground services must declare a `foregroundServiceType` with its permission and exact alarms are no longer granted by default",
+        permissions: &[
+            "android.permission.FOREGROUND_SERVICE_<TYPE>",
+            "android.permission.SCHEDULE_EXACT_ALARM",
+            "android.permission.USE_EXACT_ALARM",
+        ],
+    },
+    BehaviorChange {
+        level: 35,
+        summary: "apps are displayed edge-to-edge and `dataSync` foreground services are limited to 6 hours a day",
+        permissions: &[],
+    },
+];
+
+/// Changes of apps targeting `to` instead of `from`.
+fn behavior_changes(from: u32, to: u32) -> impl Iterator<Item = &'static BehaviorChange> {
+    BEHAVIOR_CHANGES
+        .iter()
+        .filter(move |change| change.level > from && change.level <= to)
+}
+
+/// Warns about the behavior changes since the target sdk version of the
+/// previous build, which is stored in the build directory. The first build
+/// only stores it.
+pub fn warn_behavior_changes(env: &BuildEnv) -> Result<()> {
+    let stamp = env.build_dir().join("android.target_sdk");
+    let target_sdk = env.target_sdk_version();
+    let previous = std::fs::read_to_string(&stamp)
+        .ok()
+        .and_then(|previous| previous.trim().parse::<u32>().ok());
+    if let Some(previous) = previous {
+        for change in behavior_changes(previous, target_sdk) {
+            let permissions = if change.permissions.is_empty() {
+                String::new()
+            } else {
+                format!(" (permissions: {})", change.permissions.join(", "))
+            };
+            tracing::warn!(
+                "target_sdk_version {} -> {}: since api {} {}{}",
+                previous,
+                target_sdk,
+                change.level,
+                change.summary,
+                permissions
+            );
+        }
+    }
+    if previous != Some(target_sdk) {
+        std::fs::create_dir_all(env.build_dir())?;
+        std::fs::write(&stamp, target_sdk.to_string())?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_behavior_changes() {
+    let levels = behavior_changes(33, 34).map(|change| change.level);
+    assert_eq!(levels.collect::<Vec<_>>(), [34]);
+    let change = behavior_changes(33, 34).next().unwrap();
+    assert!(change.summary.contains("foregroundServiceType"));
+    assert!(change.summary.contains("exact alarms"));
+    assert!(change
+        .permissions
+        .contains(&"android.permission.SCHEDULE_EXACT_ALARM"));
+    assert_eq!(behavior_changes(30, 33).count(), 2);
+    assert_eq!(behavior_changes(34, 34).count(), 0);
+    assert_eq!(behavior_changes(34, 33).count(), 0);
+}
@@ -1,3 +1,4 @@
+use crate::XbuildError;
 use anyhow::Result;
 use console::{style, Term};
 use std::process::Command;
@@ -11,25 +12,39 @@ pub struct TaskRunner {
     descr: String,
     verbose: bool,
     started: bool,
+    plain: Option<Instant>,
+}
+
+/// Plain output is used when `--ci` is passed, the `CI` environment variable
+/// is set or stdout isn't a terminal, as redrawn lines garble logs.
+fn plain_output(ci: bool) -> bool {
+    let ci_env = std::env::var("CI").is_ok_and(|ci| !ci.is_empty() && ci != "false");
+    ci || ci_env || !Term::stdout().is_term()
 }
 
 impl TaskRunner {
-    pub fn new(num_tasks: u32, verbose: bool) -> Self {
+    pub fn new(num_tasks: u32, verbose: bool, ci: bool) -> Self {
+        let now = Instant::now();
         Self {
             term: Term::stdout(),
             num_tasks,
             current_task: 0,
-            now: Instant::now(),
+            now,
             descr: "".into(),
             verbose,
             started: false,
+            plain: plain_output(ci).then_some(now),
         }
     }
 
     fn task_id(&self) -> String {
-        style(format!("[{}/{}]", self.current_task + 1, self.num_tasks))
-            .force_styling(true)
-            .to_string()
+        let id = format!("[{}/{}]", self.current_task + 1, self.num_tasks);
+        if let Some(start) = self.plain {
+            // prefixed with the time since the start instead of being styled
+            format!("[{:>7.1}s] {}", start.elapsed().as_secs_f32(), id)
+        } else {
+            style(id).force_styling(true).to_string()
+        }
     }
 
     pub fn start_task(&mut self, descr: impl Into<String>) {
@@ -39,12 +54,15 @@ impl TaskRunner {
         self.now = Instant::now();
         self.descr = descr.into();
         self.started = true;
-        println!("{} {}", self.task_id(), &self.descr);
+        // plain output only has a line per task, unless the task prints output
+        if self.plain.is_none() || self.verbose {
+            println!("{} {}", self.task_id(), &self.descr);
+        }
     }
 
     fn finish_task(&mut self, skipped: bool, clear_last: bool) {
         self.started = false;
-        if clear_last {
+        if clear_last && self.plain.is_none() {
             self.term.clear_last_lines(1).unwrap();
         }
         let status = if skipped {
@@ -81,8 +99,17 @@ pub fn run(mut command: Command, verbose: bool) -> Result<()> {
         };
         println!("{} {} {} {}", style("[ERROR]").red(), program, args, status);
     }
+    // a program which can't be spawned is most likely not installed
+    let program = command.get_program().to_string_lossy().into_owned();
+    let missing_tool = |err: std::io::Error| -> anyhow::Error {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            XbuildError::MissingTool(program.clone()).into()
+        } else {
+            err.into()
+        }
+    };
     if !verbose {
-        let output = command.output()?;
+        let output = command.output().map_err(missing_tool)?;
         if !output.status.success() {
             print_error(&command, output.status.code());
             let stdout = std::str::from_utf8(&output.stdout)?;
@@ -92,7 +119,7 @@ pub fn run(mut command: Command, verbose: bool) -> Result<()> {
             std::process::exit(1);
         }
     } else {
-        let status = command.status()?;
+        let status = command.status().map_err(missing_tool)?;
         if !status.success() {
             print_error(&command, status.code());
             std::process::exit(1);
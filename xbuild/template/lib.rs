@@ -35,6 +35,13 @@ pub fn main() {
 }
 
 fn app(cx: Scope) -> Element {
+    // `x run --profile-startup` measures the time until this is printed
+    use_effect(cx, (), |()| async {
+        #[cfg(not(target_family = "wasm"))]
+        if std::env::var_os("X_PROFILE_STARTUP").is_some() {
+            println!("x: startup complete");
+        }
+    });
     cx.render(rsx! {
         div {
             "hello world"
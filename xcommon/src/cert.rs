@@ -0,0 +1,262 @@
+use crate::{Certificate, EC_PUBLIC_KEY_OID, RSA_ENCRYPTION_OID};
+use anyhow::{Context, Result};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const PRINTABLE_STRING: u8 = 0x13;
+const TELETEX_STRING: u8 = 0x14;
+const IA5_STRING: u8 = 0x16;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const BMP_STRING: u8 = 0x1e;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const EXPLICIT_0: u8 = 0xa0;
+
+/// Human readable summary of a certificate, for checking which key an
+/// artifact is signed with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    /// Key algorithm and size, e.g. `RSA 2048 bit`
+    pub key: String,
+    /// SHA-1 fingerprint, formatted like `keytool`
+    pub sha1: String,
+    /// SHA-256 fingerprint, formatted like `keytool`
+    pub sha256: String,
+}
+
+impl CertInfo {
+    pub fn new(cert: &Certificate) -> Result<Self> {
+        let der = rasn::der::encode(cert).map_err(|err| anyhow::anyhow!("{}", err))?;
+        Self::from_der(&der)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let mut cert = Der(der).expect(SEQUENCE)?;
+        let mut tbs = cert.expect(SEQUENCE)?;
+        if tbs.0.first() == Some(&EXPLICIT_0) {
+            tbs.read()?;
+        }
+        tbs.expect(INTEGER)?;
+        tbs.expect(SEQUENCE)?;
+        let issuer = name(tbs.expect(SEQUENCE)?)?;
+        let mut validity = tbs.expect(SEQUENCE)?;
+        let not_before = time(validity.read()?)?;
+        let not_after = time(validity.read()?)?;
+        let subject = name(tbs.expect(SEQUENCE)?)?;
+        let mut spki = tbs.expect(SEQUENCE)?;
+        let mut algorithm = spki.expect(SEQUENCE)?;
+        let algorithm_oid = oid(algorithm.expect(OBJECT_IDENTIFIER)?.0)?;
+        let key = if algorithm_oid == RSA_ENCRYPTION_OID {
+            let bits = spki.expect(BIT_STRING)?.0;
+            let mut key = Der(bits.get(1..).context("invalid public key")?).expect(SEQUENCE)?;
+            let modulus = key.expect(INTEGER)?.0;
+            let modulus = &modulus[modulus.iter().take_while(|b| **b == 0).count()..];
+            let bits = modulus.len() * 8
+                - modulus
+                    .first()
+                    .map(|b| b.leading_zeros())
+                    .unwrap_or_default() as usize;
+            format!("RSA {} bit", bits)
+        } else if algorithm_oid == EC_PUBLIC_KEY_OID {
+            let curve = oid(algorithm.expect(OBJECT_IDENTIFIER)?.0)?;
+            let curve = match &curve[..] {
+                [1, 2, 840, 10045, 3, 1, 7] => "P-256".to_string(),
+                [1, 3, 132, 0, 34] => "P-384".to_string(),
+                [1, 3, 132, 0, 35] => "P-521".to_string(),
+                _ => dotted(&curve),
+            };
+            format!("EC {}", curve)
+        } else {
+            dotted(&algorithm_oid)
+        };
+        Ok(Self {
+            subject,
+            issuer,
+            not_before,
+            not_after,
+            key,
+            sha1: fingerprint(&Sha1::digest(der)),
+            sha256: fingerprint(&Sha256::digest(der)),
+        })
+    }
+}
+
+impl std::fmt::Display for CertInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{:20}{}", "subject:", self.subject)?;
+        writeln!(f, "{:20}{}", "issuer:", self.issuer)?;
+        writeln!(f, "{:20}{}", "valid from:", self.not_before)?;
+        writeln!(f, "{:20}{}", "valid until:", self.not_after)?;
+        writeln!(f, "{:20}{}", "key:", self.key)?;
+        writeln!(f, "{:20}{}", "sha1 fingerprint:", self.sha1)?;
+        writeln!(f, "{:20}{}", "sha256 fingerprint:", self.sha256)
+    }
+}
+
+/// Formats a digest as colon separated upper case hex bytes.
+pub(crate) fn fingerprint(digest: &[u8]) -> String {
+    let hex = digest.iter().map(|b| format!("{:02X}", b));
+    hex.collect::<Vec<_>>().join(":")
+}
+
+/// Reads DER encoded values, only definite lengths are valid in DER.
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    fn read(&mut self) -> Result<(u8, &'a [u8])> {
+        let truncated = || anyhow::anyhow!("truncated certificate");
+        let tag = *self.0.first().ok_or_else(truncated)?;
+        let first = *self.0.get(1).ok_or_else(truncated)?;
+        let (len, header) = if first < 0x80 {
+            (first as usize, 2)
+        } else {
+            let n = (first & 0x7f) as usize;
+            anyhow::ensure!(n > 0 && n <= 4, "invalid length in certificate");
+            let bytes = self.0.get(2..2 + n).ok_or_else(truncated)?;
+            let len = bytes.iter().fold(0, |len, b| len << 8 | *b as usize);
+            (len, 2 + n)
+        };
+        let contents = self.0.get(header..header + len).ok_or_else(truncated)?;
+        self.0 = &self.0[header + len..];
+        Ok((tag, contents))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<Der<'a>> {
+        let (tag, contents) = self.read()?;
+        anyhow::ensure!(
+            tag == expected,
+            "expected tag {:#04x} in certificate, found {:#04x}",
+            expected,
+            tag
+        );
+        Ok(Der(contents))
+    }
+}
+
+fn oid(bytes: &[u8]) -> Result<Vec<u32>> {
+    let (first, rest) = bytes.split_first().context("empty object identifier")?;
+    let mut arcs = vec![(*first / 40).min(2) as u32];
+    arcs.push(*first as u32 - arcs[0] * 40);
+    let mut arc = 0u32;
+    for b in rest {
+        arc = arc << 7 | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    Ok(arcs)
+}
+
+fn dotted(oid: &[u32]) -> String {
+    let arcs = oid.iter().map(|arc| arc.to_string());
+    arcs.collect::<Vec<_>>().join(".")
+}
+
+/// Formats a distinguished name like `openssl x509 -subject`.
+fn name(mut rdns: Der) -> Result<String> {
+    let mut parts = vec![];
+    while !rdns.0.is_empty() {
+        let mut rdn = rdns.expect(SET)?;
+        while !rdn.0.is_empty() {
+            let mut attribute = rdn.expect(SEQUENCE)?;
+            let key = oid(attribute.expect(OBJECT_IDENTIFIER)?.0)?;
+            let key = match &key[..] {
+                [2, 5, 4, 3] => "CN".to_string(),
+                [2, 5, 4, 6] => "C".to_string(),
+                [2, 5, 4, 7] => "L".to_string(),
+                [2, 5, 4, 8] => "ST".to_string(),
+                [2, 5, 4, 10] => "O".to_string(),
+                [2, 5, 4, 11] => "OU".to_string(),
+                [1, 2, 840, 113549, 1, 9, 1] => "emailAddress".to_string(),
+                _ => dotted(&key),
+            };
+            let (tag, value) = attribute.read()?;
+            let value = match tag {
+                UTF8_STRING | PRINTABLE_STRING | TELETEX_STRING | IA5_STRING => {
+                    String::from_utf8_lossy(value).into_owned()
+                }
+                BMP_STRING => String::from_utf16_lossy(
+                    &value
+                        .chunks_exact(2)
+                        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                        .collect::<Vec<_>>(),
+                ),
+                _ => fingerprint(value),
+            };
+            parts.push(format!("{}={}", key, value));
+        }
+    }
+    Ok(parts.join(", "))
+}
+
+/// Formats a `UTCTime` or `GeneralizedTime` as `YYYY-MM-DD HH:MM:SS UTC`.
+fn time((tag, value): (u8, &[u8])) -> Result<String> {
+    let value = std::str::from_utf8(value)?;
+    let value = match tag {
+        UTC_TIME => {
+            let year: u32 = value.get(..2).context("invalid time")?.parse()?;
+            let century = if year >= 50 { "19" } else { "20" };
+            format!("{}{}", century, value)
+        }
+        GENERALIZED_TIME => value.to_string(),
+        _ => anyhow::bail!("expected a time in certificate, found tag {:#04x}", tag),
+    };
+    let digits = value.trim_end_matches('Z');
+    anyhow::ensure!(
+        digits.len() >= 14 && digits.bytes().all(|b| b.is_ascii_digit()),
+        "invalid time `{}` in certificate",
+        value
+    );
+    Ok(format!(
+        "{}-{}-{} {}:{}:{} UTC",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cert_der(pem: &str) -> Vec<u8> {
+        let pem = pem::parse_many(pem).unwrap();
+        let cert = pem.iter().find(|pem| pem.tag == "CERTIFICATE").unwrap();
+        cert.contents.clone()
+    }
+
+    #[test]
+    fn cert_info() {
+        let info = CertInfo::from_der(&cert_der(include_str!("../assets/test.pem"))).unwrap();
+        assert_eq!(
+            info.subject,
+            "C=US, ST=Some-State, O=Internet Widgits Pty Ltd"
+        );
+        assert_eq!(info.issuer, info.subject);
+        assert_eq!(info.not_before, "2022-02-21 14:13:19 UTC");
+        assert_eq!(info.not_after, "2032-02-19 14:13:19 UTC");
+        assert_eq!(info.key, "RSA 2048 bit");
+        assert_eq!(
+            info.sha1,
+            "AE:B5:7D:91:CE:F6:F8:67:99:10:FD:DE:26:42:41:09:4E:C8:A2:74"
+        );
+        assert!(info.sha256.starts_with("FD:F4:51:AA:0F:3D"));
+
+        let info = CertInfo::from_der(&cert_der(include_str!("../assets/test-ec.pem"))).unwrap();
+        assert_eq!(info.subject, "CN=xbuild test");
+        assert_eq!(info.key, "EC P-256");
+    }
+}
@@ -1,27 +1,75 @@
+mod cert;
 pub mod llvm;
 
 use anyhow::{Context, Result};
 use byteorder::{LittleEndian, ReadBytesExt};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
 use image::imageops::FilterType;
 use image::io::Reader as ImageReader;
 use image::{DynamicImage, GenericImageView, ImageOutputFormat, RgbaImage};
-use rsa::pkcs8::DecodePrivateKey;
+use p256::ecdsa::signature::Signer as _;
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey, PrivateKeyInfo};
 use rsa::{PaddingScheme, RsaPrivateKey, RsaPublicKey};
 use sha2::{Digest, Sha256};
 use std::fs::{File, OpenOptions};
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 use zip::write::FileOptions;
 use zip::{CompressionMethod, ZipArchive, ZipWriter};
 
+pub use cert::CertInfo;
 pub use rasn_pkix::Certificate;
 pub use zip::read::ZipFile;
 
+static ICON_CACHE: OnceLock<PathBuf> = OnceLock::new();
+static ICON_FILTER: OnceLock<ScalerFilter> = OnceLock::new();
+
+/// Resampling filter used when resizing icons.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ScalerFilter {
+    /// Smooth results at any scale.
+    #[default]
+    Lanczos3,
+    /// Cheaper and slightly softer than [`ScalerFilter::Lanczos3`].
+    Triangle,
+    /// Keeps hard edges, for pixel art icons.
+    Nearest,
+}
+
+impl ScalerFilter {
+    fn filter_type(self) -> FilterType {
+        match self {
+            Self::Lanczos3 => FilterType::Lanczos3,
+            Self::Triangle => FilterType::Triangle,
+            Self::Nearest => FilterType::Nearest,
+        }
+    }
+}
+
 pub struct Scaler {
     img: DynamicImage,
+    /// Hash of the source image, used as part of the cache key.
+    source: [u8; 32],
+    filter: ScalerFilter,
+    cache: Option<PathBuf>,
 }
 
 impl Scaler {
+    /// Cache directory of every [`Scaler`] opened afterwards, see
+    /// [`Scaler::set_cache`].
+    pub fn set_cache_dir(dir: PathBuf) {
+        ICON_CACHE.set(dir).ok();
+    }
+
+    /// Filter of every [`Scaler`] opened afterwards.
+    pub fn set_default_filter(filter: ScalerFilter) {
+        ICON_FILTER.set(filter).ok();
+    }
+
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
         let img = ImageReader::open(path)
@@ -30,7 +78,24 @@ impl Scaler {
         let (width, height) = img.dimensions();
         anyhow::ensure!(width == height, "expected width == height");
         anyhow::ensure!(width >= 512, "expected icon of at least 512x512 px");
-        Ok(Self { img })
+        let source = Sha256::digest(std::fs::read(path)?).into();
+        let filter = ICON_FILTER.get().copied().unwrap_or_default();
+        Ok(Self {
+            img,
+            source,
+            filter,
+            cache: ICON_CACHE.get().cloned(),
+        })
+    }
+
+    pub fn set_filter(&mut self, filter: ScalerFilter) {
+        self.filter = filter;
+    }
+
+    /// Stores scaled icons in `dir` so that they are reused across builds when
+    /// neither the source image nor the [`ScalerOpts`] changed.
+    pub fn set_cache(&mut self, dir: Option<PathBuf>) {
+        self.cache = dir;
     }
 
     pub fn optimize(&mut self) {
@@ -60,9 +125,52 @@ impl Scaler {
     }
 
     pub fn write<W: Write + Seek>(&self, w: &mut W, opts: ScalerOpts) -> Result<()> {
-        let resized = self
-            .img
-            .resize(opts.scaled_size, opts.scaled_size, FilterType::Nearest);
+        let Some(dir) = self.cache.as_ref() else {
+            return self.write_png(w, opts);
+        };
+        let path = dir.join(format!("{}.png", self.cache_key(opts)));
+        if let Ok(png) = std::fs::read(&path) {
+            w.write_all(&png)?;
+            return Ok(());
+        }
+        let mut png = vec![];
+        self.write_png(&mut Cursor::new(&mut png), opts)?;
+        // the cache is best effort, write to a temporary file first so that
+        // concurrent builds never observe a partially written icon.
+        let tmp = path.with_extension(format!("png.{}", std::process::id()));
+        if std::fs::create_dir_all(dir).is_ok() && std::fs::write(&tmp, &png).is_ok() {
+            std::fs::rename(&tmp, &path).ok();
+        }
+        w.write_all(&png)?;
+        Ok(())
+    }
+
+    /// The color type reflects the effect of [`Scaler::optimize`] on the output.
+    fn cache_key(&self, opts: ScalerOpts) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.source);
+        hasher.update(format!("{:?}", self.img.color()));
+        hasher.update(opts.target_width.to_le_bytes());
+        hasher.update(opts.target_height.to_le_bytes());
+        hasher.update(opts.scaled_size.to_le_bytes());
+        hasher.update([opts.round as u8]);
+        hasher.update(format!("{:?}", self.filter));
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn write_png<W: Write + Seek>(&self, w: &mut W, opts: ScalerOpts) -> Result<()> {
+        let mut resized = self.img.resize(
+            opts.scaled_size,
+            opts.scaled_size,
+            self.filter.filter_type(),
+        );
+        if opts.round {
+            resized = DynamicImage::ImageRgba8(round(resized.to_rgba8()));
+        }
         if opts.scaled_size == opts.target_width && opts.scaled_size == opts.target_height {
             resized.write_to(w, ImageOutputFormat::Png)?;
         } else {
@@ -83,12 +191,28 @@ impl Scaler {
     }
 }
 
+/// Masks out all pixels outside of the circle inscribed in `img`.
+fn round(mut img: RgbaImage) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let radius = std::cmp::min(width, height) as f32 / 2.0;
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - cx;
+        let dy = y as f32 + 0.5 - cy;
+        if dx * dx + dy * dy > radius * radius {
+            pixel[3] = 0;
+        }
+    }
+    img
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ScalerOptsBuilder {
     width: u32,
     height: u32,
     scale: f32,
     padding: f32,
+    round: bool,
 }
 
 impl ScalerOptsBuilder {
@@ -98,6 +222,7 @@ impl ScalerOptsBuilder {
             height,
             scale: 1.0,
             padding: 0.0,
+            round: false,
         }
     }
 
@@ -111,6 +236,12 @@ impl ScalerOptsBuilder {
         self
     }
 
+    /// Crops the icon to a circle.
+    pub fn round(mut self, round: bool) -> Self {
+        self.round = round;
+        self
+    }
+
     pub fn build(self) -> ScalerOpts {
         let target_width = (self.width as f32 * self.scale) as u32;
         let target_height = (self.height as f32 * self.scale) as u32;
@@ -120,6 +251,7 @@ impl ScalerOptsBuilder {
             target_width,
             target_height,
             scaled_size,
+            round: self.round,
         }
     }
 }
@@ -129,6 +261,7 @@ pub struct ScalerOpts {
     target_width: u32,
     target_height: u32,
     scaled_size: u32,
+    round: bool,
 }
 
 impl ScalerOpts {
@@ -137,19 +270,117 @@ impl ScalerOpts {
             target_width: size,
             target_height: size,
             scaled_size: size,
+            round: false,
+        }
+    }
+}
+
+/// Algorithm of a [`Signer`] key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyType {
+    /// RSA with PKCS#1 v1.5 padding and SHA-256
+    Rsa,
+    /// ECDSA using the P-256 curve and SHA-256
+    Ec,
+}
+
+#[derive(Clone)]
+enum PrivateKey {
+    Rsa(Box<RsaPrivateKey>),
+    Ec(p256::SecretKey),
+    Pkcs11(Pkcs11Key, KeyType),
+}
+
+/// Variable `pkcs11-tool` reads the user pin from.
+const PKCS11_PIN_ENV: &str = "X_PKCS11_PIN";
+
+/// A private key that never leaves a PKCS#11 token like an HSM or a YubiKey.
+/// Signing is delegated to `pkcs11-tool` from OpenSC.
+#[derive(Clone, Debug)]
+pub struct Pkcs11Key {
+    /// Path to the PKCS#11 module of the token, e.g. `opensc-pkcs11.so`.
+    pub module: PathBuf,
+    /// Slot containing the token, defaults to the first slot with a token.
+    pub slot: Option<String>,
+    /// Hex encoded id of the key, defaults to the only key on the token.
+    pub key_id: Option<String>,
+    /// User pin, the token prompts for it if not set.
+    pub pin: Option<String>,
+}
+
+impl Pkcs11Key {
+    /// Reads the key from `X_PKCS11_MODULE`, `X_PKCS11_SLOT`, `X_PKCS11_KEY_ID`
+    /// and `X_PKCS11_PIN`. Returns `None` if no module is configured.
+    pub fn from_env() -> Option<Self> {
+        let module = std::env::var_os("X_PKCS11_MODULE")?;
+        Some(Self {
+            module: module.into(),
+            slot: std::env::var("X_PKCS11_SLOT").ok(),
+            key_id: std::env::var("X_PKCS11_KEY_ID").ok(),
+            pin: std::env::var(PKCS11_PIN_ENV).ok(),
+        })
+    }
+
+    fn sign(&self, key_type: KeyType, bytes: &[u8]) -> Result<Vec<u8>> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("x-pkcs11-{}-{}.in", std::process::id(), id));
+        let output = input.with_extension("sig");
+        std::fs::write(&input, bytes)?;
+        let mut cmd = Command::new("pkcs11-tool");
+        cmd.arg("--module").arg(&self.module);
+        if let Some(slot) = self.slot.as_ref() {
+            cmd.arg("--slot").arg(slot);
+        }
+        if let Some(key_id) = self.key_id.as_ref() {
+            cmd.arg("--id").arg(key_id);
         }
+        cmd.arg("--login");
+        // passed through the environment so the pin doesn't show up in the
+        // process list
+        if let Some(pin) = self.pin.as_ref() {
+            cmd.env(PKCS11_PIN_ENV, pin)
+                .arg("--pin")
+                .arg(format!("env:{}", PKCS11_PIN_ENV));
+        }
+        cmd.arg("--sign");
+        match key_type {
+            KeyType::Rsa => cmd.arg("--mechanism").arg("SHA256-RSA-PKCS"),
+            KeyType::Ec => cmd
+                .arg("--mechanism")
+                .arg("ECDSA-SHA256")
+                .arg("--signature-format")
+                .arg("openssl"),
+        };
+        cmd.arg("--input-file")
+            .arg(&input)
+            .arg("--output-file")
+            .arg(&output);
+        let status = cmd.status().context("pkcs11-tool not found");
+        let signature = status.and_then(|status| {
+            anyhow::ensure!(
+                status.success(),
+                "pkcs11-tool failed with exit code {:?}",
+                status
+            );
+            Ok(std::fs::read(&output)?)
+        });
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+        signature
     }
 }
 
 #[derive(Clone)]
 pub struct Signer {
-    key: RsaPrivateKey,
-    pubkey: RsaPublicKey,
+    key: PrivateKey,
     cert: Certificate,
 }
 
 impl Signer {
-    /// Creates a new signer using a private key and a certificate.
+    /// Creates a new signer using a private key and a certificate. The key
+    /// can be either an RSA or a P-256 EC key in PKCS#8 format.
     ///
     /// A new self signed certificate can be generated using openssl:
     /// ```sh
@@ -157,39 +388,99 @@ impl Signer {
     /// cat cert.pem > pem
     /// cat key.pem >> pem
     /// ```
+    /// For an EC key use `-newkey ec -pkeyopt ec_paramgen_curve:prime256v1` instead.
     pub fn new(pem: &str) -> Result<Self> {
         let pem = pem::parse_many(pem)?;
         let key = if let Some(key) = pem.iter().find(|pem| pem.tag == "PRIVATE KEY") {
-            RsaPrivateKey::from_pkcs8_der(&key.contents)?
+            let info = PrivateKeyInfo::try_from(&key.contents[..])?;
+            if info.algorithm.oid == rsa::pkcs1::ALGORITHM_OID {
+                PrivateKey::Rsa(Box::new(RsaPrivateKey::from_pkcs8_der(&key.contents)?))
+            } else if info.algorithm.oid == p256::elliptic_curve::ALGORITHM_OID {
+                PrivateKey::Ec(
+                    p256::SecretKey::from_pkcs8_der(&key.contents)
+                        .context("only P-256 EC keys are supported")?,
+                )
+            } else {
+                anyhow::bail!("unsupported key algorithm {}", info.algorithm.oid);
+            }
         } else {
             anyhow::bail!("no private key found");
         };
-        let cert = if let Some(cert) = pem.iter().find(|pem| pem.tag == "CERTIFICATE") {
-            rasn::der::decode::<Certificate>(&cert.contents)
-                .map_err(|err| anyhow::anyhow!("{}", err))?
-        } else {
-            anyhow::bail!("no certificate found");
+        let cert = parse_cert(&pem)?;
+        Ok(Self { key, cert })
+    }
+
+    /// Creates a new signer using a key stored on a PKCS#11 token. Only the
+    /// certificate is read from `pem`, the key type is derived from its
+    /// public key.
+    pub fn with_pkcs11(pem: &str, key: Pkcs11Key) -> Result<Self> {
+        let cert = parse_cert(&pem::parse_many(pem)?)?;
+        let algorithm = &cert
+            .tbs_certificate
+            .subject_public_key_info
+            .algorithm
+            .algorithm;
+        let key_type = match &algorithm[..] {
+            RSA_ENCRYPTION_OID => KeyType::Rsa,
+            EC_PUBLIC_KEY_OID => KeyType::Ec,
+            _ => anyhow::bail!("unsupported certificate key algorithm {:?}", algorithm),
         };
-        let pubkey = RsaPublicKey::from(&key);
-        Ok(Self { key, pubkey, cert })
+        Ok(Self {
+            key: PrivateKey::Pkcs11(key, key_type),
+            cert,
+        })
     }
 
     pub fn from_path(path: &Path) -> Result<Self> {
         Self::new(&std::fs::read_to_string(path)?)
     }
 
-    pub fn sign(&self, bytes: &[u8]) -> Vec<u8> {
-        let digest = Sha256::digest(bytes);
-        let padding = PaddingScheme::new_pkcs1v15_sign::<sha2::Sha256>();
-        self.key.sign(padding, &digest).unwrap()
+    pub fn key_type(&self) -> KeyType {
+        match &self.key {
+            PrivateKey::Rsa(_) => KeyType::Rsa,
+            PrivateKey::Ec(_) => KeyType::Ec,
+            PrivateKey::Pkcs11(_, key_type) => *key_type,
+        }
+    }
+
+    /// Signs the SHA-256 digest of `bytes`. EC signatures are DER encoded.
+    pub fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        Ok(match &self.key {
+            PrivateKey::Rsa(key) => {
+                let digest = Sha256::digest(bytes);
+                let padding = PaddingScheme::new_pkcs1v15_sign::<sha2::Sha256>();
+                key.sign(padding, &digest)?
+            }
+            PrivateKey::Ec(key) => {
+                let signature: p256::ecdsa::Signature =
+                    p256::ecdsa::SigningKey::from(key).sign(bytes);
+                signature.to_der().as_bytes().to_vec()
+            }
+            PrivateKey::Pkcs11(key, key_type) => key.sign(*key_type, bytes)?,
+        })
     }
 
-    pub fn pubkey(&self) -> &RsaPublicKey {
-        &self.pubkey
+    /// DER encoded `SubjectPublicKeyInfo` of the key.
+    pub fn public_key_der(&self) -> Result<Vec<u8>> {
+        let der = match &self.key {
+            PrivateKey::Rsa(key) => RsaPublicKey::from(&**key).to_public_key_der()?,
+            PrivateKey::Ec(key) => key.public_key().to_public_key_der()?,
+            PrivateKey::Pkcs11(_, _) => {
+                return rasn::der::encode(&self.cert.tbs_certificate.subject_public_key_info)
+                    .map_err(|err| anyhow::anyhow!("{}", err));
+            }
+        };
+        Ok(der.as_ref().to_vec())
     }
 
-    pub fn key(&self) -> &RsaPrivateKey {
-        &self.key
+    /// DER encoded PKCS#8 private key.
+    pub fn private_key_der(&self) -> Result<Vec<u8>> {
+        let der = match &self.key {
+            PrivateKey::Rsa(key) => key.to_pkcs8_der()?,
+            PrivateKey::Ec(key) => key.to_pkcs8_der()?,
+            PrivateKey::Pkcs11(_, _) => anyhow::bail!("the private key can't be read from a token"),
+        };
+        Ok(der.as_bytes().to_vec())
     }
 
     pub fn cert(&self) -> &Certificate {
@@ -197,10 +488,23 @@ impl Signer {
     }
 }
 
+/// `rsaEncryption` from RFC 8017.
+const RSA_ENCRYPTION_OID: &[u32] = &[1, 2, 840, 113549, 1, 1, 1];
+/// `id-ecPublicKey` from RFC 5480.
+const EC_PUBLIC_KEY_OID: &[u32] = &[1, 2, 840, 10045, 2, 1];
+
+fn parse_cert(pem: &[pem::Pem]) -> Result<Certificate> {
+    if let Some(cert) = pem.iter().find(|pem| pem.tag == "CERTIFICATE") {
+        rasn::der::decode::<Certificate>(&cert.contents).map_err(|err| anyhow::anyhow!("{}", err))
+    } else {
+        anyhow::bail!("no certificate found");
+    }
+}
+
 impl std::fmt::Debug for Signer {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("Signer")
-            .field("pubkey", &self.pubkey)
+            .field("key_type", &self.key_type())
             .field("cert", &self.cert)
             .finish_non_exhaustive()
     }
@@ -229,6 +533,39 @@ impl ZipFileOptions {
     }
 }
 
+/// Deflate level used for [`ZipFileOptions::Compressed`] files, files are
+/// stored uncompressed with [`ZipCompression::None`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ZipCompression {
+    #[default]
+    None,
+    Fast,
+    Best,
+}
+
+impl ZipCompression {
+    fn level(self) -> Option<i32> {
+        match self {
+            Self::None => None,
+            Self::Fast => Some(1),
+            Self::Best => Some(9),
+        }
+    }
+}
+
+impl std::str::FromStr for ZipCompression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => Self::None,
+            "fast" => Self::Fast,
+            "best" => Self::Best,
+            _ => anyhow::bail!("unknown compression `{}`, expected none, fast or best", s),
+        })
+    }
+}
+
 pub struct ZipInfo {
     pub cde_start: u64,
     pub cd_start: u64,
@@ -269,25 +606,33 @@ fn find_cde_start_pos<R: Read + Seek>(reader: &mut R) -> Result<u64> {
 
 pub struct Zip {
     zip: ZipWriter<File>,
-    compress: bool,
+    compression: ZipCompression,
+    exclude: Exclude,
 }
 
 impl Zip {
-    pub fn new(path: &Path, compress: bool) -> Result<Self> {
+    pub fn new(path: &Path, compression: ZipCompression) -> Result<Self> {
         Ok(Self {
             zip: ZipWriter::new(File::create(path)?),
-            compress,
+            compression,
+            exclude: Exclude::default(),
         })
     }
 
-    pub fn append(path: &Path, compress: bool) -> Result<Self> {
+    pub fn append(path: &Path, compression: ZipCompression) -> Result<Self> {
         let f = OpenOptions::new().read(true).write(true).open(path)?;
         Ok(Self {
             zip: ZipWriter::new_append(f)?,
-            compress,
+            compression,
+            exclude: Exclude::default(),
         })
     }
 
+    /// Files skipped by [`Zip::add_directory`].
+    pub fn set_exclude(&mut self, exclude: Exclude) {
+        self.exclude = exclude;
+    }
+
     pub fn add_file(&mut self, source: &Path, dest: &Path, opts: ZipFileOptions) -> Result<()> {
         let mut f = File::open(source)
             .with_context(|| format!("While opening file `{}`", source.display()))?;
@@ -302,8 +647,8 @@ impl Zip {
         dest: &Path,
         opts: ZipFileOptions,
     ) -> Result<()> {
-        add_recursive(self, source, dest, opts)?;
-        Ok(())
+        let exclude = self.exclude.with_ignore_file(source)?;
+        add_recursive(self, source, dest, Path::new(""), &exclude, opts)
     }
 
     pub fn add_zip_file(&mut self, f: ZipFile) -> Result<()> {
@@ -328,12 +673,14 @@ impl Zip {
             .map(|seg| seg.to_str().unwrap())
             .collect::<Vec<_>>()
             .join("/");
-        let compression_method = if self.compress {
-            opts.compression_method()
+        let level = self.compression.level();
+        let zopts = if level.is_some() && opts.compression_method() != CompressionMethod::Stored {
+            FileOptions::default()
+                .compression_method(opts.compression_method())
+                .compression_level(level)
         } else {
-            CompressionMethod::Stored
+            FileOptions::default().compression_method(CompressionMethod::Stored)
         };
-        let zopts = FileOptions::default().compression_method(compression_method);
         self.zip.start_file_aligned(name, zopts, opts.alignment())?;
         Ok(())
     }
@@ -344,17 +691,28 @@ impl Zip {
     }
 }
 
-fn add_recursive(zip: &mut Zip, source: &Path, dest: &Path, opts: ZipFileOptions) -> Result<()> {
+fn add_recursive(
+    zip: &mut Zip,
+    source: &Path,
+    dest: &Path,
+    relative: &Path,
+    exclude: &Exclude,
+    opts: ZipFileOptions,
+) -> Result<()> {
     for entry in std::fs::read_dir(source)
         .with_context(|| format!("While reading directory `{}`", source.display()))?
     {
         let entry = entry?;
         let file_name = entry.file_name();
+        let relative = relative.join(&file_name);
+        let file_type = entry.file_type()?;
+        if exclude.matches(&relative, file_type.is_dir()) {
+            continue;
+        }
         let source = source.join(&file_name);
         let dest = dest.join(&file_name);
-        let file_type = entry.file_type()?;
         if file_type.is_dir() {
-            add_recursive(zip, &source, &dest, opts)?;
+            add_recursive(zip, &source, &dest, &relative, exclude, opts)?;
         } else if file_type.is_file() {
             zip.add_file(&source, &dest, opts)?;
         }
@@ -372,16 +730,106 @@ impl Write for Zip {
     }
 }
 
+/// Name of the ignore file in gitignore syntax which is honored in every
+/// copied directory.
+pub const IGNORE_FILE: &str = ".xbuildignore";
+
+/// Files which are never packaged from a copied directory.
+const DEFAULT_IGNORE: [&str; 3] = [".DS_Store", "Thumbs.db", IGNORE_FILE];
+
+/// Glob patterns of files and directories to leave out when copying a
+/// directory, matched against the path relative to the copied directory.
+/// Ignore files are matched with gitignore semantics and take precedence over
+/// the patterns, the last added one first, so negated patterns of an ignore
+/// file can include files again.
+#[derive(Clone, Debug, Default)]
+pub struct Exclude {
+    patterns: Vec<glob::Pattern>,
+    ignores: Vec<Gitignore>,
+}
+
+impl Exclude {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self> {
+        let mut exclude = Self::default();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            let glob = glob::Pattern::new(pattern)
+                .with_context(|| format!("invalid exclude pattern `{}`", pattern))?;
+            exclude.patterns.push(glob);
+        }
+        Ok(exclude)
+    }
+
+    /// Adds the lines of a gitignore file.
+    pub fn add_ignore(&mut self, ignore: &str) -> Result<()> {
+        let mut builder = GitignoreBuilder::new(".");
+        for line in ignore.lines() {
+            builder
+                .add_line(None, line)
+                .with_context(|| format!("invalid ignore pattern `{}`", line))?;
+        }
+        self.ignores.push(builder.build()?);
+        Ok(())
+    }
+
+    /// Extends the patterns with the default ignores and the [`IGNORE_FILE`]
+    /// of `dir` if it exists.
+    pub fn with_ignore_file(&self, dir: &Path) -> Result<Self> {
+        let mut exclude = self.clone();
+        exclude.add_ignore(&DEFAULT_IGNORE.join("\n"))?;
+        let path = dir.join(IGNORE_FILE);
+        if path.exists() {
+            let ignore = std::fs::read_to_string(&path)?;
+            exclude
+                .add_ignore(&ignore)
+                .with_context(|| format!("invalid ignore file `{}`", path.display()))?;
+        }
+        Ok(exclude)
+    }
+
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        for ignore in self.ignores.iter().rev() {
+            match ignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        let opts = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_path_with(path, opts))
+    }
+}
+
 pub fn copy_dir_all(source: &Path, dest: &Path) -> Result<()> {
+    copy_dir_filtered(source, dest, &Exclude::default())
+}
+
+/// Like [`copy_dir_all`] but skips everything matching `exclude` or the
+/// [`IGNORE_FILE`] of `source`.
+pub fn copy_dir_filtered(source: &Path, dest: &Path, exclude: &Exclude) -> Result<()> {
+    let exclude = exclude.with_ignore_file(source)?;
+    copy_recursive(source, dest, Path::new(""), &exclude)
+}
+
+fn copy_recursive(source: &Path, dest: &Path, relative: &Path, exclude: &Exclude) -> Result<()> {
     for entry in std::fs::read_dir(source)? {
         let entry = entry?;
         let file_name = entry.file_name();
+        let relative = relative.join(&file_name);
+        let file_type = entry.file_type()?;
+        if exclude.matches(&relative, file_type.is_dir()) {
+            continue;
+        }
         let source = source.join(&file_name);
         let dest = dest.join(&file_name);
-        let file_type = entry.file_type()?;
         if file_type.is_dir() {
             std::fs::create_dir_all(&dest)?;
-            copy_dir_all(&source, &dest)?;
+            copy_recursive(&source, &dest, &relative, exclude)?;
         } else if file_type.is_file() {
             std::fs::copy(&source, &dest)?;
         } else if file_type.is_symlink() {
@@ -478,14 +926,125 @@ pub fn extract_zip_file(archive: &Path, name: &str) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// SHA-256 fingerprint of the DER encoded `cert`, formatted like `keytool`.
+pub fn cert_fingerprint(cert: &Certificate) -> Result<String> {
+    let der = rasn::der::encode(cert).map_err(|err| anyhow::anyhow!("{}", err))?;
+    Ok(cert::fingerprint(&Sha256::digest(der)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     const PEM: &str = include_str!("../assets/test.pem");
 
+    const EC_PEM: &str = include_str!("../assets/test-ec.pem");
+
+    #[test]
+    fn exclude() {
+        let exclude = Exclude::new(&["**/*.d", "**/.DS_Store", "fixtures"]).unwrap();
+        assert!(exclude.matches(Path::new("main.d"), false));
+        assert!(exclude.matches(Path::new("deps/main.d"), false));
+        assert!(exclude.matches(Path::new("a/b/.DS_Store"), false));
+        assert!(exclude.matches(Path::new("fixtures"), false));
+        assert!(!exclude.matches(Path::new("assets/fixtures"), false));
+        assert!(!exclude.matches(Path::new("main.dll"), false));
+    }
+
+    #[test]
+    fn exclude_ignore_file() {
+        let dir = std::env::temp_dir().join(format!("xcommon-ignore-file-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(IGNORE_FILE),
+            "# generated\n*.tmp\n/cache/\n!keep.tmp\nsub/dir\n\\#notes\n",
+        )
+        .unwrap();
+        let exclude = Exclude::new(&["*.d"]).unwrap();
+        let exclude = exclude.with_ignore_file(&dir).unwrap();
+        assert!(exclude.matches(Path::new("main.d"), false));
+        assert!(exclude.matches(Path::new("a/b.tmp"), false));
+        assert!(!exclude.matches(Path::new("a/keep.tmp"), false));
+        assert!(exclude.matches(Path::new("cache"), true));
+        assert!(!exclude.matches(Path::new("a/cache"), true));
+        // a trailing slash only matches directories
+        assert!(!exclude.matches(Path::new("cache"), false));
+        assert!(exclude.matches(Path::new("#notes"), false));
+        assert!(exclude.matches(Path::new("sub/dir"), true));
+        assert!(exclude.matches(Path::new("images/.DS_Store"), false));
+        assert!(exclude.matches(Path::new("Thumbs.db"), false));
+        assert!(exclude.matches(Path::new(IGNORE_FILE), false));
+        assert!(!exclude.matches(Path::new("image.png"), false));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn create_signer() {
         Signer::new(PEM).unwrap();
     }
+
+    #[test]
+    fn create_ec_signer() {
+        use p256::ecdsa::signature::Verifier;
+        use p256::pkcs8::DecodePublicKey;
+
+        let signer = Signer::new(EC_PEM).unwrap();
+        assert_eq!(signer.key_type(), KeyType::Ec);
+        let signature = signer.sign(b"hello").unwrap();
+        let key = p256::ecdsa::VerifyingKey::from_public_key_der(&signer.public_key_der().unwrap())
+            .unwrap();
+        let signature = p256::ecdsa::Signature::from_der(&signature).unwrap();
+        key.verify(b"hello", &signature).unwrap();
+    }
+
+    #[test]
+    fn scaler_cache() {
+        let dir = std::env::temp_dir().join(format!("xcommon-scaler-cache-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let icon = dir.join("icon.png");
+        RgbaImage::from_pixel(512, 512, image::Rgba([255, 0, 0, 255]))
+            .save(&icon)
+            .unwrap();
+        let cache = dir.join("cache");
+
+        let mut scaler = Scaler::open(&icon).unwrap();
+        scaler.set_cache(Some(cache.clone()));
+        let rgba = scaler.to_vec(ScalerOpts::new(48));
+        assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 1);
+        assert_eq!(scaler.to_vec(ScalerOpts::new(48)), rgba);
+        scaler.optimize();
+        let rgb = scaler.to_vec(ScalerOpts::new(48));
+        assert_ne!(rgb, rgba);
+        assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scaler_filter() {
+        let icon =
+            std::env::temp_dir().join(format!("xcommon-scaler-filter-{}.png", std::process::id()));
+        RgbaImage::from_fn(512, 512, |x, y| {
+            let c = if (x / 3 + y / 3) % 2 == 0 { 255 } else { 0 };
+            image::Rgba([c, c, c, 255])
+        })
+        .save(&icon)
+        .unwrap();
+        let mut scaler = Scaler::open(&icon).unwrap();
+        let mut buf = vec![];
+        scaler
+            .write_png(&mut Cursor::new(&mut buf), ScalerOpts::new(60))
+            .unwrap();
+        let smooth = image::load_from_memory(&buf).unwrap().to_luma8();
+        scaler.set_filter(ScalerFilter::Nearest);
+        buf.clear();
+        scaler
+            .write_png(&mut Cursor::new(&mut buf), ScalerOpts::new(60))
+            .unwrap();
+        let nearest = image::load_from_memory(&buf).unwrap().to_luma8();
+        std::fs::remove_file(&icon).unwrap();
+        assert!(nearest.pixels().all(|p| p[0] == 0 || p[0] == 255));
+        assert!(smooth.pixels().any(|p| p[0] != 0 && p[0] != 255));
+    }
 }
@@ -52,7 +52,7 @@ pub fn list_needed_libs_recursively(
 }
 
 /// List all required shared libraries as per the dynamic section
-fn list_needed_libs(library_path: &Path) -> Result<HashSet<String>> {
+pub fn list_needed_libs(library_path: &Path) -> Result<HashSet<String>> {
     let mut readelf = Command::new("llvm-readobj");
     let readelf = readelf.arg("--needed-libs").arg(library_path);
     let output = readelf
@@ -100,3 +100,224 @@ fn find_library_path(paths: &[&Path], library: &str) -> Result<Option<PathBuf>>
     }
     Ok(None)
 }
+
+/// Symbols in the dynamic symbol table of a shared library.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct DynamicSymbols {
+    /// Undefined symbols with global binding. Weak imports are left out, as
+    /// they may be missing at runtime.
+    pub imported: HashSet<String>,
+    /// Symbols the library exports.
+    pub defined: HashSet<String>,
+}
+
+/// Reads the dynamic symbol table with `llvm-readelf`.
+pub fn dynamic_symbols(library_path: &Path) -> Result<DynamicSymbols> {
+    let mut readelf = Command::new("llvm-readelf");
+    let readelf = readelf.arg("--dyn-syms").arg("--wide").arg(library_path);
+    let output = readelf
+        .output()
+        .with_context(|| format!("Failed to run `{:?}`", readelf))?;
+    ensure!(
+        output.status.success(),
+        "Failed to run `{:?}`: {}",
+        readelf,
+        output.status
+    );
+    Ok(parse_dyn_syms(std::str::from_utf8(&output.stdout)?))
+}
+
+fn parse_dyn_syms(output: &str) -> DynamicSymbols {
+    let mut symbols = DynamicSymbols::default();
+    for line in output.lines() {
+        // `Num: Value Size Type Bind Vis Ndx Name`
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let [num, _, _, _, bind, _, ndx, name] = fields[..] else {
+            continue;
+        };
+        let is_entry = num
+            .strip_suffix(':')
+            .is_some_and(|num| num.parse::<u32>().is_ok());
+        if !is_entry {
+            continue;
+        }
+        // strip the symbol version
+        let name = name.split('@').next().unwrap().to_string();
+        match (ndx, bind) {
+            ("UND", "GLOBAL") => {
+                symbols.imported.insert(name);
+            }
+            ("UND", _) => {}
+            _ => {
+                symbols.defined.insert(name);
+            }
+        }
+    }
+    symbols
+}
+
+/// Build id of an ELF binary or the `LC_UUID` of a Mach-O binary as lower
+/// case hex. Stripping keeps it, so it identifies the unstripped binary.
+pub fn build_id(path: &Path) -> Result<Option<String>> {
+    let mut readobj = Command::new("llvm-readobj");
+    let readobj = readobj.arg("--notes").arg(path);
+    let output = readobj
+        .output()
+        .with_context(|| format!("Failed to run `{:?}`", readobj))?;
+    if output.status.success() {
+        if let Some(id) = parse_build_id(std::str::from_utf8(&output.stdout)?) {
+            return Ok(Some(id));
+        }
+    }
+    let mut dwarfdump = Command::new("llvm-dwarfdump");
+    let dwarfdump = dwarfdump.arg("--uuid").arg(path);
+    let output = dwarfdump
+        .output()
+        .with_context(|| format!("Failed to run `{:?}`", dwarfdump))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(parse_uuid(std::str::from_utf8(&output.stdout)?))
+}
+
+/// `Build ID: <hex>` in the output of `llvm-readobj --notes`.
+fn parse_build_id(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let id = line.trim().strip_prefix("Build ID:")?.trim();
+        Some(id.to_lowercase())
+    })
+}
+
+/// `UUID: <uuid> (<arch>) <path>` in the output of `llvm-dwarfdump --uuid`.
+fn parse_uuid(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let uuid = line
+            .trim()
+            .strip_prefix("UUID:")?
+            .split_whitespace()
+            .next()?;
+        Some(uuid.replace('-', "").to_lowercase())
+    })
+}
+
+/// Copies the debug info of an ELF binary into `debug_file`, for debuggers
+/// to find it through the `.gnu_debuglink` added by [`strip`].
+pub fn only_keep_debug(path: &Path, debug_file: &Path) -> Result<()> {
+    let mut objcopy = Command::new("llvm-objcopy");
+    objcopy.arg("--only-keep-debug").arg(path).arg(debug_file);
+    let status = objcopy
+        .status()
+        .with_context(|| format!("Failed to run `{:?}`", objcopy))?;
+    ensure!(
+        status.success(),
+        "Failed to run `{:?}`: {}",
+        objcopy,
+        status
+    );
+    Ok(())
+}
+
+/// Links the debug info of a Mach-O binary into a `.dSYM` bundle, with
+/// `llvm-dsymutil` when xcode's `dsymutil` isn't installed.
+pub fn dsymutil(path: &Path, dsym: &Path) -> Result<()> {
+    let mut dsymutil = Command::new("dsymutil");
+    dsymutil.arg(path).arg("-o").arg(dsym);
+    let status = match dsymutil.status() {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            dsymutil = Command::new("llvm-dsymutil");
+            dsymutil.arg(path).arg("-o").arg(dsym);
+            dsymutil.status()
+        }
+        status => status,
+    };
+    let status = status.with_context(|| format!("Failed to run `{:?}`", dsymutil))?;
+    ensure!(
+        status.success(),
+        "Failed to run `{:?}`: {}",
+        dsymutil,
+        status
+    );
+    Ok(())
+}
+
+/// Strips an ELF binary in place, linking it to `debug_file` when given.
+/// Mach-O binaries only have their local symbols removed, like `strip -x`,
+/// as their debug info lives in a dSYM.
+pub fn strip(path: &Path, macho: bool, debug_file: Option<&Path>) -> Result<()> {
+    let mut strip = if macho {
+        let mut strip = Command::new("llvm-strip");
+        strip.arg("-x");
+        strip
+    } else {
+        let mut strip = Command::new("llvm-objcopy");
+        strip.arg("--strip-all");
+        if let Some(debug_file) = debug_file {
+            // replaces the link of a previous build
+            let mut link = OsString::from("--add-gnu-debuglink=");
+            link.push(debug_file);
+            strip.arg("--remove-section=.gnu_debuglink").arg(link);
+        }
+        strip
+    };
+    strip.arg(path);
+    let status = strip
+        .status()
+        .with_context(|| format!("Failed to run `{:?}`", strip))?;
+    ensure!(status.success(), "Failed to run `{:?}`: {}", strip, status);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dyn_syms() {
+        let output = r#"
+Symbol table '.dynsym' contains 5 entries:
+   Num:    Value          Size Type    Bind   Vis       Ndx Name
+     0: 0000000000000000     0 NOTYPE  LOCAL  DEFAULT   UND
+     1: 0000000000000000     0 FUNC    GLOBAL DEFAULT   UND AHardwareBuffer_allocate
+     2: 0000000000000000     0 FUNC    GLOBAL DEFAULT   UND malloc@LIBC
+     3: 0000000000000000     0 FUNC    WEAK   DEFAULT   UND getrandom@LIBC
+     4: 0000000000012340    24 FUNC    GLOBAL DEFAULT    12 ANativeActivity_onCreate
+"#;
+        let symbols = parse_dyn_syms(output);
+        let mut imported = symbols.imported.into_iter().collect::<Vec<_>>();
+        imported.sort();
+        assert_eq!(imported, ["AHardwareBuffer_allocate", "malloc"]);
+        assert_eq!(
+            symbols.defined.into_iter().collect::<Vec<_>>(),
+            ["ANativeActivity_onCreate"]
+        );
+    }
+
+    #[test]
+    fn test_parse_build_id() {
+        let output = r#"
+Notes [
+  NoteSection {
+    Name: .note.gnu.build-id
+    Offset: 0x238
+    Size: 0x24
+    Note {
+      Owner: GNU
+      Data size: 0x14
+      Type: NT_GNU_BUILD_ID (unique build ID bitstring)
+      Build ID: 5d0f6e1c29b3e3b8c4a94f3a1e5b2c7d8e9f0a1b
+    }
+  }
+]
+"#;
+        assert_eq!(
+            parse_build_id(output).as_deref(),
+            Some("5d0f6e1c29b3e3b8c4a94f3a1e5b2c7d8e9f0a1b")
+        );
+        assert_eq!(parse_build_id("Notes [\n]\n"), None);
+        let output = "UUID: 1A2B3C4D-5E6F-7081-92A3-B4C5D6E7F809 (arm64) libapp.dylib\n";
+        assert_eq!(
+            parse_uuid(output).as_deref(),
+            Some("1a2b3c4d5e6f708192a3b4c5d6e7f809")
+        );
+    }
+}